@@ -0,0 +1,120 @@
+//! Renders a MIDI file with a SoundFont to a WAV file.
+
+use std::{env, error::Error, fs::File, io::BufWriter, process::ExitCode, sync::Arc};
+
+use midix_synth::prelude::*;
+
+const USAGE: &str = "\
+Usage: midix-render [OPTIONS] <SOUNDFONT> <MIDI_FILE> <OUTPUT>
+
+Options:
+  --sample-rate <RATE>     The sample rate [default: 44100]
+  --polyphony <COUNT>      The maximum polyphony [default: 64]
+  --no-reverb              Disables the reverb
+  --no-chorus              Disables the chorus
+  --format <FORMAT>        The sample format: 16, 24 or float [default: 16]
+  --dither <DITHER>        The dither for integer formats: none, tpdf or shaped [default: tpdf]
+  --tail <SECONDS>         The maximum length rendered after the end [default: 10]
+  -h, --help               Prints this help";
+
+struct Options {
+    sound_font: String,
+    midi_file: String,
+    output: String,
+    settings: SynthesizerSettings,
+    format: WavFormat,
+    dither: Dither,
+    tail: f64,
+}
+
+fn parse_options() -> Result<Option<Options>, Box<dyn Error>> {
+    let mut settings = SynthesizerSettings::default();
+    let mut format = WavFormat::Pcm16;
+    let mut dither = Dither::Triangular;
+    let mut tail = 10.0;
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--sample-rate" => settings.sample_rate = value()?.parse()?,
+            "--polyphony" => settings.maximum_polyphony = value()?.parse()?,
+            "--no-reverb" => settings.enable_reverb = false,
+            "--no-chorus" => settings.enable_chorus = false,
+            "--format" => {
+                format = match value()?.as_str() {
+                    "16" => WavFormat::Pcm16,
+                    "24" => WavFormat::Pcm24,
+                    "float" => WavFormat::Float32,
+                    other => return Err(format!("unknown format: {other}").into()),
+                }
+            }
+            "--dither" => {
+                dither = match value()?.as_str() {
+                    "none" => Dither::None,
+                    "tpdf" => Dither::Triangular,
+                    "shaped" => Dither::NoiseShaped,
+                    other => return Err(format!("unknown dither: {other}").into()),
+                }
+            }
+            "--tail" => tail = value()?.parse()?,
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {arg}").into()),
+            _ => paths.push(arg),
+        }
+    }
+
+    let [sound_font, midi_file, output] = <[String; 3]>::try_from(paths)
+        .map_err(|_| "expected a SoundFont, a MIDI file and an output path")?;
+
+    Ok(Some(Options {
+        sound_font,
+        midi_file,
+        output,
+        settings,
+        format,
+        dither,
+        tail,
+    }))
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let sound_font = Arc::new(SoundFont::new(&mut File::open(&options.sound_font)?)?);
+    let midi_file = Arc::new(MidiFile::new(&mut File::open(&options.midi_file)?)?);
+
+    let waveform = render_midi_file(&sound_font, &midi_file, &options.settings, options.tail)?;
+
+    let mut writer = BufWriter::new(File::create(&options.output)?);
+    write_wav(
+        &mut writer,
+        waveform.sample_rate as u32,
+        &[&waveform.left, &waveform.right],
+        options.format,
+        options.dither,
+    )?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Prints the contents of a SoundFont.
+
+use std::{env, error::Error, fs::File, process::ExitCode};
+
+use midix_synth::prelude::*;
+
+const USAGE: &str = "\
+Usage: sf2-info [OPTIONS] <SOUNDFONT>
+
+Options:
+  --zones    Also dumps the zones of the presets and instruments
+  --json     Prints everything as JSON
+  -h, --help Prints this help";
+
+fn run() -> Result<bool, Box<dyn Error>> {
+    let mut zones = false;
+    let mut json = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(false),
+            "--zones" => zones = true,
+            "--json" => json = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {arg}").into()),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err("expected a single SoundFont".into()),
+        }
+    }
+
+    let path = path.ok_or("expected a SoundFont")?;
+    let sound_font = SoundFont::new(&mut File::open(path)?)?;
+
+    let report = SoundFontReport::new(&sound_font);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text(zones));
+    }
+
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
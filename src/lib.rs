@@ -1,18 +1,22 @@
 pub(crate) mod reader;
+pub mod sequencer;
 pub mod soundfont;
 #[allow(missing_docs)]
 #[allow(clippy::module_inception)]
 pub mod synthesizer;
 
 pub(crate) mod utils;
+pub mod wav;
 
 #[cfg(test)]
 mod tests;
 
 pub mod prelude {
     pub use crate::{
+        sequencer::*,
         soundfont::{instrument::*, preset::*, *},
         synthesizer::*,
+        wav::*,
     };
 
     pub(crate) use crate::{reader::*, soundfont::generator::*};
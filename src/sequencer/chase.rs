@@ -0,0 +1,121 @@
+use midix::prelude::ChannelVoiceMessage;
+
+use crate::prelude::*;
+
+// Tracks the notes held on each channel, to rebuild them after a seek or a loop.
+#[derive(Clone)]
+pub(crate) struct ChaseState {
+    // The velocity of each sounding note, including the ones held by the sustain pedal.
+    velocities: [[u8; 128]; 16],
+    pressed: [[bool; 128]; 16],
+    hold_pedal: [bool; 16],
+}
+
+impl ChaseState {
+    pub(crate) fn new() -> Self {
+        Self {
+            velocities: [[0; 128]; 16],
+            pressed: [[false; 128]; 16],
+            hold_pedal: [false; 16],
+        }
+    }
+
+    // Updates the held notes with the message.
+    // Returns `false` if the message only starts or stops notes.
+    pub(crate) fn update(&mut self, message: ChannelVoiceMessage) -> bool {
+        let status = message.status();
+        let channel = (status & 0x0F) as usize;
+        let key = (message.data_1_byte() & 0x7F) as usize;
+        let data2 = message.data_2_byte().unwrap_or_default();
+
+        match status & 0xF0 {
+            0x90 if data2 > 0 => {
+                self.velocities[channel][key] = data2;
+                self.pressed[channel][key] = true;
+                false
+            }
+            0x80 | 0x90 => {
+                self.pressed[channel][key] = false;
+                if !self.hold_pedal[channel] {
+                    self.velocities[channel][key] = 0;
+                }
+                false
+            }
+            // Poly pressure only applies to sounding notes.
+            0xA0 => false,
+            0xB0 => match key {
+                0x40 => {
+                    self.hold_pedal[channel] = data2 >= 64;
+                    if !self.hold_pedal[channel] {
+                        self.release_unpressed(channel);
+                    }
+                    true
+                }
+                0x78 => {
+                    self.velocities[channel] = [0; 128];
+                    self.pressed[channel] = [false; 128];
+                    false
+                }
+                0x7B => {
+                    self.pressed[channel] = [false; 128];
+                    if !self.hold_pedal[channel] {
+                        self.velocities[channel] = [0; 128];
+                    }
+                    false
+                }
+                0x79 => {
+                    self.hold_pedal[channel] = false;
+                    self.release_unpressed(channel);
+                    true
+                }
+                _ => true,
+            },
+            _ => true,
+        }
+    }
+
+    // Sends the message to the synthesizer unless it starts or stops notes.
+    pub(crate) fn process(&mut self, synthesizer: &mut Synthesizer, message: ChannelVoiceMessage) {
+        if self.update(message) {
+            synthesizer.process_midi_message(message);
+        }
+    }
+
+    fn release_unpressed(&mut self, channel: usize) {
+        for (velocity, pressed) in self.velocities[channel]
+            .iter_mut()
+            .zip(self.pressed[channel].iter())
+        {
+            if !pressed {
+                *velocity = 0;
+            }
+        }
+    }
+
+    // Starts and stops the notes so that the held notes become the ones of the target.
+    // The notes held in both are left untouched, and the ones only held by the sustain pedal
+    // in the target are released right away, so the pedal keeps them.
+    pub(crate) fn transition(&self, target: &ChaseState, synthesizer: &mut Synthesizer) {
+        for channel in 0..16 {
+            for key in 0..128 {
+                let current = self.velocities[channel][key] > 0;
+                let velocity = target.velocities[channel][key];
+                let pressed = target.pressed[channel][key];
+
+                if velocity == 0 {
+                    if current {
+                        synthesizer.note_off(channel as u8, key as u8);
+                    }
+                    continue;
+                }
+
+                if !current {
+                    synthesizer.note_on(channel as u8, key as u8, velocity);
+                }
+                if !pressed && (!current || self.pressed[channel][key]) {
+                    synthesizer.note_off(channel as u8, key as u8);
+                }
+            }
+        }
+    }
+}
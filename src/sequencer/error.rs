@@ -0,0 +1,49 @@
+use core::error;
+use core::fmt;
+use std::io;
+
+use midix::prelude::ReaderError;
+
+/// Represents an error when loading a MIDI file.
+#[derive(Debug)]
+pub enum MidiFileError {
+    IoError(io::Error),
+    ReaderError(ReaderError),
+    HeaderNotFound,
+    UnsupportedFormat,
+}
+
+impl error::Error for MidiFileError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MidiFileError::IoError(err) => Some(err),
+            MidiFileError::ReaderError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MidiFileError::IoError(err) => err.fmt(f),
+            MidiFileError::ReaderError(err) => err.fmt(f),
+            MidiFileError::HeaderNotFound => write!(f, "the MIDI file has no header chunk"),
+            MidiFileError::UnsupportedFormat => {
+                write!(f, "only the MIDI file formats 0 and 1 are supported")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for MidiFileError {
+    fn from(err: io::Error) -> Self {
+        MidiFileError::IoError(err)
+    }
+}
+
+impl From<ReaderError> for MidiFileError {
+    fn from(err: ReaderError) -> Self {
+        MidiFileError::ReaderError(err)
+    }
+}
@@ -0,0 +1,220 @@
+use bevy_platform::prelude::*;
+use midix::{file::builder::event::FileEvent, prelude::*};
+use std::io::Read;
+
+use super::MidiFileError;
+
+/// Represents a standard MIDI file (format 0 or 1).
+///
+/// The events of all the tracks are merged into a single sequence,
+/// and their times are resolved with the tempo map.
+pub struct MidiFile {
+    pub(crate) events: Vec<MidiFileEvent>,
+    tempo_map: Vec<TempoSegment>,
+    length: f64,
+    loop_start: f64,
+    loop_end: f64,
+}
+
+pub(crate) struct MidiFileEvent {
+    /// The time of the event in seconds.
+    pub(crate) time: f64,
+    pub(crate) message: ChannelVoiceMessage,
+}
+
+impl MidiFileEvent {
+    /// Gets the time of the event in samples.
+    pub(crate) fn get_sample(&self, sample_rate: i32) -> u64 {
+        (self.time * sample_rate as f64).round() as u64
+    }
+}
+
+// A span of the file with a constant tempo.
+struct TempoSegment {
+    tick: u64,
+    time: f64,
+    seconds_per_tick: f64,
+}
+
+enum TrackMessageKind {
+    ChannelVoice(ChannelVoiceMessage),
+    Tempo(u32),
+    LoopStart,
+    LoopEnd,
+    Other,
+}
+
+impl MidiFile {
+    /// Loads a MIDI file from the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The data stream used to load the MIDI file.
+    pub fn new<R: Read + ?Sized>(reader: &mut R) -> Result<Self, MidiFileError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        MidiFile::from_bytes(&data)
+    }
+
+    /// Loads a MIDI file from bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The content of the MIDI file.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MidiFileError> {
+        let mut reader = Reader::from_byte_slice(data);
+
+        let mut timing = None;
+        // The events of all the tracks, as (tick, track, message).
+        let mut messages: Vec<(u64, usize, TrackMessageKind)> = Vec::new();
+        let mut track_count = 0;
+        let mut tick = 0_u64;
+
+        loop {
+            let event = match reader.read_event() {
+                Ok(event) => event,
+                Err(err) if matches!(err.error_kind(), ReaderErrorKind::Eof) => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            match event {
+                FileEvent::Header(header) => {
+                    if header.format_type() == FormatType::SequentiallyIndependent {
+                        return Err(MidiFileError::UnsupportedFormat);
+                    }
+                    timing = Some(header.timing());
+                }
+                FileEvent::Track(_) => {
+                    track_count += 1;
+                    tick = 0;
+                }
+                FileEvent::TrackEvent(event) => {
+                    tick += event.delta_ticks() as u64;
+                    let message = match event.into_event() {
+                        TrackMessage::ChannelVoice(message) => {
+                            TrackMessageKind::ChannelVoice(message)
+                        }
+                        TrackMessage::Meta(MetaMessage::Tempo(tempo)) => {
+                            TrackMessageKind::Tempo(tempo.micros_per_quarter_note())
+                        }
+                        TrackMessage::Meta(MetaMessage::Marker(text)) => match text.as_str() {
+                            Ok(text) if text.trim().eq_ignore_ascii_case("loopStart") => {
+                                TrackMessageKind::LoopStart
+                            }
+                            Ok(text) if text.trim().eq_ignore_ascii_case("loopEnd") => {
+                                TrackMessageKind::LoopEnd
+                            }
+                            _ => TrackMessageKind::Other,
+                        },
+                        // The payload of SysEx events is not exposed by midix.
+                        _ => TrackMessageKind::Other,
+                    };
+                    messages.push((tick, track_count, message));
+                }
+                FileEvent::Unknown(_) => (),
+            }
+        }
+
+        let timing = timing.ok_or(MidiFileError::HeaderNotFound)?;
+
+        // The sort is stable, so the events at the same tick keep the order of the tracks.
+        messages.sort_by_key(|(tick, track, _)| (*tick, *track));
+
+        let mut events = Vec::new();
+        let mut tempo_map = vec![TempoSegment {
+            tick: 0,
+            time: 0.0,
+            seconds_per_tick: MidiFile::get_seconds_per_tick(timing, 500000),
+        }];
+        let mut length = 0.0;
+        let mut loop_start = None;
+        let mut loop_end = None;
+        for (tick, _, message) in messages {
+            let time = MidiFile::resolve_time(&tempo_map, tick);
+            length = time;
+
+            match message {
+                TrackMessageKind::ChannelVoice(message) => {
+                    // The loop start of RPG Maker.
+                    if message.status() & 0xF0 == 0xB0 && message.data_1_byte() == 111 {
+                        loop_start.get_or_insert(time);
+                    }
+                    events.push(MidiFileEvent { time, message })
+                }
+                TrackMessageKind::LoopStart => {
+                    loop_start.get_or_insert(time);
+                }
+                TrackMessageKind::LoopEnd => {
+                    loop_end.get_or_insert(time);
+                }
+                TrackMessageKind::Tempo(tempo) => tempo_map.push(TempoSegment {
+                    tick,
+                    time,
+                    seconds_per_tick: MidiFile::get_seconds_per_tick(timing, tempo),
+                }),
+                TrackMessageKind::Other => (),
+            }
+        }
+
+        Ok(Self {
+            events,
+            tempo_map,
+            length,
+            loop_start: loop_start.unwrap_or_default(),
+            loop_end: loop_end.unwrap_or(length),
+        })
+    }
+
+    fn get_seconds_per_tick(timing: Timing, tempo: u32) -> f64 {
+        match timing {
+            Timing::TicksPerQuarterNote(ticks) => {
+                tempo as f64 / (1000000.0 * ticks.ticks_per_quarter_note().max(1) as f64)
+            }
+            Timing::Smpte(smpte) => {
+                1.0 / (smpte.fps().as_f64() * smpte.ticks_per_frame().max(1) as f64)
+            }
+        }
+    }
+
+    fn resolve_time(tempo_map: &[TempoSegment], tick: u64) -> f64 {
+        let segment = tempo_map
+            .iter()
+            .rev()
+            .find(|segment| segment.tick <= tick)
+            .unwrap_or(&tempo_map[0]);
+        segment.time + (tick - segment.tick) as f64 * segment.seconds_per_tick
+    }
+
+    /// Gets the time in seconds at the specified tick, following the tempo map.
+    ///
+    /// # Arguments
+    ///
+    /// * `tick` - The position in ticks.
+    pub fn get_time_at_tick(&self, tick: u64) -> f64 {
+        MidiFile::resolve_time(&self.tempo_map, tick)
+    }
+
+    /// Gets the length of the MIDI file in seconds.
+    pub fn get_length(&self) -> f64 {
+        self.length
+    }
+
+    /// Gets the start of the loop in seconds.
+    ///
+    /// # Remarks
+    ///
+    /// This is the first controller 111 (the loop start of RPG Maker) or `loopStart` marker,
+    /// or the beginning of the file if there are none.
+    pub fn get_loop_start(&self) -> f64 {
+        self.loop_start
+    }
+
+    /// Gets the end of the loop in seconds.
+    ///
+    /// # Remarks
+    ///
+    /// This is the first `loopEnd` marker, or the end of the file if there are none.
+    pub fn get_loop_end(&self) -> f64 {
+        self.loop_end
+    }
+}
@@ -0,0 +1,442 @@
+use core::cmp;
+use std::sync::Arc;
+
+use crate::prelude::*;
+use midix::prelude::ChannelVoiceMessage;
+
+use super::{ChaseState, MidiFile};
+
+/// An instance of the MIDI file sequencer.
+///
+/// # Remarks
+///
+/// The blocks of the synthesizer are split at the events,
+/// so that every event takes effect at its exact sample.
+///
+/// When looping, playback jumps from the loop end back to the loop start
+/// (see [`MidiFile::get_loop_start`] and [`set_loop_range`](Self::set_loop_range)).
+/// The notes held across the seam keep sounding, and the controllers are restored
+/// to their values at the loop start.
+pub struct MidiFileSequencer {
+    synthesizer: Synthesizer,
+
+    midi_file: Option<Arc<MidiFile>>,
+    play_loop: bool,
+
+    loop_range: Option<(u64, u64)>,
+    loop_count: Option<u32>,
+    fade_out: f64,
+
+    // The number of samples rendered since the start of the current playback.
+    position: u64,
+    // The difference between the playback position and the position in the file.
+    loop_offset: u64,
+    event_index: usize,
+    loops_played: u32,
+    // The notes currently held by the events of the file.
+    held: ChaseState,
+    fade_start: Option<u64>,
+    faded_out: bool,
+}
+
+impl MidiFileSequencer {
+    /// Initializes a new instance of the sequencer.
+    ///
+    /// # Arguments
+    ///
+    /// * `synthesizer` - The synthesizer to be driven by the sequencer.
+    pub fn new(synthesizer: Synthesizer) -> Self {
+        Self {
+            synthesizer,
+            midi_file: None,
+            play_loop: false,
+            loop_range: None,
+            loop_count: None,
+            fade_out: 0.0,
+            position: 0,
+            loop_offset: 0,
+            event_index: 0,
+            loops_played: 0,
+            held: ChaseState::new(),
+            fade_start: None,
+            faded_out: false,
+        }
+    }
+
+    /// Plays the MIDI file.
+    ///
+    /// # Arguments
+    ///
+    /// * `midi_file` - The MIDI file to be played.
+    /// * `play_loop` - If `true`, the MIDI file loops after reaching the loop end.
+    pub fn play(&mut self, midi_file: Arc<MidiFile>, play_loop: bool) {
+        self.midi_file = Some(midi_file);
+        self.play_loop = play_loop;
+
+        self.position = 0;
+        self.loop_offset = 0;
+        self.event_index = 0;
+        self.loops_played = 0;
+        self.held = ChaseState::new();
+        self.fade_start = None;
+        self.faded_out = false;
+
+        self.synthesizer.reset();
+    }
+
+    /// Moves the playback position to the specified time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The target position in seconds.
+    /// * `retrigger` - If `true`, the notes held at the target position are started again.
+    ///
+    /// # Remarks
+    ///
+    /// The program changes, controllers (including RPN and NRPN) and pitch bends
+    /// before the target position are replayed on every channel without sounding any note,
+    /// so playback resumes with the same state as if it had started from the beginning.
+    /// This does nothing if no MIDI file is being played.
+    pub fn seek(&mut self, time: f64, retrigger: bool) {
+        let Some(midi_file) = self.midi_file.clone() else {
+            return;
+        };
+
+        let time = time.clamp(0.0, midi_file.get_length());
+
+        self.synthesizer.reset();
+
+        let mut state = ChaseState::new();
+        let sample_rate = self.synthesizer.get_sample_rate();
+        let target = (time * sample_rate as f64).round() as u64;
+        self.event_index = 0;
+        for event in &midi_file.events {
+            if event.get_sample(sample_rate) >= target {
+                break;
+            }
+            state.process(&mut self.synthesizer, event.message);
+            self.event_index += 1;
+        }
+
+        self.held = ChaseState::new();
+        if retrigger {
+            self.held.transition(&state, &mut self.synthesizer);
+            self.held = state;
+        }
+
+        self.position = target;
+        self.loop_offset = 0;
+        self.fade_start = None;
+        self.faded_out = false;
+    }
+
+    /// Moves the playback position to the specified tick.
+    ///
+    /// # Arguments
+    ///
+    /// * `tick` - The target position in ticks.
+    /// * `retrigger` - If `true`, the notes held at the target position are started again.
+    ///
+    /// # Remarks
+    ///
+    /// See [`seek`](Self::seek).
+    pub fn seek_to_tick(&mut self, tick: u64, retrigger: bool) {
+        if let Some(midi_file) = self.midi_file.as_ref() {
+            let time = midi_file.get_time_at_tick(tick);
+            self.seek(time, retrigger);
+        }
+    }
+
+    /// Stops playing.
+    pub fn stop(&mut self) {
+        self.midi_file = None;
+        self.synthesizer.reset();
+    }
+
+    /// Renders the waveform.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The buffer of the left channel to store the rendered waveform.
+    /// * `right` - The buffer of the right channel to store the rendered waveform.
+    ///
+    /// # Remarks
+    ///
+    /// The output buffers for the left and right must be the same length.
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        if left.len() != right.len() {
+            panic!("The output buffers for the left and right must be the same length.");
+        }
+
+        if self.faded_out {
+            left.fill(0_f32);
+            right.fill(0_f32);
+            return;
+        }
+
+        let length = left.len();
+        let mut wrote = 0;
+        while wrote < length {
+            if self.synthesizer.get_block_remaining() == 0 {
+                self.process_events();
+            }
+
+            let rem = cmp::min(
+                match self.synthesizer.get_block_remaining() {
+                    0 => self.synthesizer.get_next_block_length(),
+                    remaining => remaining,
+                },
+                length - wrote,
+            );
+
+            self.synthesizer.render(
+                &mut left[wrote..wrote + rem],
+                &mut right[wrote..wrote + rem],
+            );
+
+            if let Some(fade_start) = self.fade_start {
+                self.apply_fade(
+                    fade_start,
+                    &mut left[wrote..wrote + rem],
+                    &mut right[wrote..wrote + rem],
+                );
+            }
+
+            self.position += rem as u64;
+            wrote += rem;
+
+            if self.faded_out {
+                left[wrote..].fill(0_f32);
+                right[wrote..].fill(0_f32);
+                return;
+            }
+        }
+    }
+
+    fn apply_fade(&mut self, fade_start: u64, left: &mut [f32], right: &mut [f32]) {
+        let fade_length = self.fade_out * self.synthesizer.get_sample_rate() as f64;
+        for (t, (left, right)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            let elapsed = (self.position + t as u64).saturating_sub(fade_start);
+            let gain = (1.0 - elapsed as f64 / fade_length).max(0.0) as f32;
+            *left *= gain;
+            *right *= gain;
+        }
+
+        if (self.position + left.len() as u64).saturating_sub(fade_start) as f64 >= fade_length {
+            self.faded_out = true;
+            self.synthesizer.note_off_all(true);
+        }
+    }
+
+    // Sends the events due at the current position to the synthesizer,
+    // and ends the next block at the following event or at the loop end.
+    fn process_events(&mut self) {
+        let Some(midi_file) = self.midi_file.clone() else {
+            return;
+        };
+
+        let sample_rate = self.synthesizer.get_sample_rate();
+        let (loop_start, loop_end) = self.get_loop_samples(&midi_file);
+
+        loop {
+            let looping = self.is_looping();
+
+            if let Some(event) = midi_file.events.get(self.event_index) {
+                let event_sample = event.get_sample(sample_rate);
+                if !looping || event_sample < loop_end {
+                    let sample = self.loop_offset + event_sample;
+                    if sample > self.position {
+                        self.end_block_at(sample);
+                        break;
+                    }
+
+                    self.send_message(event.message);
+                    self.event_index += 1;
+                    continue;
+                }
+            }
+
+            // An empty loop would loop forever at the same position.
+            if !looping || loop_end == loop_start {
+                break;
+            }
+
+            let seam = self.loop_offset + loop_end;
+            if seam > self.position {
+                self.end_block_at(seam);
+                break;
+            }
+
+            self.jump_to_loop_start(&midi_file, loop_start);
+            self.loop_offset = seam - loop_start;
+            self.loops_played += 1;
+
+            if self.fade_start.is_none()
+                && self.fade_out > 0.0
+                && self
+                    .loop_count
+                    .is_some_and(|count| self.loops_played >= count)
+            {
+                self.fade_start = Some(seam);
+            }
+        }
+    }
+
+    // Restores the controllers and the held notes at the loop start.
+    // The controllers are reset first, so that those only set within the loop do not carry over.
+    fn jump_to_loop_start(&mut self, midi_file: &MidiFile, loop_start: u64) {
+        let sample_rate = self.synthesizer.get_sample_rate();
+
+        self.synthesizer.reset_channels();
+
+        let mut state = ChaseState::new();
+        self.event_index = 0;
+        for event in &midi_file.events {
+            if event.get_sample(sample_rate) >= loop_start {
+                break;
+            }
+            state.process(&mut self.synthesizer, event.message);
+            self.event_index += 1;
+        }
+
+        self.held.transition(&state, &mut self.synthesizer);
+        self.held = state;
+    }
+
+    // Shortens the next block of the synthesizer if it would go past the sample.
+    fn end_block_at(&mut self, sample: u64) {
+        let length = sample - self.position;
+        if length < self.synthesizer.get_block_size() as u64 {
+            self.synthesizer.set_next_block_length(length as usize);
+        }
+    }
+
+    fn is_looping(&self) -> bool {
+        // Once the loops are done, playback either fades out while still looping
+        // or goes on to the end of the file.
+        self.play_loop
+            && (self.fade_out > 0.0
+                || self
+                    .loop_count
+                    .is_none_or(|count| self.loops_played < count))
+    }
+
+    fn get_loop_samples(&self, midi_file: &MidiFile) -> (u64, u64) {
+        let (start, end) = match self.loop_range {
+            Some((start, end)) => (
+                midi_file.get_time_at_tick(start),
+                midi_file.get_time_at_tick(end),
+            ),
+            None => (midi_file.get_loop_start(), midi_file.get_loop_end()),
+        };
+
+        let sample_rate = self.synthesizer.get_sample_rate() as f64;
+        let end = end.min(midi_file.get_length());
+        if start < end {
+            (
+                (start * sample_rate).round() as u64,
+                (end * sample_rate).round() as u64,
+            )
+        } else {
+            (0, (midi_file.get_length() * sample_rate).round() as u64)
+        }
+    }
+
+    fn send_message(&mut self, message: ChannelVoiceMessage) {
+        self.held.update(message);
+        self.synthesizer.process_midi_message(message);
+    }
+
+    /// Gets the current playback position in seconds.
+    pub fn get_position(&self) -> f64 {
+        let sample_rate = self.synthesizer.get_sample_rate() as f64;
+        let position = self.position - self.loop_offset.min(self.position);
+        position as f64 / sample_rate
+    }
+
+    /// Gets a value that indicates whether the current playback position is at the end of the sequence.
+    ///
+    /// # Remarks
+    ///
+    /// If the `play` method has not yet been called, this value will be `true`.
+    /// This value will never be `true` if loop playback is enabled,
+    /// unless the loop count is limited.
+    pub fn end_of_sequence(&self) -> bool {
+        match &self.midi_file {
+            None => true,
+            Some(_) if self.faded_out => true,
+            Some(midi_file) => {
+                !self.is_looping()
+                    && self.event_index == midi_file.events.len()
+                    && self.get_position() >= midi_file.get_length()
+            }
+        }
+    }
+
+    /// Gets the loop range in ticks, or `None` if the loop points of the MIDI file are used.
+    pub fn get_loop_range(&self) -> Option<(u64, u64)> {
+        self.loop_range
+    }
+
+    /// Sets the loop range.
+    ///
+    /// # Arguments
+    ///
+    /// * `loop_range` - The loop start and end in ticks,
+    ///   or `None` to use the loop points of the MIDI file.
+    ///
+    /// # Remarks
+    ///
+    /// If the range is empty, the whole file is looped.
+    pub fn set_loop_range(&mut self, loop_range: Option<(u64, u64)>) {
+        self.loop_range = loop_range;
+    }
+
+    /// Gets the number of times the loop is repeated, or `None` if it loops forever.
+    pub fn get_loop_count(&self) -> Option<u32> {
+        self.loop_count
+    }
+
+    /// Sets the number of times the loop is repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `loop_count` - The number of jumps back to the loop start, or `None` to loop forever.
+    ///
+    /// # Remarks
+    ///
+    /// This only applies when playing with loop enabled. Once the loops are done,
+    /// playback fades out if a fade-out is set, or goes on to the end of the file.
+    pub fn set_loop_count(&mut self, loop_count: Option<u32>) {
+        self.loop_count = loop_count;
+    }
+
+    /// Gets the fade-out duration in seconds.
+    pub fn get_fade_out(&self) -> f64 {
+        self.fade_out
+    }
+
+    /// Sets the fade-out applied once the loops are done.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The fade-out duration in seconds, or 0 to play to the end of the file.
+    pub fn set_fade_out(&mut self, duration: f64) {
+        self.fade_out = duration.max(0.0);
+    }
+
+    /// Gets the synthesizer handled by the sequencer.
+    pub fn get_synthesizer(&self) -> &Synthesizer {
+        &self.synthesizer
+    }
+
+    /// Gets the synthesizer handled by the sequencer.
+    pub fn get_synthesizer_mut(&mut self) -> &mut Synthesizer {
+        &mut self.synthesizer
+    }
+
+    /// Gets the MIDI file currently being played.
+    pub fn get_midi_file(&self) -> Option<&MidiFile> {
+        self.midi_file.as_deref()
+    }
+}
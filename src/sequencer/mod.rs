@@ -0,0 +1,14 @@
+mod chase;
+use chase::ChaseState;
+
+mod error;
+pub use error::*;
+
+mod midi_file;
+pub use midi_file::*;
+
+mod midi_file_sequencer;
+pub use midi_file_sequencer::*;
+
+mod offline;
+pub use offline::*;
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use bevy_platform::prelude::*;
+
+use crate::prelude::*;
+
+use super::{MidiFile, MidiFileSequencer};
+
+/// The stereo waveform rendered from a MIDI file.
+pub struct RenderedWaveform {
+    /// The sample rate of the waveform.
+    pub sample_rate: i32,
+    /// The samples of the left channel.
+    pub left: Vec<f32>,
+    /// The samples of the right channel.
+    pub right: Vec<f32>,
+}
+
+// The peak below which the tail is regarded as silent, about -100 dB.
+const SILENCE: f32 = 1.0e-5_f32;
+
+/// Renders a MIDI file from start to end, followed by the release of the last notes
+/// and the decay of the reverb and chorus.
+///
+/// # Arguments
+///
+/// * `sound_font` - The SoundFont used to render the MIDI file.
+/// * `midi_file` - The MIDI file to be rendered.
+/// * `settings` - The settings of the synthesizer.
+/// * `maximum_tail` - The maximum length in seconds rendered after the end of the MIDI file.
+///
+/// # Remarks
+///
+/// The tail ends once no voice is playing anymore and the last block is below -100 dB,
+/// or after `maximum_tail` seconds.
+pub fn render_midi_file(
+    sound_font: &Arc<SoundFont>,
+    midi_file: &Arc<MidiFile>,
+    settings: &SynthesizerSettings,
+    maximum_tail: f64,
+) -> Result<RenderedWaveform, SynthesizerError> {
+    let synthesizer = Synthesizer::new(sound_font.clone(), settings)?;
+    let mut sequencer = MidiFileSequencer::new(synthesizer);
+    sequencer.play(midi_file.clone(), false);
+
+    let block_size = settings.block_size;
+    let maximum_tail = (maximum_tail.max(0.0) * settings.sample_rate as f64) as usize;
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    let mut tail = 0;
+    let mut silent = false;
+    loop {
+        if sequencer.end_of_sequence() {
+            let playing = sequencer.get_synthesizer().get_active_voice_count() > 0;
+            if tail >= maximum_tail || (!playing && silent) {
+                break;
+            }
+            tail += block_size;
+        }
+
+        let start = left.len();
+        left.resize(start + block_size, 0_f32);
+        right.resize(start + block_size, 0_f32);
+        sequencer.render(&mut left[start..], &mut right[start..]);
+        silent = left[start..]
+            .iter()
+            .chain(&right[start..])
+            .all(|x| x.abs() < SILENCE);
+    }
+
+    Ok(RenderedWaveform {
+        sample_rate: settings.sample_rate,
+        left,
+        right,
+    })
+}
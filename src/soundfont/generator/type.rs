@@ -68,4 +68,44 @@ impl GeneratorType {
     pub(crate) const UNUSED_END: u16 = 60;
 
     pub(crate) const COUNT: usize = 61;
+
+    // Gets the range of the value of a generator as the SoundFont specification defines,
+    // which also bounds the sum of the preset and instrument values.
+    // The generators without a defined range are not bounded.
+    pub(crate) fn get_range(generator: u16) -> (i32, i32) {
+        match generator {
+            Self::MODULATION_LFO_TO_PITCH
+            | Self::VIBRATO_LFO_TO_PITCH
+            | Self::MODULATION_ENVELOPE_TO_PITCH => (-12000, 12000),
+            Self::INITIAL_FILTER_CUTOFF_FREQUENCY => (1500, 13500),
+            Self::INITIAL_FILTER_Q => (0, 960),
+            Self::MODULATION_LFO_TO_FILTER_CUTOFF_FREQUENCY
+            | Self::MODULATION_ENVELOPE_TO_FILTER_CUTOFF_FREQUENCY => (-12000, 12000),
+            Self::MODULATION_LFO_TO_VOLUME => (-960, 960),
+            Self::CHORUS_EFFECTS_SEND | Self::REVERB_EFFECTS_SEND => (0, 1000),
+            Self::PAN => (-500, 500),
+            Self::DELAY_MODULATION_LFO | Self::DELAY_VIBRATO_LFO => (-12000, 5000),
+            Self::FREQUENCY_MODULATION_LFO | Self::FREQUENCY_VIBRATO_LFO => (-16000, 4500),
+            Self::DELAY_MODULATION_ENVELOPE
+            | Self::HOLD_MODULATION_ENVELOPE
+            | Self::DELAY_VOLUME_ENVELOPE
+            | Self::HOLD_VOLUME_ENVELOPE => (-12000, 5000),
+            Self::ATTACK_MODULATION_ENVELOPE
+            | Self::DECAY_MODULATION_ENVELOPE
+            | Self::RELEASE_MODULATION_ENVELOPE
+            | Self::ATTACK_VOLUME_ENVELOPE
+            | Self::DECAY_VOLUME_ENVELOPE
+            | Self::RELEASE_VOLUME_ENVELOPE => (-12000, 8000),
+            Self::SUSTAIN_MODULATION_ENVELOPE => (0, 1000),
+            Self::SUSTAIN_VOLUME_ENVELOPE | Self::INITIAL_ATTENUATION => (0, 1440),
+            Self::KEY_NUMBER_TO_MODULATION_ENVELOPE_HOLD
+            | Self::KEY_NUMBER_TO_MODULATION_ENVELOPE_DECAY
+            | Self::KEY_NUMBER_TO_VOLUME_ENVELOPE_HOLD
+            | Self::KEY_NUMBER_TO_VOLUME_ENVELOPE_DECAY => (-1200, 1200),
+            Self::COARSE_TUNE => (-120, 120),
+            Self::FINE_TUNE => (-99, 99),
+            Self::SCALE_TUNING => (0, 1200),
+            _ => (i32::MIN, i32::MAX),
+        }
+    }
 }
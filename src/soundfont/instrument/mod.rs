@@ -68,4 +68,9 @@ impl Instrument {
     pub fn get_regions(&self) -> &[InstrumentRegion] {
         &self.regions[..]
     }
+
+    /// Gets the regions of the instrument for modification.
+    pub fn get_regions_mut(&mut self) -> &mut [InstrumentRegion] {
+        &mut self.regions[..]
+    }
 }
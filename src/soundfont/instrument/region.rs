@@ -30,6 +30,8 @@ pub struct InstrumentRegion {
     pub(crate) sample_sample_rate: i32,
     pub(crate) sample_original_pitch: i32,
     pub(crate) sample_pitch_correction: i32,
+    pub(crate) filter_type: FilterType,
+    pub(crate) filter_slope: FilterSlope,
 }
 
 impl InstrumentRegion {
@@ -86,6 +88,8 @@ impl InstrumentRegion {
             sample_sample_rate: sample.sample_rate,
             sample_original_pitch: sample.original_pitch as i32,
             sample_pitch_correction: sample.pitch_correction as i32,
+            filter_type: FilterType::LowPass,
+            filter_slope: FilterSlope::TwoPole,
         })
     }
 
@@ -368,4 +372,30 @@ impl InstrumentRegion {
     pub fn get_sample_id(&self) -> usize {
         self.gs[GeneratorType::SAMPLE_ID as usize] as usize
     }
+
+    /// Sets the filter of the region, as the `fil_type` opcode of SFZ does.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter_type` - The response of the filter.
+    /// * `slope` - The steepness of the filter.
+    ///
+    /// # Remarks
+    ///
+    /// SoundFont files always use a 2-pole low-pass filter, which is the default.
+    /// A filter selected for the channel overrides the filter of the region.
+    pub fn set_filter(&mut self, filter_type: FilterType, slope: FilterSlope) {
+        self.filter_type = filter_type;
+        self.filter_slope = slope;
+    }
+
+    /// Gets the response of the filter.
+    pub fn get_filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+
+    /// Gets the steepness of the filter.
+    pub fn get_filter_slope(&self) -> FilterSlope {
+        self.filter_slope
+    }
 }
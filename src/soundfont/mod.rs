@@ -2,7 +2,12 @@
 #![allow(missing_docs)]
 
 use bevy_platform::prelude::*;
-use std::{io::Read, sync::Arc};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 pub mod generator;
 pub mod instrument;
 pub mod preset;
@@ -20,6 +25,8 @@ mod error;
 pub use error::*;
 mod sample_header;
 pub use sample_header::*;
+mod report;
+pub use report::*;
 
 use crate::prelude::*;
 
@@ -163,4 +170,68 @@ impl SoundFont {
     pub fn get_instruments(&self) -> &[Instrument] {
         &self.instruments[..]
     }
+
+    /// Gets the instruments of the SoundFont for modification.
+    ///
+    /// # Remarks
+    ///
+    /// The instruments can only be modified before the SoundFont is shared with a synthesizer.
+    pub fn get_instruments_mut(&mut self) -> &mut [Instrument] {
+        &mut self.instruments[..]
+    }
+
+    /// Gets the indices of the samples used by a preset, in order of first use.
+    ///
+    /// # Arguments
+    ///
+    /// * `preset` - The preset of the SoundFont.
+    pub fn get_preset_sample_ids(&self, preset: &Preset) -> Vec<usize> {
+        let mut ids = Vec::new();
+        for preset_region in preset.get_regions() {
+            let instrument = &self.instruments[preset_region.get_instrument_id()];
+            for region in instrument.get_regions() {
+                if !ids.contains(&region.get_sample_id()) {
+                    ids.push(region.get_sample_id());
+                }
+            }
+        }
+        ids
+    }
+
+    /// Writes every sample used by a preset to a WAV file in the directory.
+    /// Returns the paths of the files written.
+    ///
+    /// # Arguments
+    ///
+    /// * `preset` - The preset of the SoundFont.
+    /// * `directory` - The directory where the files are written.
+    ///
+    /// # Remarks
+    ///
+    /// The files are named after the index and the name of each sample.
+    /// See [`SampleHeader::write_wav`] for the format of the files.
+    pub fn export_preset_samples(
+        &self,
+        preset: &Preset,
+        directory: &Path,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for id in self.get_preset_sample_ids(preset) {
+            let sample = &self.sample_headers[id];
+            let name: String = sample
+                .get_name()
+                .chars()
+                .map(|c| match c {
+                    c if c.is_alphanumeric() || c == '-' || c == '_' => c,
+                    _ => '_',
+                })
+                .collect();
+            let path = directory.join(format!("{id:04}_{name}.wav"));
+
+            let mut writer = BufWriter::new(File::create(&path)?);
+            sample.write_wav(&self.wave_data, &mut writer)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
 }
@@ -0,0 +1,707 @@
+use bevy_platform::prelude::*;
+use core::fmt::Write;
+
+use crate::{prelude::*, utils};
+
+/// A value in the description of a SoundFont.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReportValue {
+    Int(i64),
+    /// A value converted to its unit, e.g. seconds or decibels.
+    Float(f32),
+    Text(String),
+    /// An inclusive range of keys or velocities.
+    Range(u8, u8),
+    /// The named values describing an item.
+    Fields(ReportFields),
+    List(Vec<ReportValue>),
+}
+
+/// The named values describing an item of a SoundFont, in the order they are printed.
+pub type ReportFields = Vec<(&'static str, ReportValue)>;
+
+impl ReportValue {
+    /// Formats the value as it is printed in the text report.
+    ///
+    /// # Remarks
+    ///
+    /// The nested fields and lists are formatted on a single line.
+    pub fn to_text(&self) -> String {
+        match self {
+            ReportValue::Int(value) => value.to_string(),
+            ReportValue::Float(value) => format!("{value:.4}"),
+            ReportValue::Text(value) => value.clone(),
+            ReportValue::Range(start, end) => format!("{start}-{end}"),
+            ReportValue::Fields(fields) => fields_to_text(fields),
+            ReportValue::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_text()).collect();
+                format!("[{}]", items.join(", "))
+            }
+        }
+    }
+
+    /// Formats the value as JSON.
+    ///
+    /// # Remarks
+    ///
+    /// The non-empty lists and the fields containing them span several lines.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json, 0);
+        json
+    }
+
+    fn write_json(&self, json: &mut String, indent: usize) {
+        match self {
+            ReportValue::Int(value) => write!(json, "{value}").unwrap(),
+            ReportValue::Float(value) if value.is_finite() => write!(json, "{value}").unwrap(),
+            ReportValue::Float(_) => json.push_str("null"),
+            ReportValue::Text(value) => write_json_string(json, value),
+            ReportValue::Range(start, end) => write!(json, "[{start}, {end}]").unwrap(),
+            ReportValue::Fields(fields) => {
+                let multiline = fields.iter().any(|(_, value)| value.is_multiline());
+                json.push('{');
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    if multiline {
+                        new_line(json, indent + 1);
+                    } else if i > 0 {
+                        json.push(' ');
+                    }
+                    write_json_string(json, name);
+                    json.push_str(": ");
+                    value.write_json(json, indent + 1);
+                }
+                if multiline {
+                    new_line(json, indent);
+                }
+                json.push('}');
+            }
+            ReportValue::List(items) => {
+                json.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    new_line(json, indent + 1);
+                    item.write_json(json, indent + 1);
+                }
+                if !items.is_empty() {
+                    new_line(json, indent);
+                }
+                json.push(']');
+            }
+        }
+    }
+
+    fn is_multiline(&self) -> bool {
+        match self {
+            ReportValue::Fields(fields) => fields.iter().any(|(_, value)| value.is_multiline()),
+            ReportValue::List(items) => !items.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+fn new_line(json: &mut String, indent: usize) {
+    json.push('\n');
+    for _ in 0..indent {
+        json.push_str("  ");
+    }
+}
+
+fn write_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn fields_to_text(fields: &ReportFields) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{name}={}", value.to_text()))
+        .collect();
+    fields.join(" ")
+}
+
+/// The generators of a preset zone combined with the ones of a zone of its instrument,
+/// which are the values a note played in both zones uses.
+///
+/// # Remarks
+///
+/// The preset values are added to the instrument values,
+/// and the sums are clamped to the ranges the SoundFont specification defines.
+/// Modulators are not applied.
+#[derive(Clone, Copy, Debug)]
+pub struct ResolvedZone<'a> {
+    preset: &'a PresetRegion,
+    instrument: &'a InstrumentRegion,
+}
+
+impl<'a> ResolvedZone<'a> {
+    /// Combines a preset zone with a zone of its instrument.
+    /// Returns `None` if the key or velocity ranges of the zones do not overlap,
+    /// since no note is played in both.
+    ///
+    /// # Arguments
+    ///
+    /// * `preset` - The preset zone.
+    /// * `instrument` - A zone of the instrument of the preset zone.
+    pub fn new(preset: &'a PresetRegion, instrument: &'a InstrumentRegion) -> Option<Self> {
+        let zone = Self { preset, instrument };
+        let (key_start, key_end) = zone.get_key_range();
+        let (velocity_start, velocity_end) = zone.get_velocity_range();
+        (key_start <= key_end && velocity_start <= velocity_end).then_some(zone)
+    }
+
+    fn gs(&self, generator: u16) -> i32 {
+        let (min, max) = GeneratorType::get_range(generator);
+        let index = generator as usize;
+        (self.preset.gs[index] as i32 + self.instrument.gs[index] as i32).clamp(min, max)
+    }
+
+    /// Gets the keys covered by both zones.
+    pub fn get_key_range(&self) -> (u8, u8) {
+        (
+            self.preset
+                .get_key_range_start()
+                .max(self.instrument.get_key_range_start()),
+            self.preset
+                .get_key_range_end()
+                .min(self.instrument.get_key_range_end()),
+        )
+    }
+
+    /// Gets the velocities covered by both zones.
+    pub fn get_velocity_range(&self) -> (u8, u8) {
+        (
+            self.preset
+                .get_velocity_range_start()
+                .max(self.instrument.get_velocity_range_start()),
+            self.preset
+                .get_velocity_range_end()
+                .min(self.instrument.get_velocity_range_end()),
+        )
+    }
+
+    /// Gets the attenuation in decibels.
+    pub fn get_initial_attenuation(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::INITIAL_ATTENUATION) as f32
+    }
+
+    /// Gets the pan from -50 (left) to 50 (right).
+    pub fn get_pan(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::PAN) as f32
+    }
+
+    /// Gets the coarse tune in semitones.
+    pub fn get_coarse_tune(&self) -> i32 {
+        self.gs(GeneratorType::COARSE_TUNE)
+    }
+
+    /// Gets the fine tune in cents, without the pitch correction of the sample.
+    pub fn get_fine_tune(&self) -> i32 {
+        self.gs(GeneratorType::FINE_TUNE)
+    }
+
+    /// Gets the scale tuning in cents per key.
+    pub fn get_scale_tuning(&self) -> i32 {
+        self.gs(GeneratorType::SCALE_TUNING)
+    }
+
+    /// Gets the cutoff frequency of the filter in hertz.
+    pub fn get_initial_filter_cutoff_frequency(&self) -> f32 {
+        utils::cents_to_hertz(self.gs(GeneratorType::INITIAL_FILTER_CUTOFF_FREQUENCY) as f32)
+    }
+
+    /// Gets the resonance of the filter in decibels.
+    pub fn get_initial_filter_q(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::INITIAL_FILTER_Q) as f32
+    }
+
+    /// Gets the delay of the volume envelope in seconds.
+    pub fn get_delay_volume_envelope(&self) -> f32 {
+        utils::timecents_to_seconds(self.gs(GeneratorType::DELAY_VOLUME_ENVELOPE) as f32)
+    }
+
+    /// Gets the attack of the volume envelope in seconds.
+    pub fn get_attack_volume_envelope(&self) -> f32 {
+        utils::timecents_to_seconds(self.gs(GeneratorType::ATTACK_VOLUME_ENVELOPE) as f32)
+    }
+
+    /// Gets the hold of the volume envelope in seconds.
+    pub fn get_hold_volume_envelope(&self) -> f32 {
+        utils::timecents_to_seconds(self.gs(GeneratorType::HOLD_VOLUME_ENVELOPE) as f32)
+    }
+
+    /// Gets the decay of the volume envelope in seconds.
+    pub fn get_decay_volume_envelope(&self) -> f32 {
+        utils::timecents_to_seconds(self.gs(GeneratorType::DECAY_VOLUME_ENVELOPE) as f32)
+    }
+
+    /// Gets the sustain attenuation of the volume envelope in decibels.
+    pub fn get_sustain_volume_envelope(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::SUSTAIN_VOLUME_ENVELOPE) as f32
+    }
+
+    /// Gets the release of the volume envelope in seconds.
+    pub fn get_release_volume_envelope(&self) -> f32 {
+        utils::timecents_to_seconds(self.gs(GeneratorType::RELEASE_VOLUME_ENVELOPE) as f32)
+    }
+
+    /// Gets the reverb send in percent.
+    pub fn get_reverb_effects_send(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::REVERB_EFFECTS_SEND) as f32
+    }
+
+    /// Gets the chorus send in percent.
+    pub fn get_chorus_effects_send(&self) -> f32 {
+        0.1_f32 * self.gs(GeneratorType::CHORUS_EFFECTS_SEND) as f32
+    }
+}
+
+/// Describes the contents of a SoundFont as text or JSON.
+pub struct SoundFontReport<'a> {
+    sound_font: &'a SoundFont,
+}
+
+impl<'a> SoundFontReport<'a> {
+    /// Initializes a new report.
+    ///
+    /// # Arguments
+    ///
+    /// * `sound_font` - The SoundFont to describe.
+    pub fn new(sound_font: &'a SoundFont) -> Self {
+        Self { sound_font }
+    }
+
+    /// Gets the INFO metadata.
+    pub fn get_info_fields(&self) -> ReportFields {
+        let info = self.sound_font.get_info();
+        let text = |value: &str| ReportValue::Text(value.into());
+        vec![
+            ("version", version(info.get_version())),
+            ("target_sound_engine", text(info.get_target_sound_engine())),
+            ("bank_name", text(info.get_bank_name())),
+            ("rom_name", text(info.get_rom_name())),
+            ("rom_version", version(info.get_rom_version())),
+            ("creation_date", text(info.get_creation_date())),
+            ("author", text(info.get_author())),
+            ("target_product", text(info.get_target_product())),
+            ("copyright", text(info.get_copyright())),
+            ("comments", text(info.get_comments())),
+            ("tools", text(info.get_tools())),
+        ]
+    }
+
+    /// Gets the presets ordered by bank and program.
+    pub fn get_sorted_presets(&self) -> Vec<&'a Preset> {
+        let mut presets: Vec<&Preset> = self.sound_font.get_presets().iter().collect();
+        presets.sort_by_key(|preset| (preset.get_bank_number(), preset.get_patch_number()));
+        presets
+    }
+
+    /// Gets the bank, program and name of a preset.
+    pub fn get_preset_fields(&self, preset: &Preset) -> ReportFields {
+        vec![
+            ("bank", ReportValue::Int(preset.get_bank_number() as i64)),
+            (
+                "program",
+                ReportValue::Int(preset.get_patch_number() as i64),
+            ),
+            ("name", ReportValue::Text(preset.get_name().into())),
+        ]
+    }
+
+    /// Gets the instrument and ranges of a preset zone.
+    ///
+    /// # Remarks
+    ///
+    /// The generators of a preset zone are offsets to the ones of the instrument zones,
+    /// so they are described by [`SoundFontReport::get_resolved_zones`].
+    pub fn get_preset_zone_fields(&self, region: &PresetRegion) -> ReportFields {
+        let instrument = &self.sound_font.get_instruments()[region.get_instrument_id()];
+        vec![
+            (
+                "instrument",
+                ReportValue::Text(instrument.get_name().into()),
+            ),
+            (
+                "key_range",
+                ReportValue::Range(region.get_key_range_start(), region.get_key_range_end()),
+            ),
+            (
+                "velocity_range",
+                ReportValue::Range(
+                    region.get_velocity_range_start(),
+                    region.get_velocity_range_end(),
+                ),
+            ),
+        ]
+    }
+
+    /// Gets the zones of the instrument of a preset zone resolved against the preset zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The preset zone.
+    pub fn get_resolved_zones(&self, region: &'a PresetRegion) -> Vec<ResolvedZone<'a>> {
+        self.sound_font.get_instruments()[region.get_instrument_id()]
+            .get_regions()
+            .iter()
+            .filter_map(|instrument| ResolvedZone::new(region, instrument))
+            .collect()
+    }
+
+    /// Gets the sample, ranges and generator values of a resolved zone.
+    pub fn get_resolved_zone_fields(&self, zone: &ResolvedZone) -> ReportFields {
+        let (key_start, key_end) = zone.get_key_range();
+        let (velocity_start, velocity_end) = zone.get_velocity_range();
+        let sample = &self.sound_font.get_sample_headers()[zone.instrument.get_sample_id()];
+        vec![
+            ("sample", ReportValue::Text(sample.get_name().into())),
+            ("key_range", ReportValue::Range(key_start, key_end)),
+            (
+                "velocity_range",
+                ReportValue::Range(velocity_start, velocity_end),
+            ),
+            (
+                "root_key",
+                ReportValue::Int(zone.instrument.get_root_key() as i64),
+            ),
+            (
+                "attenuation",
+                ReportValue::Float(zone.get_initial_attenuation()),
+            ),
+            ("pan", ReportValue::Float(zone.get_pan())),
+            (
+                "coarse_tune",
+                ReportValue::Int(zone.get_coarse_tune() as i64),
+            ),
+            ("fine_tune", ReportValue::Int(zone.get_fine_tune() as i64)),
+            (
+                "scale_tuning",
+                ReportValue::Int(zone.get_scale_tuning() as i64),
+            ),
+            (
+                "filter_cutoff",
+                ReportValue::Float(zone.get_initial_filter_cutoff_frequency()),
+            ),
+            ("filter_q", ReportValue::Float(zone.get_initial_filter_q())),
+            (
+                "volume_delay",
+                ReportValue::Float(zone.get_delay_volume_envelope()),
+            ),
+            (
+                "volume_attack",
+                ReportValue::Float(zone.get_attack_volume_envelope()),
+            ),
+            (
+                "volume_hold",
+                ReportValue::Float(zone.get_hold_volume_envelope()),
+            ),
+            (
+                "volume_decay",
+                ReportValue::Float(zone.get_decay_volume_envelope()),
+            ),
+            (
+                "volume_sustain",
+                ReportValue::Float(zone.get_sustain_volume_envelope()),
+            ),
+            (
+                "volume_release",
+                ReportValue::Float(zone.get_release_volume_envelope()),
+            ),
+            (
+                "reverb_send",
+                ReportValue::Float(zone.get_reverb_effects_send()),
+            ),
+            (
+                "chorus_send",
+                ReportValue::Float(zone.get_chorus_effects_send()),
+            ),
+        ]
+    }
+
+    /// Gets the sample, ranges and generator values of an instrument zone.
+    pub fn get_instrument_zone_fields(&self, region: &InstrumentRegion) -> ReportFields {
+        let sample = &self.sound_font.get_sample_headers()[region.get_sample_id()];
+        vec![
+            ("sample", ReportValue::Text(sample.get_name().into())),
+            (
+                "key_range",
+                ReportValue::Range(region.get_key_range_start(), region.get_key_range_end()),
+            ),
+            (
+                "velocity_range",
+                ReportValue::Range(
+                    region.get_velocity_range_start(),
+                    region.get_velocity_range_end(),
+                ),
+            ),
+            ("root_key", ReportValue::Int(region.get_root_key() as i64)),
+            (
+                "sample_modes",
+                ReportValue::Text(format!("{:?}", region.get_sample_modes())),
+            ),
+            (
+                "exclusive_class",
+                ReportValue::Int(region.get_exclusive_class() as i64),
+            ),
+            (
+                "sample_start",
+                ReportValue::Int(region.get_sample_start() as i64),
+            ),
+            (
+                "sample_end",
+                ReportValue::Int(region.get_sample_end() as i64),
+            ),
+            (
+                "loop_start",
+                ReportValue::Int(region.get_sample_start_loop() as i64),
+            ),
+            (
+                "loop_end",
+                ReportValue::Int(region.get_sample_end_loop() as i64),
+            ),
+            (
+                "attenuation",
+                ReportValue::Float(region.get_initial_attenuation()),
+            ),
+            ("pan", ReportValue::Float(region.get_pan())),
+            (
+                "coarse_tune",
+                ReportValue::Int(region.get_coarse_tune() as i64),
+            ),
+            ("fine_tune", ReportValue::Int(region.get_fine_tune() as i64)),
+            (
+                "scale_tuning",
+                ReportValue::Int(region.get_scale_tuning() as i64),
+            ),
+            (
+                "filter_cutoff",
+                ReportValue::Float(region.get_initial_filter_cutoff_frequency()),
+            ),
+            (
+                "filter_q",
+                ReportValue::Float(region.get_initial_filter_q()),
+            ),
+            (
+                "volume_delay",
+                ReportValue::Float(region.get_delay_volume_envelope()),
+            ),
+            (
+                "volume_attack",
+                ReportValue::Float(region.get_attack_volume_envelope()),
+            ),
+            (
+                "volume_hold",
+                ReportValue::Float(region.get_hold_volume_envelope()),
+            ),
+            (
+                "volume_decay",
+                ReportValue::Float(region.get_decay_volume_envelope()),
+            ),
+            (
+                "volume_sustain",
+                ReportValue::Float(region.get_sustain_volume_envelope()),
+            ),
+            (
+                "volume_release",
+                ReportValue::Float(region.get_release_volume_envelope()),
+            ),
+            (
+                "reverb_send",
+                ReportValue::Float(region.get_reverb_effects_send()),
+            ),
+            (
+                "chorus_send",
+                ReportValue::Float(region.get_chorus_effects_send()),
+            ),
+        ]
+    }
+
+    /// Gets the rate, loop points and pitch of a sample.
+    pub fn get_sample_fields(&self, sample: &SampleHeader) -> ReportFields {
+        vec![
+            ("name", ReportValue::Text(sample.get_name().into())),
+            (
+                "sample_rate",
+                ReportValue::Int(sample.get_sample_rate() as i64),
+            ),
+            ("start", ReportValue::Int(sample.get_start() as i64)),
+            ("end", ReportValue::Int(sample.get_end() as i64)),
+            (
+                "loop_start",
+                ReportValue::Int(sample.get_start_loop() as i64),
+            ),
+            ("loop_end", ReportValue::Int(sample.get_end_loop() as i64)),
+            (
+                "original_pitch",
+                ReportValue::Int(sample.get_original_pitch() as i64),
+            ),
+            (
+                "pitch_correction",
+                ReportValue::Int(sample.get_pitch_correction() as i64),
+            ),
+            (
+                "sample_type",
+                ReportValue::Int(sample.get_sample_type() as i64),
+            ),
+            ("link", ReportValue::Int(sample.get_link() as i64)),
+        ]
+    }
+
+    /// Gets the whole description of the SoundFont, including every zone.
+    pub fn get_fields(&self) -> ReportFields {
+        let presets = self
+            .get_sorted_presets()
+            .into_iter()
+            .map(|preset| {
+                let zones = preset
+                    .get_regions()
+                    .iter()
+                    .map(|region| {
+                        let mut fields = self.get_preset_zone_fields(region);
+                        let resolved = self
+                            .get_resolved_zones(region)
+                            .iter()
+                            .map(|zone| ReportValue::Fields(self.get_resolved_zone_fields(zone)))
+                            .collect();
+                        fields.push(("resolved_zone_list", ReportValue::List(resolved)));
+                        ReportValue::Fields(fields)
+                    })
+                    .collect();
+                let mut fields = self.get_preset_fields(preset);
+                fields.push(("zone_list", ReportValue::List(zones)));
+                ReportValue::Fields(fields)
+            })
+            .collect();
+
+        let instruments = self
+            .sound_font
+            .get_instruments()
+            .iter()
+            .map(|instrument| {
+                let zones = instrument
+                    .get_regions()
+                    .iter()
+                    .map(|region| ReportValue::Fields(self.get_instrument_zone_fields(region)))
+                    .collect();
+                ReportValue::Fields(vec![
+                    ("name", ReportValue::Text(instrument.get_name().into())),
+                    ("zone_list", ReportValue::List(zones)),
+                ])
+            })
+            .collect();
+
+        let samples = self
+            .sound_font
+            .get_sample_headers()
+            .iter()
+            .map(|sample| ReportValue::Fields(self.get_sample_fields(sample)))
+            .collect();
+
+        vec![
+            ("info", ReportValue::Fields(self.get_info_fields())),
+            (
+                "bits_per_sample",
+                ReportValue::Int(self.sound_font.get_bits_per_sample() as i64),
+            ),
+            ("presets", ReportValue::List(presets)),
+            ("instruments", ReportValue::List(instruments)),
+            ("samples", ReportValue::List(samples)),
+        ]
+    }
+
+    /// Formats the whole description of the SoundFont as JSON.
+    pub fn to_json(&self) -> String {
+        ReportValue::Fields(self.get_fields()).to_json()
+    }
+
+    /// Formats the description of the SoundFont as text.
+    ///
+    /// # Arguments
+    ///
+    /// * `zones` - If `true`, the zones of the presets and instruments are also described.
+    pub fn to_text(&self, zones: bool) -> String {
+        let mut text = String::new();
+
+        writeln!(text, "[INFO]").unwrap();
+        for (name, value) in self.get_info_fields() {
+            writeln!(text, "  {name:<20} {}", value.to_text()).unwrap();
+        }
+        writeln!(
+            text,
+            "  {:<20} {}",
+            "bits_per_sample",
+            self.sound_font.get_bits_per_sample()
+        )
+        .unwrap();
+
+        writeln!(text).unwrap();
+        writeln!(text, "[PRESETS]").unwrap();
+        for preset in self.get_sorted_presets() {
+            writeln!(
+                text,
+                "  {:03}:{:03} {} ({} zones)",
+                preset.get_bank_number(),
+                preset.get_patch_number(),
+                preset.get_name(),
+                preset.get_regions().len()
+            )
+            .unwrap();
+            if zones {
+                for region in preset.get_regions() {
+                    let fields = self.get_preset_zone_fields(region);
+                    writeln!(text, "    {}", fields_to_text(&fields)).unwrap();
+                    for zone in self.get_resolved_zones(region) {
+                        let fields = self.get_resolved_zone_fields(&zone);
+                        writeln!(text, "      {}", fields_to_text(&fields)).unwrap();
+                    }
+                }
+            }
+        }
+
+        if zones {
+            writeln!(text).unwrap();
+            writeln!(text, "[INSTRUMENTS]").unwrap();
+            for (id, instrument) in self.sound_font.get_instruments().iter().enumerate() {
+                writeln!(text, "  {id:>4} {}", instrument.get_name()).unwrap();
+                for region in instrument.get_regions() {
+                    let fields = self.get_instrument_zone_fields(region);
+                    writeln!(text, "    {}", fields_to_text(&fields)).unwrap();
+                }
+            }
+        }
+
+        writeln!(text).unwrap();
+        writeln!(text, "[SAMPLES]").unwrap();
+        for (id, sample) in self.sound_font.get_sample_headers().iter().enumerate() {
+            let fields = self.get_sample_fields(sample);
+            writeln!(text, "  {id:>4} {}", fields_to_text(&fields)).unwrap();
+        }
+
+        text
+    }
+}
+
+fn version(version: &SoundFontVersion) -> ReportValue {
+    ReportValue::Text(format!(
+        "{}.{:02}",
+        version.get_major(),
+        version.get_minor()
+    ))
+}
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 
-use crate::prelude::*;
+use crate::{prelude::*, wav};
 use bevy_platform::prelude::*;
+use std::io::{self, Write};
 
 /// Represents a sample in the SoundFont.
 #[derive(Clone, Debug)]
@@ -115,4 +116,84 @@ impl SampleHeader {
     pub fn get_sample_type(&self) -> i32 {
         self.sample_type as i32
     }
+
+    /// Writes the sample to a 16-bit mono WAV file.
+    /// The loop points, the root key and the pitch correction are written in a `smpl` chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `wave_data` - The sample data of the SoundFont, as given by [`SoundFont::get_wave_data`].
+    /// * `writer` - The destination of the WAV file.
+    pub fn write_wav<W: Write + ?Sized>(
+        &self,
+        wave_data: &[i16],
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let start = self.start.max(0) as usize;
+        let end = self.end.max(0) as usize;
+        if start > end || end > wave_data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the sample is out of the sample data",
+            ));
+        }
+
+        let mut data = Vec::with_capacity(2 * (end - start));
+        for value in &wave_data[start..end] {
+            data.extend(value.to_le_bytes());
+        }
+
+        let sample_rate = self.sample_rate.max(1) as u32;
+        wav::write_riff(
+            writer,
+            sample_rate,
+            1,
+            WavFormat::Pcm16,
+            &[(*b"smpl", self.get_sampler_chunk(sample_rate))],
+            &data,
+        )
+    }
+
+    fn get_sampler_chunk(&self, sample_rate: u32) -> Vec<u8> {
+        // An original pitch of 255 means that the sample is unpitched.
+        let root_key = match self.original_pitch {
+            0..=127 => self.original_pitch as i32,
+            _ => 60,
+        };
+        // The pitch correction is applied on playback, so the sample itself is off by the opposite.
+        // The sampler chunk stores the pitch as a unity note plus an upward fraction of a semitone.
+        let pitch = 100 * root_key - self.pitch_correction as i32;
+        let unity_note = pitch.div_euclid(100);
+        let pitch_fraction = (pitch.rem_euclid(100) as u64 * (1_u64 << 32) / 100) as u32;
+
+        let loop_start = self.start_loop - self.start;
+        // The loop end of SoundFont is exclusive, but the one of the sampler chunk is inclusive.
+        let loop_end = self.end_loop - self.start - 1;
+        let has_loop = 0 <= loop_start && loop_start < loop_end && self.end_loop <= self.end;
+
+        let mut chunk = Vec::with_capacity(60);
+        // The manufacturer, the product, the sample period in nanoseconds, the unity note,
+        // the pitch fraction, the SMPTE format and offset, the number of loops and the size of extra data.
+        for value in [
+            0,
+            0,
+            1_000_000_000 / sample_rate,
+            unity_note.clamp(0, 127) as u32,
+            pitch_fraction,
+            0,
+            0,
+            has_loop as u32,
+            0,
+        ] {
+            chunk.extend(value.to_le_bytes());
+        }
+        if has_loop {
+            // The cue point, a forward loop, the start and end, the fraction and an infinite play count.
+            for value in [0, 0, loop_start as u32, loop_end as u32, 0, 0] {
+                chunk.extend(value.to_le_bytes());
+            }
+        }
+
+        chunk
+    }
 }
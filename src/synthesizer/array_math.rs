@@ -1,17 +1,321 @@
+use crate::utils;
+
 pub(crate) struct ArrayMath {}
 
 impl ArrayMath {
     pub(crate) fn multiply_add(a: f32, x: &[f32], destination: &mut [f32]) {
-        for (x, destination) in x.iter().zip(destination.iter_mut()) {
-            *destination += a * *x;
+        Kernel::detect().multiply_add(a, x, destination);
+    }
+
+    // Mixes the source into every destination in a single pass.
+    pub(crate) fn multiply_add_ramps(x: &[f32], targets: &mut [MixTarget<'_>]) {
+        Kernel::detect().multiply_add_ramps(x, targets);
+    }
+}
+
+// The gain of a destination over a block.
+// A sudden change in the gain will cause pop noise, so the gain changes linearly from the previous block.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Ramp {
+    pub(crate) gain: f32,
+    pub(crate) step: f32,
+}
+
+impl Ramp {
+    // Gets the ramp from the gain of the previous block to the current one, or None if both are inaudible.
+    pub(crate) fn new(
+        previous_gain: f32,
+        current_gain: f32,
+        inverse_block_size: f32,
+    ) -> Option<Self> {
+        if previous_gain.max(current_gain) < utils::NON_AUDIBLE {
+            return None;
+        }
+
+        if (current_gain - previous_gain).abs() < utils::NON_AUDIBLE {
+            Some(Self {
+                gain: current_gain,
+                step: 0_f32,
+            })
+        } else {
+            Some(Self {
+                gain: previous_gain,
+                step: inverse_block_size * (current_gain - previous_gain),
+            })
+        }
+    }
+
+    // Gets the mix target for the destination, which is skipped if the gain is inaudible.
+    pub(crate) fn target<'a>(
+        previous_gain: f32,
+        current_gain: f32,
+        inverse_block_size: f32,
+        destination: &'a mut [f32],
+    ) -> MixTarget<'a> {
+        match Ramp::new(previous_gain, current_gain, inverse_block_size) {
+            Some(ramp) => (ramp, destination),
+            None => empty_target(),
         }
     }
+}
+
+// A destination of the fused mix. The target is skipped if the destination is empty.
+pub(crate) type MixTarget<'a> = (Ramp, &'a mut [f32]);
+
+// Gets a target which is skipped.
+pub(crate) fn empty_target<'a>() -> MixTarget<'a> {
+    (Ramp::default(), &mut [])
+}
+
+// The implementation of the array operations.
+// Every kernel computes `destination[i] += (gain + step * i) * x[i]` with the same operations in the same order,
+// so the output does not depend on the instruction set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kernel {
+    // Only selected on the other architectures, and by the tests as the reference.
+    #[allow(unused)]
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse,
+    #[cfg(target_arch = "x86_64")]
+    Avx,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl Kernel {
+    // Gets the fastest kernel supported by the CPU.
+    pub(crate) fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx") {
+                Kernel::Avx
+            } else {
+                Kernel::Sse
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            Kernel::Neon
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            Kernel::Scalar
+        }
+    }
+
+    // Gets the kernels supported by the CPU.
+    #[cfg(test)]
+    pub(crate) fn supported() -> impl Iterator<Item = Kernel> {
+        [
+            Some(Kernel::Scalar),
+            #[cfg(target_arch = "x86_64")]
+            Some(Kernel::Sse),
+            #[cfg(target_arch = "x86_64")]
+            std::arch::is_x86_feature_detected!("avx").then_some(Kernel::Avx),
+            #[cfg(target_arch = "aarch64")]
+            Some(Kernel::Neon),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    pub(crate) fn multiply_add(self, a: f32, x: &[f32], destination: &mut [f32]) {
+        let length = x.len().min(destination.len());
+        let x = &x[..length];
+        let destination = &mut destination[..length];
+
+        match self {
+            Kernel::Scalar => scalar::multiply_add(a, x, destination, 0),
+            // SAFETY: SSE is always available on x86_64, and the lengths are equal.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse => unsafe { x86::multiply_add_sse(a, x, destination) },
+            // SAFETY: AVX is only selected when the CPU supports it, and the lengths are equal.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx => unsafe { x86::multiply_add_avx(a, x, destination) },
+            // SAFETY: NEON is always available on aarch64, and the lengths are equal.
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => unsafe { neon::multiply_add(a, x, destination) },
+        }
+    }
+
+    pub(crate) fn multiply_add_ramps(self, x: &[f32], targets: &mut [MixTarget<'_>]) {
+        for (_, destination) in targets.iter() {
+            assert!(
+                destination.is_empty() || destination.len() == x.len(),
+                "the destinations must be as long as the source"
+            );
+        }
 
-    pub(crate) fn multiply_add_slope(a: f32, step: f32, x: &[f32], destination: &mut [f32]) {
-        let mut a = a;
-        for (x, destination) in x.iter().zip(destination.iter_mut()) {
+        match self {
+            Kernel::Scalar => scalar::multiply_add_ramps(x, targets, 0),
+            // SAFETY: SSE is always available on x86_64, and the lengths were checked above.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse => unsafe { x86::multiply_add_ramps_sse(x, targets) },
+            // SAFETY: AVX is only selected when the CPU supports it, and the lengths were checked above.
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx => unsafe { x86::multiply_add_ramps_avx(x, targets) },
+            // SAFETY: NEON is always available on aarch64, and the lengths were checked above.
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => unsafe { neon::multiply_add_ramps(x, targets) },
+        }
+    }
+}
+
+// The portable kernels, which also process the remainder of the vectorized kernels.
+mod scalar {
+    use super::MixTarget;
+
+    pub(super) fn multiply_add(a: f32, x: &[f32], destination: &mut [f32], start: usize) {
+        for (x, destination) in x[start..].iter().zip(destination[start..].iter_mut()) {
             *destination += a * *x;
-            a += step;
         }
     }
+
+    pub(super) fn multiply_add_ramps(x: &[f32], targets: &mut [MixTarget<'_>], start: usize) {
+        for (ramp, destination) in targets.iter_mut() {
+            if destination.is_empty() {
+                continue;
+            }
+            for (i, (x, destination)) in
+                x.iter().zip(destination.iter_mut()).enumerate().skip(start)
+            {
+                *destination += (ramp.gain + ramp.step * i as f32) * *x;
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    use super::{MixTarget, scalar};
+
+    // The callers must ensure that the destination is as long as the source.
+    pub(super) unsafe fn multiply_add_sse(a: f32, x: &[f32], destination: &mut [f32]) {
+        let chunks = x.len() / 4;
+        unsafe {
+            let a4 = _mm_set1_ps(a);
+            for i in (0..chunks).map(|chunk| 4 * chunk) {
+                let product = _mm_mul_ps(a4, _mm_loadu_ps(x.as_ptr().add(i)));
+                let d = destination.as_mut_ptr().add(i);
+                _mm_storeu_ps(d, _mm_add_ps(_mm_loadu_ps(d), product));
+            }
+        }
+        scalar::multiply_add(a, x, destination, 4 * chunks);
+    }
+
+    // The callers must ensure that every destination is empty or as long as the source.
+    pub(super) unsafe fn multiply_add_ramps_sse(x: &[f32], targets: &mut [MixTarget<'_>]) {
+        let chunks = x.len() / 4;
+        unsafe {
+            let lanes = _mm_setr_ps(0_f32, 1_f32, 2_f32, 3_f32);
+            for i in (0..chunks).map(|chunk| 4 * chunk) {
+                let x4 = _mm_loadu_ps(x.as_ptr().add(i));
+                let index = _mm_add_ps(_mm_set1_ps(i as f32), lanes);
+                for (ramp, destination) in targets.iter_mut() {
+                    if destination.is_empty() {
+                        continue;
+                    }
+                    let gain = _mm_add_ps(
+                        _mm_set1_ps(ramp.gain),
+                        _mm_mul_ps(_mm_set1_ps(ramp.step), index),
+                    );
+                    let d = destination.as_mut_ptr().add(i);
+                    _mm_storeu_ps(d, _mm_add_ps(_mm_loadu_ps(d), _mm_mul_ps(gain, x4)));
+                }
+            }
+        }
+        scalar::multiply_add_ramps(x, targets, 4 * chunks);
+    }
+
+    // The callers must ensure that AVX is supported and that the destination is as long as the source.
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn multiply_add_avx(a: f32, x: &[f32], destination: &mut [f32]) {
+        let chunks = x.len() / 8;
+        unsafe {
+            let a8 = _mm256_set1_ps(a);
+            for i in (0..chunks).map(|chunk| 8 * chunk) {
+                let product = _mm256_mul_ps(a8, _mm256_loadu_ps(x.as_ptr().add(i)));
+                let d = destination.as_mut_ptr().add(i);
+                _mm256_storeu_ps(d, _mm256_add_ps(_mm256_loadu_ps(d), product));
+            }
+        }
+        scalar::multiply_add(a, x, destination, 8 * chunks);
+    }
+
+    // The callers must ensure that AVX is supported and that every destination is empty or as long as the source.
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn multiply_add_ramps_avx(x: &[f32], targets: &mut [MixTarget<'_>]) {
+        let chunks = x.len() / 8;
+        unsafe {
+            let lanes = _mm256_setr_ps(0_f32, 1_f32, 2_f32, 3_f32, 4_f32, 5_f32, 6_f32, 7_f32);
+            for i in (0..chunks).map(|chunk| 8 * chunk) {
+                let x8 = _mm256_loadu_ps(x.as_ptr().add(i));
+                let index = _mm256_add_ps(_mm256_set1_ps(i as f32), lanes);
+                for (ramp, destination) in targets.iter_mut() {
+                    if destination.is_empty() {
+                        continue;
+                    }
+                    let gain = _mm256_add_ps(
+                        _mm256_set1_ps(ramp.gain),
+                        _mm256_mul_ps(_mm256_set1_ps(ramp.step), index),
+                    );
+                    let d = destination.as_mut_ptr().add(i);
+                    _mm256_storeu_ps(
+                        d,
+                        _mm256_add_ps(_mm256_loadu_ps(d), _mm256_mul_ps(gain, x8)),
+                    );
+                }
+            }
+        }
+        scalar::multiply_add_ramps(x, targets, 8 * chunks);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use core::arch::aarch64::*;
+
+    use super::{MixTarget, scalar};
+
+    // The callers must ensure that the destination is as long as the source.
+    pub(super) unsafe fn multiply_add(a: f32, x: &[f32], destination: &mut [f32]) {
+        let chunks = x.len() / 4;
+        unsafe {
+            let a4 = vdupq_n_f32(a);
+            for i in (0..chunks).map(|chunk| 4 * chunk) {
+                let product = vmulq_f32(a4, vld1q_f32(x.as_ptr().add(i)));
+                let d = destination.as_mut_ptr().add(i);
+                vst1q_f32(d, vaddq_f32(vld1q_f32(d), product));
+            }
+        }
+        scalar::multiply_add(a, x, destination, 4 * chunks);
+    }
+
+    // The callers must ensure that every destination is empty or as long as the source.
+    // The multiplication and the addition are kept separate, since a fused multiply-add rounds differently.
+    pub(super) unsafe fn multiply_add_ramps(x: &[f32], targets: &mut [MixTarget<'_>]) {
+        let chunks = x.len() / 4;
+        unsafe {
+            let lanes = vld1q_f32([0_f32, 1_f32, 2_f32, 3_f32].as_ptr());
+            for i in (0..chunks).map(|chunk| 4 * chunk) {
+                let x4 = vld1q_f32(x.as_ptr().add(i));
+                let index = vaddq_f32(vdupq_n_f32(i as f32), lanes);
+                for (ramp, destination) in targets.iter_mut() {
+                    if destination.is_empty() {
+                        continue;
+                    }
+                    let gain = vaddq_f32(
+                        vdupq_n_f32(ramp.gain),
+                        vmulq_f32(vdupq_n_f32(ramp.step), index),
+                    );
+                    let d = destination.as_mut_ptr().add(i);
+                    vst1q_f32(d, vaddq_f32(vld1q_f32(d), vmulq_f32(gain, x4)));
+                }
+            }
+        }
+        scalar::multiply_add_ramps(x, targets, 4 * chunks);
+    }
 }
@@ -1,3 +1,6 @@
+use super::{FilterSlope, FilterType, InsertionEffect};
+use crate::utils;
+
 #[derive(PartialEq, Eq)]
 enum DataType {
     None,
@@ -21,16 +24,47 @@ pub(crate) struct SynthChannel {
     chorus_send: u8,
 
     rpn: u16,
+    nrpn: u16,
     pitch_bend_range: u16,
     coarse_tune: u16,
     fine_tune: u16,
 
     pitch_bend: f32,
+    pressure: u8,
+    brightness: u8,
+
+    // The GM2 sound controllers (CC71 to CC78), which are relative to the SoundFont.
+    resonance: u8,
+    release_time: u8,
+    attack_time: u8,
+    decay_time: u8,
+    vibrato_rate: u8,
+    vibrato_depth: u8,
+    vibrato_delay: u8,
+
+    // The filter selected by the API or the NRPNs, which overrides the regions.
+    filter_type: Option<FilterType>,
+    filter_slope: Option<FilterSlope>,
 
     last_data_type: DataType,
+
+    mpe_manager: Option<u8>,
+
+    // The priority for voice stealing and the polyphony limits,
+    // which are not MIDI states and survive a reset.
+    priority: u8,
+    polyphony: Option<usize>,
+    key_polyphony: Option<usize>,
+
+    pub(crate) insertion_effect: Option<InsertionEffect>,
+    // The insertion effect allocated at construction, kept here while the channel has none.
+    pub(crate) idle_insertion_effect: Option<InsertionEffect>,
 }
 
 impl SynthChannel {
+    pub(crate) const FILTER_TYPE_NRPN: u16 = (1 << 7) | 0x28;
+    pub(crate) const FILTER_SLOPE_NRPN: u16 = (1 << 7) | 0x29;
+
     pub(crate) fn new(is_percussion_channel: bool) -> Self {
         let mut channel = Self {
             is_percussion_channel,
@@ -44,11 +78,29 @@ impl SynthChannel {
             reverb_send: 0,
             chorus_send: 0,
             rpn: 0,
+            nrpn: 0,
             pitch_bend_range: 0,
             coarse_tune: 0,
             fine_tune: 0,
             pitch_bend: 0_f32,
+            pressure: 0,
+            brightness: 0,
+            resonance: 0,
+            release_time: 0,
+            attack_time: 0,
+            decay_time: 0,
+            vibrato_rate: 0,
+            vibrato_depth: 0,
+            vibrato_delay: 0,
+            filter_type: None,
+            filter_slope: None,
             last_data_type: DataType::None,
+            mpe_manager: None,
+            priority: 64,
+            polyphony: None,
+            key_polyphony: None,
+            insertion_effect: None,
+            idle_insertion_effect: None,
         };
 
         channel.reset();
@@ -70,11 +122,27 @@ impl SynthChannel {
         self.chorus_send = 0;
 
         self.rpn = 0xFFFF;
+        self.nrpn = 0xFFFF;
         self.pitch_bend_range = 2 << 7;
         self.coarse_tune = 0;
         self.fine_tune = 8192;
 
         self.pitch_bend = 0_f32;
+        self.pressure = 0;
+        self.brightness = 64;
+
+        self.resonance = 64;
+        self.release_time = 64;
+        self.attack_time = 64;
+        self.decay_time = 64;
+        self.vibrato_rate = 64;
+        self.vibrato_depth = 64;
+        self.vibrato_delay = 64;
+
+        self.filter_type = None;
+        self.filter_slope = None;
+
+        self.mpe_manager = None;
     }
 
     pub(crate) fn reset_all_controllers(&mut self) {
@@ -84,9 +152,11 @@ impl SynthChannel {
         self.hold_pedal = false;
 
         self.rpn = 0xFFFF;
+        self.nrpn = 0xFFFF;
         self.pitch_bend_range = 2 << 7;
 
         self.pitch_bend = 0_f32;
+        self.pressure = 0;
     }
 
     pub(crate) fn set_bank(&mut self, value: u8) {
@@ -137,6 +207,42 @@ impl SynthChannel {
         self.hold_pedal = value >= 64;
     }
 
+    pub(crate) fn set_brightness(&mut self, value: u8) {
+        self.brightness = value;
+    }
+
+    pub(crate) fn set_resonance(&mut self, value: u8) {
+        self.resonance = value;
+    }
+
+    pub(crate) fn set_release_time(&mut self, value: u8) {
+        self.release_time = value;
+    }
+
+    pub(crate) fn set_attack_time(&mut self, value: u8) {
+        self.attack_time = value;
+    }
+
+    pub(crate) fn set_decay_time(&mut self, value: u8) {
+        self.decay_time = value;
+    }
+
+    pub(crate) fn set_vibrato_rate(&mut self, value: u8) {
+        self.vibrato_rate = value;
+    }
+
+    pub(crate) fn set_vibrato_depth(&mut self, value: u8) {
+        self.vibrato_depth = value;
+    }
+
+    pub(crate) fn set_vibrato_delay(&mut self, value: u8) {
+        self.vibrato_delay = value;
+    }
+
+    pub(crate) fn set_channel_pressure(&mut self, value: u8) {
+        self.pressure = value;
+    }
+
     pub(crate) fn set_reverb_send(&mut self, value: u8) {
         self.reverb_send = value;
     }
@@ -155,15 +261,31 @@ impl SynthChannel {
         self.last_data_type = DataType::Rpn;
     }
 
-    pub(crate) fn set_nrpn_coarse(&mut self) {
+    pub(crate) fn set_nrpn_coarse(&mut self, value: u8) {
+        self.nrpn = (self.nrpn & 0x7F) | ((value as u16) << 7);
         self.last_data_type = DataType::Nrpn;
     }
 
-    pub(crate) fn set_nrpn_fine(&mut self) {
+    pub(crate) fn set_nrpn_fine(&mut self, value: u8) {
+        self.nrpn = (self.nrpn & 0xFF80) | value as u16;
         self.last_data_type = DataType::Nrpn;
     }
 
     pub(crate) fn data_entry_coarse(&mut self, value: u8) {
+        if self.last_data_type == DataType::Nrpn {
+            // The filter NRPNs sit next to the GS cutoff and resonance NRPNs (MSB 1, LSB 0x20 and 0x21).
+            if self.nrpn == SynthChannel::FILTER_TYPE_NRPN {
+                if let Some(filter_type) = FilterType::from_nrpn(value) {
+                    self.filter_type = Some(filter_type);
+                }
+            } else if self.nrpn == SynthChannel::FILTER_SLOPE_NRPN
+                && let Some(slope) = FilterSlope::from_nrpn(value)
+            {
+                self.filter_slope = Some(slope);
+            }
+            return;
+        }
+
         if self.last_data_type != DataType::Rpn {
             return;
         }
@@ -190,10 +312,23 @@ impl SynthChannel {
         }
     }
 
+    pub(crate) fn set_pitch_bend_range(&mut self, semitones: u8) {
+        self.pitch_bend_range = (semitones as u16) << 7;
+    }
+
+    pub(crate) fn set_mpe_manager(&mut self, manager: Option<u8>) {
+        self.mpe_manager = manager;
+    }
+
     pub(crate) fn set_pitch_bend(&mut self, lsb: u8, msb: u8) {
         self.pitch_bend = (1_f32 / 8192_f32) * ((lsb as i32 | ((msb as i32) << 7)) - 8192) as f32;
     }
 
+    /// Gets the registered parameter number selected for data entry, if any.
+    pub(crate) fn get_rpn(&self) -> Option<u16> {
+        (self.last_data_type == DataType::Rpn).then_some(self.rpn)
+    }
+
     pub(crate) fn get_bank_number(&self) -> u8 {
         self.bank_number
     }
@@ -218,6 +353,43 @@ impl SynthChannel {
         (1_f32 / 16383_f32) * self.expression as f32
     }
 
+    /// Gets the channel pressure as an additional vibrato depth in cents.
+    ///
+    /// This follows the default SoundFont modulator, which maps full pressure to 50 cents.
+    pub(crate) fn get_pressure(&self) -> f32 {
+        (50_f32 / 127_f32) * self.pressure as f32
+    }
+
+    /// Gets the brightness (CC74) as a filter cutoff offset in cents.
+    pub(crate) fn get_brightness(&self) -> f32 {
+        (2400_f32 / 64_f32) * (self.brightness as f32 - 64_f32)
+    }
+
+    /// Gets the resonance (CC71) as a filter Q offset in decibels.
+    pub(crate) fn get_resonance(&self) -> f32 {
+        (24_f32 / 64_f32) * (self.resonance as f32 - 64_f32)
+    }
+
+    /// Gets the GM2 sound controllers applied to the notes when they start.
+    pub(crate) fn get_sound_controllers(&self) -> SoundControllers {
+        // The times and the rate change by up to four times in each direction.
+        let factor = |value: u8| {
+            utils::cents_to_multiplying_factor((2400_f32 / 64_f32) * (value as f32 - 64_f32))
+        };
+
+        SoundControllers {
+            resonance: self.get_resonance(),
+            release_time: factor(self.release_time),
+            attack_time: factor(self.attack_time),
+            decay_time: factor(self.decay_time),
+            vibrato_rate: factor(self.vibrato_rate),
+            vibrato_depth: (50_f32 / 64_f32) * (self.vibrato_depth as f32 - 64_f32),
+            vibrato_delay: factor(self.vibrato_delay),
+            filter_type: self.filter_type,
+            filter_slope: self.filter_slope,
+        }
+    }
+
     pub(crate) fn get_hold_pedal(&self) -> bool {
         self.hold_pedal
     }
@@ -230,6 +402,14 @@ impl SynthChannel {
         (1_f32 / 127_f32) * self.chorus_send as f32
     }
 
+    pub(crate) fn get_pitch_bend_range_data(&self) -> u16 {
+        self.pitch_bend_range
+    }
+
+    pub(crate) fn set_pitch_bend_range_data(&mut self, value: u16) {
+        self.pitch_bend_range = value;
+    }
+
     pub(crate) fn get_pitch_bend_range(&self) -> f32 {
         (self.pitch_bend_range >> 7) as f32 + 0.01_f32 * (self.pitch_bend_range & 0x7F) as f32
     }
@@ -241,4 +421,85 @@ impl SynthChannel {
     pub(crate) fn get_pitch_bend(&self) -> f32 {
         self.get_pitch_bend_range() * self.pitch_bend
     }
+
+    pub(crate) fn set_priority(&mut self, value: u8) {
+        self.priority = value;
+    }
+
+    pub(crate) fn set_filter_type(&mut self, value: Option<FilterType>) {
+        self.filter_type = value;
+    }
+
+    pub(crate) fn get_filter_type(&self) -> Option<FilterType> {
+        self.filter_type
+    }
+
+    pub(crate) fn set_filter_slope(&mut self, value: Option<FilterSlope>) {
+        self.filter_slope = value;
+    }
+
+    pub(crate) fn get_filter_slope(&self) -> Option<FilterSlope> {
+        self.filter_slope
+    }
+
+    pub(crate) fn get_priority(&self) -> u8 {
+        self.priority
+    }
+
+    pub(crate) fn set_polyphony(&mut self, value: Option<usize>) {
+        self.polyphony = value;
+    }
+
+    pub(crate) fn get_polyphony(&self) -> Option<usize> {
+        self.polyphony
+    }
+
+    pub(crate) fn set_key_polyphony(&mut self, value: Option<usize>) {
+        self.key_polyphony = value;
+    }
+
+    pub(crate) fn get_key_polyphony(&self) -> Option<usize> {
+        self.key_polyphony
+    }
+
+    /// Gets the manager channel if this channel is a member channel of an MPE zone.
+    pub(crate) fn get_mpe_manager(&self) -> Option<u8> {
+        self.mpe_manager
+    }
+}
+
+// The GM2 sound controllers of a channel, as the offsets applied to a voice.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SoundControllers {
+    // The filter Q offset in decibels.
+    pub(crate) resonance: f32,
+    // The multiplying factors of the envelope times.
+    pub(crate) release_time: f32,
+    pub(crate) attack_time: f32,
+    pub(crate) decay_time: f32,
+    // The multiplying factor of the vibrato frequency.
+    pub(crate) vibrato_rate: f32,
+    // The vibrato depth offset in cents.
+    pub(crate) vibrato_depth: f32,
+    // The multiplying factor of the vibrato delay.
+    pub(crate) vibrato_delay: f32,
+    // The filter overriding the regions, if any.
+    pub(crate) filter_type: Option<FilterType>,
+    pub(crate) filter_slope: Option<FilterSlope>,
+}
+
+impl Default for SoundControllers {
+    fn default() -> Self {
+        Self {
+            resonance: 0_f32,
+            release_time: 1_f32,
+            attack_time: 1_f32,
+            decay_time: 1_f32,
+            vibrato_rate: 1_f32,
+            vibrato_depth: 0_f32,
+            vibrato_delay: 1_f32,
+            filter_type: None,
+            filter_slope: None,
+        }
+    }
 }
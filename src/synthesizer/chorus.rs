@@ -1,43 +1,189 @@
 use core::f64::consts;
 
 use bevy_platform::prelude::*;
+
+/// Specifies the parameters of the chorus effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChorusParams {
+    /// The center delay time in seconds, up to `0.1`.
+    pub delay: f64,
+    /// The modulation depth of the delay time in seconds, up to `0.01` and up to the delay.
+    pub depth: f64,
+    /// The modulation frequency in Hz, from `0.1` to `10.0`.
+    pub rate: f64,
+    /// The amount of the output fed back into the delay line, from `0.0` to `0.95`.
+    pub feedback: f32,
+    /// The output level of the chorus. `1.0` is the default level.
+    pub level: f32,
+}
+
+impl Default for ChorusParams {
+    fn default() -> Self {
+        Self {
+            delay: 0.002,
+            depth: 0.0019,
+            rate: 0.4,
+            feedback: 0.0,
+            level: 1.0,
+        }
+    }
+}
+
+impl ChorusParams {
+    fn clamped(&self) -> Self {
+        let delay = self.delay.clamp(0.0, Chorus::MAXIMUM_DELAY);
+        Self {
+            delay,
+            // A depth beyond the delay would read ahead of the write position.
+            depth: self.depth.clamp(0.0, Chorus::MAXIMUM_DEPTH.min(delay)),
+            rate: self.rate.clamp(Chorus::MINIMUM_RATE, 10.0),
+            feedback: self.feedback.clamp(0.0, 0.95),
+            level: self.level.max(0.0),
+        }
+    }
+}
+
+/// Specifies the chorus types (macros) of the Roland GS standard.
+///
+/// The GS chorus types are approximated with the parameters of the chorus effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChorusType {
+    /// A light chorus.
+    Chorus1,
+    /// A faster chorus.
+    Chorus2,
+    /// A deep chorus. This is the default of GS devices.
+    Chorus3,
+    /// A fast and deep chorus.
+    Chorus4,
+    /// A chorus with feedback.
+    FeedbackChorus,
+    /// A flanger.
+    Flanger,
+    /// A short delay.
+    ShortDelay,
+    /// A short delay with feedback.
+    ShortDelayFeedback,
+}
+
+impl ChorusType {
+    /// Gets the chorus type from the value of the GS chorus macro (0 to 7).
+    pub fn from_gs(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ChorusType::Chorus1),
+            1 => Some(ChorusType::Chorus2),
+            2 => Some(ChorusType::Chorus3),
+            3 => Some(ChorusType::Chorus4),
+            4 => Some(ChorusType::FeedbackChorus),
+            5 => Some(ChorusType::Flanger),
+            6 => Some(ChorusType::ShortDelay),
+            7 => Some(ChorusType::ShortDelayFeedback),
+            _ => None,
+        }
+    }
+
+    /// Gets the parameters of the chorus type.
+    pub fn get_params(&self) -> ChorusParams {
+        let (delay, depth, rate, feedback) = match self {
+            ChorusType::Chorus1 => (0.010, 0.0015, 0.4, 0.0),
+            ChorusType::Chorus2 => (0.008, 0.0030, 1.1, 0.05),
+            ChorusType::Chorus3 => (0.008, 0.0030, 0.4, 0.08),
+            ChorusType::Chorus4 => (0.006, 0.0025, 1.1, 0.15),
+            ChorusType::FeedbackChorus => (0.012, 0.0040, 0.3, 0.5),
+            ChorusType::Flanger => (0.003, 0.0015, 0.15, 0.85),
+            ChorusType::ShortDelay => (0.045, 0.0, 0.1, 0.0),
+            ChorusType::ShortDelayFeedback => (0.045, 0.0, 0.1, 0.6),
+        };
+
+        ChorusParams {
+            delay,
+            depth,
+            rate,
+            feedback,
+            level: 1.0,
+        }
+    }
+}
+
 pub(crate) struct Chorus {
     buffer_l: Vec<f32>,
     buffer_r: Vec<f32>,
 
     delay_table: Vec<f32>,
+    delay_table_length: usize,
 
     buffer_index: usize,
 
     delay_table_index_l: usize,
     delay_table_index_r: usize,
+
+    feedback: f32,
 }
 
 impl Chorus {
-    pub(crate) fn new(sample_rate: i32, delay: f64, depth: f64, frequency: f64) -> Self {
-        let buffer_l = vec![0_f32; ((sample_rate as f64) * (delay + depth)) as usize + 2];
-        let buffer_r = vec![0_f32; ((sample_rate as f64) * (delay + depth)) as usize + 2];
+    /// The maximum delay time in seconds.
+    pub(crate) const MAXIMUM_DELAY: f64 = 0.1;
+    /// The maximum modulation depth in seconds.
+    pub(crate) const MAXIMUM_DEPTH: f64 = 0.01;
+    /// The minimum modulation frequency in Hz.
+    pub(crate) const MINIMUM_RATE: f64 = 0.1;
+
+    pub(crate) fn new(sample_rate: i32, params: &ChorusParams) -> Self {
+        // The delay lines and the delay table are allocated for the longest delay
+        // and the slowest rate, so that changing the parameters never reallocates.
+        let buffer_length =
+            ((sample_rate as f64) * (Chorus::MAXIMUM_DELAY + Chorus::MAXIMUM_DEPTH)) as usize + 2;
+        let delay_table_length = ((sample_rate as f64) / Chorus::MINIMUM_RATE).round() as usize;
+
+        let mut chorus = Self {
+            buffer_l: vec![0_f32; buffer_length],
+            buffer_r: vec![0_f32; buffer_length],
+            delay_table: vec![0_f32; delay_table_length],
+            delay_table_length: 0,
+            buffer_index: 0,
+            delay_table_index_l: 0,
+            delay_table_index_r: 0,
+            feedback: 0_f32,
+        };
+
+        chorus.set_params(sample_rate, params);
+
+        chorus
+    }
+
+    /// Sets the parameters.
+    ///
+    /// This rewrites the delay table in place and does not allocate memory.
+    pub(crate) fn set_params(&mut self, sample_rate: i32, params: &ChorusParams) {
+        let ChorusParams {
+            delay,
+            depth,
+            rate,
+            feedback,
+            ..
+        } = params.clamped();
 
-        let mut delay_table = vec![0_f32; ((sample_rate as f64) / frequency).round() as usize];
-        let delay_table_length = delay_table.len();
-        for (t, input) in delay_table.iter_mut().enumerate().take(delay_table_length) {
+        let delay_table_length =
+            (((sample_rate as f64) / rate).round() as usize).clamp(1, self.delay_table.len());
+        for (t, input) in self.delay_table[..delay_table_length]
+            .iter_mut()
+            .enumerate()
+        {
             let phase = 2.0 * consts::PI * (t as f64) / (delay_table_length as f64);
             *input = ((sample_rate as f64) * (delay + depth * phase.sin())) as f32;
         }
 
-        let buffer_index: usize = 0;
-
-        let delay_table_index_l: usize = 0;
-        let delay_table_index_r: usize = delay_table_length / 4;
-
-        Self {
-            buffer_l,
-            buffer_r,
-            delay_table,
-            buffer_index,
-            delay_table_index_l,
-            delay_table_index_r,
+        // Keep the phase of the modulation when the rate changes.
+        if self.delay_table_length > 0 {
+            let scale = delay_table_length as f64 / self.delay_table_length as f64;
+            self.delay_table_index_l =
+                ((self.delay_table_index_l as f64 * scale) as usize).min(delay_table_length - 1);
         }
+        self.delay_table_index_r =
+            (self.delay_table_index_l + delay_table_length / 4) % delay_table_length;
+
+        self.delay_table_length = delay_table_length;
+        self.feedback = feedback;
     }
 
     pub(crate) fn process(
@@ -48,7 +194,7 @@ impl Chorus {
         output_right: &mut [f32],
     ) {
         let buffer_length = self.buffer_l.len();
-        let delay_table_length = self.delay_table.len();
+        let delay_table_length = self.delay_table_length;
         let output_length = output_left.len();
 
         for t in 0..output_length {
@@ -57,6 +203,8 @@ impl Chorus {
                     self.buffer_index as f64 - self.delay_table[self.delay_table_index_l] as f64;
                 if position < 0.0 {
                     position += buffer_length as f64;
+                } else if position >= buffer_length as f64 {
+                    position -= buffer_length as f64;
                 }
 
                 let index1 = position as usize;
@@ -81,6 +229,8 @@ impl Chorus {
                     self.buffer_index as f64 - self.delay_table[self.delay_table_index_r] as f64;
                 if position < 0.0 {
                     position += buffer_length as f64;
+                } else if position >= buffer_length as f64 {
+                    position -= buffer_length as f64;
                 }
 
                 let index1 = position as usize;
@@ -100,8 +250,8 @@ impl Chorus {
                 }
             }
 
-            self.buffer_l[self.buffer_index] = input_left[t];
-            self.buffer_r[self.buffer_index] = input_right[t];
+            self.buffer_l[self.buffer_index] = input_left[t] + self.feedback * output_left[t];
+            self.buffer_r[self.buffer_index] = input_right[t] + self.feedback * output_right[t];
             self.buffer_index += 1;
             if self.buffer_index == buffer_length {
                 self.buffer_index = 0;
@@ -110,14 +260,7 @@ impl Chorus {
     }
 
     pub(crate) fn mute(&mut self) {
-        let buffer_length = self.buffer_l.len();
-
-        for t in 0..buffer_length {
-            self.buffer_l[t] = 0_f32;
-        }
-
-        for t in 0..buffer_length {
-            self.buffer_r[t] = 0_f32;
-        }
+        self.buffer_l.fill(0_f32);
+        self.buffer_r.fill(0_f32);
     }
 }
@@ -0,0 +1,14 @@
+/// Specifies the shape of the volume envelope.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeCurve {
+    /// The curves of the SoundFont 2.04 specification.
+    /// The attack is convex in amplitude, and the decay and the release fall linearly in decibels,
+    /// by 100 dB over the decay or release time, down to the sustain level or −100 dB.
+    #[default]
+    Specification,
+    /// The curves of RustySynth, with a linear attack and exponential decay and release.
+    /// Only the shape of the curves matches RustySynth: the envelopes are evaluated
+    /// at a different control rate and the notes start with a different delay,
+    /// so the output is not identical to earlier versions.
+    RustySynth,
+}
@@ -31,3 +31,27 @@ impl fmt::Display for SynthesizerError {
         }
     }
 }
+
+/// Represents an error when rendering the waveform.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RenderError {
+    BufferLengthMismatch(usize, usize),
+    InterleavedLengthNotEven(usize),
+}
+
+impl error::Error for RenderError {}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::BufferLengthMismatch(left, right) => write!(
+                f,
+                "the output buffers for the left and right must be the same length, but were {left} and {right}",
+            ),
+            RenderError::InterleavedLengthNotEven(value) => write!(
+                f,
+                "the length of the interleaved stereo buffer must be even, but was {value}",
+            ),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// Specifies the response of the filter applied to each voice.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    /// Attenuates the frequencies above the cutoff, as the SoundFont specification defines.
+    #[default]
+    LowPass,
+    /// Attenuates the frequencies below the cutoff.
+    HighPass,
+    /// Passes a band of frequencies around the cutoff, which narrows as the resonance rises.
+    BandPass,
+    /// Rejects a band of frequencies around the cutoff.
+    Notch,
+    /// Boosts the frequencies around the cutoff by the resonance.
+    Peak,
+}
+
+/// Specifies the steepness of the filter applied to each voice.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterSlope {
+    /// A single 2-pole stage, which rolls off at 12 dB per octave.
+    #[default]
+    TwoPole,
+    /// Two cascaded 2-pole stages, which roll off at 24 dB per octave.
+    FourPole,
+}
+
+impl FilterType {
+    // Gets the filter type selected by the value of the filter type NRPN.
+    pub(crate) fn from_nrpn(value: u8) -> Option<FilterType> {
+        match value {
+            0 => Some(FilterType::LowPass),
+            1 => Some(FilterType::HighPass),
+            2 => Some(FilterType::BandPass),
+            3 => Some(FilterType::Notch),
+            4 => Some(FilterType::Peak),
+            _ => None,
+        }
+    }
+}
+
+impl FilterSlope {
+    // Gets the filter slope selected by the value of the filter slope NRPN.
+    pub(crate) fn from_nrpn(value: u8) -> Option<FilterSlope> {
+        match value {
+            0 => Some(FilterSlope::TwoPole),
+            1 => Some(FilterSlope::FourPole),
+            _ => None,
+        }
+    }
+}
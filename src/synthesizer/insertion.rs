@@ -0,0 +1,418 @@
+use core::f32::consts;
+
+use bevy_platform::prelude::*;
+
+use crate::prelude::*;
+
+/// Specifies the types of the insertion effects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertionEffectType {
+    /// A hard distortion.
+    Distortion,
+    /// A soft overdrive.
+    Overdrive,
+    /// A rotary speaker.
+    Rotary,
+    /// A band-pass filter swept by an LFO.
+    AutoWah,
+    /// A four-stage phaser.
+    Phaser,
+    /// An amplitude modulation.
+    Tremolo,
+    /// A stereo delay with cross feedback.
+    StereoDelay,
+}
+
+impl InsertionEffectType {
+    /// Gets the default parameters of the effect type.
+    pub fn get_params(&self) -> InsertionEffectParams {
+        let params = InsertionEffectParams {
+            effect_type: *self,
+            drive: 0_f32,
+            rate: 0_f32,
+            depth: 0_f32,
+            delay: 0_f32,
+            feedback: 0_f32,
+            mix: 1_f32,
+            level: 1_f32,
+        };
+
+        match self {
+            InsertionEffectType::Distortion => InsertionEffectParams {
+                drive: 0.6,
+                level: 0.4,
+                ..params
+            },
+            InsertionEffectType::Overdrive => InsertionEffectParams {
+                drive: 0.4,
+                level: 0.6,
+                ..params
+            },
+            InsertionEffectType::Rotary => InsertionEffectParams {
+                rate: 6.5,
+                depth: 0.6,
+                ..params
+            },
+            InsertionEffectType::AutoWah => InsertionEffectParams {
+                rate: 2.0,
+                depth: 0.7,
+                mix: 0.8,
+                ..params
+            },
+            InsertionEffectType::Phaser => InsertionEffectParams {
+                rate: 0.5,
+                depth: 0.8,
+                feedback: 0.5,
+                mix: 0.5,
+                ..params
+            },
+            InsertionEffectType::Tremolo => InsertionEffectParams {
+                rate: 5.0,
+                depth: 0.6,
+                ..params
+            },
+            InsertionEffectType::StereoDelay => InsertionEffectParams {
+                delay: 0.3,
+                feedback: 0.35,
+                mix: 0.3,
+                ..params
+            },
+        }
+    }
+
+    /// Gets the effect type from the GS (SC-88Pro) EFX type.
+    /// Returns `None` for Thru and the unsupported types.
+    pub(crate) fn from_gs(msb: u8, lsb: u8) -> Option<Self> {
+        match (msb, lsb) {
+            (0x01, 0x10) => Some(InsertionEffectType::Overdrive),
+            (0x01, 0x11) => Some(InsertionEffectType::Distortion),
+            (0x01, 0x20) => Some(InsertionEffectType::Phaser),
+            (0x01, 0x21) => Some(InsertionEffectType::AutoWah),
+            (0x01, 0x22) => Some(InsertionEffectType::Rotary),
+            (0x01, 0x25) => Some(InsertionEffectType::Tremolo),
+            (0x01, 0x50) => Some(InsertionEffectType::StereoDelay),
+            _ => None,
+        }
+    }
+
+    /// Gets the effect type from the XG insertion effect type.
+    /// Returns `None` for No Effect and the unsupported types.
+    pub(crate) fn from_xg(msb: u8, _lsb: u8) -> Option<Self> {
+        match msb {
+            0x06 => Some(InsertionEffectType::StereoDelay),
+            0x45 => Some(InsertionEffectType::Rotary),
+            0x46 => Some(InsertionEffectType::Tremolo),
+            0x48 => Some(InsertionEffectType::Phaser),
+            0x49 => Some(InsertionEffectType::Distortion),
+            0x4A => Some(InsertionEffectType::Overdrive),
+            0x4E => Some(InsertionEffectType::AutoWah),
+            _ => None,
+        }
+    }
+}
+
+/// Specifies the parameters of an insertion effect.
+/// Each effect type only uses some of the parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsertionEffectParams {
+    /// The type of the effect.
+    pub effect_type: InsertionEffectType,
+    /// The amount of distortion, from `0.0` to `1.0` (distortion and overdrive).
+    pub drive: f32,
+    /// The modulation frequency in Hz (rotary, auto-wah, phaser and tremolo).
+    pub rate: f32,
+    /// The modulation depth, from `0.0` to `1.0` (rotary, auto-wah, phaser and tremolo).
+    pub depth: f32,
+    /// The delay time in seconds, up to `1.0` (stereo delay).
+    pub delay: f32,
+    /// The amount of feedback, from `0.0` to `0.95` (phaser and stereo delay).
+    pub feedback: f32,
+    /// The balance between the dry and the effected signal, from `0.0` to `1.0`.
+    pub mix: f32,
+    /// The output level.
+    pub level: f32,
+}
+
+impl InsertionEffectParams {
+    fn clamped(&self) -> Self {
+        Self {
+            effect_type: self.effect_type,
+            drive: self.drive.clamp(0_f32, 1_f32),
+            rate: self.rate.clamp(0_f32, 20_f32),
+            depth: self.depth.clamp(0_f32, 1_f32),
+            delay: self.delay.clamp(0_f32, InsertionEffect::MAXIMUM_DELAY),
+            feedback: self.feedback.clamp(0_f32, 0.95_f32),
+            mix: self.mix.clamp(0_f32, 1_f32),
+            level: self.level.max(0_f32),
+        }
+    }
+}
+
+// The state of the insertion effect configured by SysEx.
+// GS and XG both have a single insertion effect, which is copied to the assigned channels.
+pub(crate) struct InsertionSysExState {
+    pub(crate) effect_type: [u8; 2],
+    pub(crate) params: Option<InsertionEffectParams>,
+    pub(crate) channels: u16,
+}
+
+impl InsertionSysExState {
+    pub(crate) fn new() -> Self {
+        Self {
+            effect_type: [0, 0],
+            params: None,
+            channels: 0,
+        }
+    }
+}
+
+pub(crate) struct InsertionEffect {
+    sample_rate: f32,
+    params: InsertionEffectParams,
+
+    // The sub-mix of the channel, processed in place.
+    pub(crate) block_left: Vec<f32>,
+    pub(crate) block_right: Vec<f32>,
+
+    // The phase of the LFO, from 0 to 1.
+    phase: f32,
+
+    // The delay lines, allocated for the longest stereo delay so that changing the effect type
+    // does not allocate. The rotary speaker only uses the start of the left one.
+    buffer_left: Vec<f32>,
+    buffer_right: Vec<f32>,
+    rotary_length: usize,
+
+    processor: Processor,
+}
+
+enum Processor {
+    Shaper {
+        tone: [f32; 2],
+    },
+    Rotary {
+        index: usize,
+    },
+    AutoWah {
+        low: [f32; 2],
+        band: [f32; 2],
+    },
+    Phaser {
+        x1: [[f32; Processor::PHASER_STAGES]; 2],
+        y1: [[f32; Processor::PHASER_STAGES]; 2],
+        last: [f32; 2],
+    },
+    Tremolo,
+    StereoDelay {
+        index: usize,
+    },
+}
+
+impl Processor {
+    const PHASER_STAGES: usize = 4;
+
+    fn new(effect_type: InsertionEffectType) -> Self {
+        match effect_type {
+            InsertionEffectType::Distortion | InsertionEffectType::Overdrive => {
+                Processor::Shaper { tone: [0_f32; 2] }
+            }
+            InsertionEffectType::Rotary => Processor::Rotary { index: 0 },
+            InsertionEffectType::AutoWah => Processor::AutoWah {
+                low: [0_f32; 2],
+                band: [0_f32; 2],
+            },
+            InsertionEffectType::Phaser => Processor::Phaser {
+                x1: [[0_f32; Processor::PHASER_STAGES]; 2],
+                y1: [[0_f32; Processor::PHASER_STAGES]; 2],
+                last: [0_f32; 2],
+            },
+            InsertionEffectType::Tremolo => Processor::Tremolo,
+            InsertionEffectType::StereoDelay => Processor::StereoDelay { index: 0 },
+        }
+    }
+}
+
+impl InsertionEffect {
+    /// The maximum delay time of the stereo delay in seconds.
+    pub(crate) const MAXIMUM_DELAY: f32 = 1.0;
+    // The maximum delay time of the rotary speaker in seconds.
+    const ROTARY_DELAY: f32 = 0.005;
+
+    // Allocates the effect, which is configured by `set_params` before it is used.
+    pub(crate) fn new(settings: &SynthesizerSettings) -> Self {
+        let sample_rate = settings.sample_rate as f32;
+        let buffer_length = (InsertionEffect::MAXIMUM_DELAY * sample_rate) as usize + 1;
+        let params = InsertionEffectType::Tremolo.get_params();
+
+        Self {
+            sample_rate,
+            params,
+            block_left: vec![0_f32; settings.block_size],
+            block_right: vec![0_f32; settings.block_size],
+            phase: 0_f32,
+            buffer_left: vec![0_f32; buffer_length],
+            buffer_right: vec![0_f32; buffer_length],
+            rotary_length: (InsertionEffect::ROTARY_DELAY * sample_rate) as usize + 2,
+            processor: Processor::new(params.effect_type),
+        }
+    }
+
+    pub(crate) fn get_params(&self) -> InsertionEffectParams {
+        self.params
+    }
+
+    // Sets the parameters. A change of the effect type starts the new effect from silence,
+    // reusing the delay lines instead of allocating.
+    pub(crate) fn set_params(&mut self, params: &InsertionEffectParams) {
+        let effect_type = self.params.effect_type;
+        self.params = params.clamped();
+        if params.effect_type != effect_type {
+            self.phase = 0_f32;
+            self.mute();
+        }
+    }
+
+    // Processes the first `length` samples of the sub-mix in place.
+    pub(crate) fn process(&mut self, length: usize) {
+        let Self {
+            sample_rate,
+            params,
+            block_left,
+            block_right,
+            phase,
+            buffer_left,
+            buffer_right,
+            rotary_length,
+            processor,
+        } = self;
+        let sample_rate = *sample_rate;
+        let phase_increment = params.rate / sample_rate;
+
+        for (left, right) in block_left[..length]
+            .iter_mut()
+            .zip(block_right[..length].iter_mut())
+        {
+            let input = [*left, *right];
+            let angle = 2_f32 * consts::PI * *phase;
+            let (lfo_sin, lfo_cos) = angle.sin_cos();
+            *phase += phase_increment;
+            if *phase >= 1_f32 {
+                *phase -= 1_f32;
+            }
+
+            let output = match processor {
+                Processor::Shaper { tone } => {
+                    let (gain, cutoff) = if params.effect_type == InsertionEffectType::Distortion {
+                        (1_f32 + 30_f32 * params.drive, 6000_f32)
+                    } else {
+                        (1_f32 + 10_f32 * params.drive, 8000_f32)
+                    };
+                    let a = 1_f32 - (-2_f32 * consts::PI * cutoff / sample_rate).exp();
+                    for (tone, input) in tone.iter_mut().zip(input) {
+                        let x = gain * input;
+                        let y = if params.effect_type == InsertionEffectType::Distortion {
+                            x.tanh()
+                        } else {
+                            x / (1_f32 + x.abs())
+                        };
+                        *tone += a * (y - *tone);
+                    }
+                    *tone
+                }
+                Processor::Rotary { index } => {
+                    let buffer = &mut buffer_left[..*rotary_length];
+                    let length = buffer.len();
+                    buffer[*index] = 0.5_f32 * (input[0] + input[1]);
+
+                    // The horn moves towards one side while moving away from the other.
+                    let mut output = [0_f32; 2];
+                    for (ch, lfo) in [lfo_sin, -lfo_sin].into_iter().enumerate() {
+                        let delay = 0.5_f32
+                            * InsertionEffect::ROTARY_DELAY
+                            * sample_rate
+                            * (1_f32 + params.depth * lfo);
+                        let mut position = *index as f32 - delay;
+                        if position < 0_f32 {
+                            position += length as f32;
+                        }
+                        let index1 = position as usize % length;
+                        let index2 = (index1 + 1) % length;
+                        let a = position - position.floor();
+                        let x = buffer[index1] + a * (buffer[index2] - buffer[index1]);
+                        let tremolo =
+                            1_f32 - 0.3_f32 * params.depth * (0.5_f32 - 0.5_f32 * lfo_cos);
+                        output[ch] = tremolo * x;
+                    }
+
+                    *index += 1;
+                    if *index == length {
+                        *index = 0;
+                    }
+                    output
+                }
+                Processor::AutoWah { low, band } => {
+                    let exponent = 4_f32 * params.depth * (0.5_f32 + 0.5_f32 * lfo_sin);
+                    let cutoff = (200_f32 * 2_f32.powf(exponent)).min(sample_rate / 6_f32);
+                    let f = 2_f32 * (consts::PI * cutoff / sample_rate).sin();
+                    let damping = 0.25_f32;
+                    let mut output = [0_f32; 2];
+                    for ch in 0..2 {
+                        low[ch] += f * band[ch];
+                        let high = input[ch] - low[ch] - damping * band[ch];
+                        band[ch] += f * high;
+                        output[ch] = damping * band[ch];
+                    }
+                    output
+                }
+                Processor::Phaser { x1, y1, last } => {
+                    let mut output = [0_f32; 2];
+                    for (ch, lfo) in [lfo_sin, lfo_cos].into_iter().enumerate() {
+                        let exponent = params.depth * (0.5_f32 + 0.5_f32 * lfo);
+                        let cutoff = (200_f32 * 10_f32.powf(exponent)).min(0.45_f32 * sample_rate);
+                        let t = (consts::PI * cutoff / sample_rate).tan();
+                        let a = (t - 1_f32) / (t + 1_f32);
+                        let mut x = input[ch] + params.feedback * last[ch];
+                        for stage in 0..Processor::PHASER_STAGES {
+                            let y = a * x + x1[ch][stage] - a * y1[ch][stage];
+                            x1[ch][stage] = x;
+                            y1[ch][stage] = y;
+                            x = y;
+                        }
+                        last[ch] = x;
+                        output[ch] = x;
+                    }
+                    output
+                }
+                Processor::Tremolo => {
+                    let gain = 1_f32 - params.depth * (0.5_f32 - 0.5_f32 * lfo_sin);
+                    [gain * input[0], gain * input[1]]
+                }
+                Processor::StereoDelay { index } => {
+                    let length = buffer_left.len();
+                    let delay = ((params.delay * sample_rate) as usize).clamp(1, length - 1);
+                    let read = (*index + length - delay) % length;
+                    let output = [buffer_left[read], buffer_right[read]];
+                    buffer_left[*index] = input[0] + params.feedback * output[1];
+                    buffer_right[*index] = input[1] + params.feedback * output[0];
+                    *index += 1;
+                    if *index == length {
+                        *index = 0;
+                    }
+                    output
+                }
+            };
+
+            let dry = 1_f32 - params.mix;
+            *left = params.level * (dry * input[0] + params.mix * output[0]);
+            *right = params.level * (dry * input[1] + params.mix * output[1]);
+        }
+    }
+
+    pub(crate) fn mute(&mut self) {
+        self.block_left.fill(0_f32);
+        self.block_right.fill(0_f32);
+        self.buffer_left.fill(0_f32);
+        self.buffer_right.fill(0_f32);
+        self.processor = Processor::new(self.params.effect_type);
+    }
+}
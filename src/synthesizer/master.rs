@@ -0,0 +1,419 @@
+use bevy_platform::prelude::*;
+
+use super::voice::BiQuadFilter;
+use crate::{prelude::*, utils};
+
+/// Specifies the shape of a band of the master equalizer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EqBandType {
+    /// Boosts or cuts the frequencies below the corner frequency.
+    LowShelf,
+    /// Boosts or cuts the frequencies above the corner frequency.
+    HighShelf,
+    /// Boosts or cuts the frequencies around the center frequency.
+    Peak,
+}
+
+/// Specifies a band of the master equalizer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    /// The shape of the band.
+    pub band_type: EqBandType,
+    /// The corner or center frequency in Hz.
+    pub frequency: f32,
+    /// The gain in dB. A band with the gain of `0.0` is bypassed.
+    pub gain: f32,
+    /// The Q value (the bandwidth for peaks, the slope for shelves).
+    pub q: f32,
+}
+
+impl EqBand {
+    fn new(band_type: EqBandType, frequency: f32) -> Self {
+        Self {
+            band_type,
+            frequency,
+            gain: 0_f32,
+            q: core::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+}
+
+/// Specifies the parameters of the master compressor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressorParams {
+    /// The threshold in dBFS.
+    pub threshold: f32,
+    /// The compression ratio, `1.0` or greater.
+    pub ratio: f32,
+    /// The attack time in seconds.
+    pub attack: f32,
+    /// The release time in seconds.
+    pub release: f32,
+    /// The gain applied after compression in dB.
+    pub makeup_gain: f32,
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        Self {
+            threshold: -12_f32,
+            ratio: 4_f32,
+            attack: 0.005_f32,
+            release: 0.1_f32,
+            makeup_gain: 0_f32,
+        }
+    }
+}
+
+/// Specifies the parameters of the master limiter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterParams {
+    /// The maximum output level in dBFS, `0.0` or lower.
+    pub ceiling: f32,
+    /// The lookahead time in seconds, up to `0.02`.
+    /// The output of the synthesizer is delayed by this time.
+    pub lookahead: f32,
+    /// The release time in seconds.
+    pub release: f32,
+}
+
+impl Default for LimiterParams {
+    fn default() -> Self {
+        Self {
+            ceiling: -0.3_f32,
+            lookahead: 0.0015_f32,
+            release: 0.05_f32,
+        }
+    }
+}
+
+/// Specifies the processing applied to the master output after the effects.
+///
+/// The signal goes through the equalizer, the compressor and the limiter in this order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterChainParams {
+    /// The bands of the parametric equalizer.
+    pub eq_bands: [EqBand; MasterChainParams::EQ_BAND_COUNT],
+    /// The compressor, or `None` to bypass it.
+    pub compressor: Option<CompressorParams>,
+    /// The limiter, or `None` to bypass it.
+    pub limiter: Option<LimiterParams>,
+}
+
+impl MasterChainParams {
+    /// The number of bands of the equalizer.
+    pub const EQ_BAND_COUNT: usize = 4;
+}
+
+impl Default for MasterChainParams {
+    fn default() -> Self {
+        Self {
+            eq_bands: [
+                EqBand::new(EqBandType::LowShelf, 100_f32),
+                EqBand::new(EqBandType::Peak, 500_f32),
+                EqBand::new(EqBandType::Peak, 2500_f32),
+                EqBand::new(EqBandType::HighShelf, 8000_f32),
+            ],
+            compressor: None,
+            limiter: Some(LimiterParams::default()),
+        }
+    }
+}
+
+pub(crate) struct MasterChain {
+    sample_rate: i32,
+    params: MasterChainParams,
+
+    eq_left: Vec<BiQuadFilter>,
+    eq_right: Vec<BiQuadFilter>,
+
+    compressor: Compressor,
+    limiter: Option<Limiter>,
+}
+
+impl MasterChain {
+    pub(crate) fn new(settings: &SynthesizerSettings, params: &MasterChainParams) -> Self {
+        let mut chain = Self {
+            sample_rate: settings.sample_rate,
+            params: *params,
+            eq_left: (0..MasterChainParams::EQ_BAND_COUNT)
+                .map(|_| BiQuadFilter::new(settings))
+                .collect(),
+            eq_right: (0..MasterChainParams::EQ_BAND_COUNT)
+                .map(|_| BiQuadFilter::new(settings))
+                .collect(),
+            compressor: Compressor::new(),
+            limiter: None,
+        };
+
+        chain.set_params(params);
+
+        chain
+    }
+
+    pub(crate) fn get_params(&self) -> MasterChainParams {
+        self.params
+    }
+
+    /// Sets the parameters, keeping the state of the processors.
+    /// The lookahead buffer is reallocated here if necessary, never while rendering.
+    pub(crate) fn set_params(&mut self, params: &MasterChainParams) {
+        self.params = *params;
+
+        for (band, (left, right)) in params
+            .eq_bands
+            .iter()
+            .zip(self.eq_left.iter_mut().zip(self.eq_right.iter_mut()))
+        {
+            let q = band.q.max(0.1_f32);
+            for filter in [left, right] {
+                match band.band_type {
+                    EqBandType::LowShelf => {
+                        filter.set_low_shelf_filter(band.frequency, band.gain, q)
+                    }
+                    EqBandType::HighShelf => {
+                        filter.set_high_shelf_filter(band.frequency, band.gain, q)
+                    }
+                    EqBandType::Peak => filter.set_peaking_filter(band.frequency, band.gain, q),
+                }
+            }
+        }
+
+        if let Some(compressor) = params.compressor.as_ref() {
+            self.compressor.set_params(self.sample_rate, compressor);
+        }
+
+        match params.limiter.as_ref() {
+            Some(limiter_params) => {
+                let lookahead = Limiter::get_lookahead_samples(self.sample_rate, limiter_params);
+                match self.limiter.as_mut() {
+                    Some(limiter) if limiter.get_lookahead_length() == lookahead => {
+                        limiter.set_params(self.sample_rate, limiter_params)
+                    }
+                    _ => self.limiter = Some(Limiter::new(self.sample_rate, limiter_params)),
+                }
+            }
+            None => self.limiter = None,
+        }
+    }
+
+    pub(crate) fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        for filter in self.eq_left.iter_mut() {
+            filter.process(left);
+        }
+        for filter in self.eq_right.iter_mut() {
+            filter.process(right);
+        }
+
+        if self.params.compressor.is_some() {
+            self.compressor.process(left, right);
+        }
+
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.process(left, right);
+        }
+    }
+
+    pub(crate) fn mute(&mut self) {
+        for filter in self.eq_left.iter_mut().chain(self.eq_right.iter_mut()) {
+            filter.clear_buffer();
+        }
+        self.compressor.envelope = 0_f32;
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.mute();
+        }
+    }
+}
+
+fn get_smoothing_coefficient(sample_rate: i32, time: f32) -> f32 {
+    let samples = time * sample_rate as f32;
+    if samples > 1_f32 {
+        1_f32 - (-1_f32 / samples).exp()
+    } else {
+        1_f32
+    }
+}
+
+// A feed-forward compressor with a peak detector linked across the channels.
+struct Compressor {
+    threshold: f32,
+    slope: f32,
+    makeup_gain: f32,
+    attack: f32,
+    release: f32,
+
+    envelope: f32,
+}
+
+impl Compressor {
+    fn new() -> Self {
+        Self {
+            threshold: 0_f32,
+            slope: 0_f32,
+            makeup_gain: 1_f32,
+            attack: 1_f32,
+            release: 1_f32,
+            envelope: 0_f32,
+        }
+    }
+
+    fn set_params(&mut self, sample_rate: i32, params: &CompressorParams) {
+        self.threshold = params.threshold;
+        self.slope = 1_f32 / params.ratio.max(1_f32) - 1_f32;
+        self.makeup_gain = utils::decibels_to_linear(params.makeup_gain);
+        self.attack = get_smoothing_coefficient(sample_rate, params.attack);
+        self.release = get_smoothing_coefficient(sample_rate, params.release);
+    }
+
+    fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let level = left.abs().max(right.abs());
+            let coefficient = if level > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope += coefficient * (level - self.envelope);
+
+            let mut gain = self.makeup_gain;
+            let over = utils::linear_to_decibels(self.envelope) - self.threshold;
+            if over > 0_f32 {
+                gain *= utils::decibels_to_linear(self.slope * over);
+            }
+
+            *left *= gain;
+            *right *= gain;
+        }
+    }
+}
+
+// A brickwall limiter which looks ahead the true peaks.
+//
+// The true peaks are estimated by interpolating the signal at four times the sample rate.
+// The gain reduction is smoothed over the lookahead time, but the output samples
+// never exceed the ceiling.
+struct Limiter {
+    ceiling: f32,
+    attack: f32,
+    release: f32,
+
+    buffer_left: Vec<f32>,
+    buffer_right: Vec<f32>,
+    required_gain: Vec<f32>,
+    buffer_index: usize,
+
+    // The last three input samples, used to find the peaks between the samples.
+    history_left: [f32; 3],
+    history_right: [f32; 3],
+
+    gain: f32,
+}
+
+impl Limiter {
+    const MAXIMUM_LOOKAHEAD: f32 = 0.02;
+
+    fn get_lookahead_samples(sample_rate: i32, params: &LimiterParams) -> usize {
+        let lookahead = params.lookahead.clamp(0_f32, Limiter::MAXIMUM_LOOKAHEAD);
+        (lookahead * sample_rate as f32).round() as usize
+    }
+
+    fn new(sample_rate: i32, params: &LimiterParams) -> Self {
+        let length = Limiter::get_lookahead_samples(sample_rate, params) + 1;
+        let mut limiter = Self {
+            ceiling: 1_f32,
+            attack: 1_f32,
+            release: 1_f32,
+            buffer_left: vec![0_f32; length],
+            buffer_right: vec![0_f32; length],
+            required_gain: vec![1_f32; length],
+            buffer_index: 0,
+            history_left: [0_f32; 3],
+            history_right: [0_f32; 3],
+            gain: 1_f32,
+        };
+
+        limiter.set_params(sample_rate, params);
+
+        limiter
+    }
+
+    fn get_lookahead_length(&self) -> usize {
+        self.buffer_left.len() - 1
+    }
+
+    fn set_params(&mut self, sample_rate: i32, params: &LimiterParams) {
+        self.ceiling = utils::decibels_to_linear(params.ceiling.min(0_f32));
+        // Reaches the target gain within the lookahead time.
+        self.attack = get_smoothing_coefficient(sample_rate, 0.2 * params.lookahead);
+        self.release = get_smoothing_coefficient(sample_rate, params.release);
+    }
+
+    fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let length = self.buffer_left.len();
+
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            // The peak of the previous sample is known once the current sample arrives.
+            let peak = Limiter::get_true_peak(&self.history_left, *left)
+                .max(Limiter::get_true_peak(&self.history_right, *right));
+            let sample_left = self.history_left[2];
+            let sample_right = self.history_right[2];
+            self.history_left = [self.history_left[1], self.history_left[2], *left];
+            self.history_right = [self.history_right[1], self.history_right[2], *right];
+
+            self.buffer_left[self.buffer_index] = sample_left;
+            self.buffer_right[self.buffer_index] = sample_right;
+            self.required_gain[self.buffer_index] = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1_f32
+            };
+            self.buffer_index += 1;
+            if self.buffer_index == length {
+                self.buffer_index = 0;
+            }
+
+            let target = self
+                .required_gain
+                .iter()
+                .fold(1_f32, |min, gain| min.min(*gain));
+            let coefficient = if target < self.gain {
+                self.attack
+            } else {
+                self.release
+            };
+            self.gain += coefficient * (target - self.gain);
+
+            // The oldest sample in the buffer is the output.
+            let gain = self.gain.min(self.required_gain[self.buffer_index]);
+            *left = (gain * self.buffer_left[self.buffer_index]).clamp(-self.ceiling, self.ceiling);
+            *right =
+                (gain * self.buffer_right[self.buffer_index]).clamp(-self.ceiling, self.ceiling);
+        }
+    }
+
+    // Gets the peak level around the last sample of the history,
+    // including the peak between it and the sample before.
+    fn get_true_peak(history: &[f32; 3], next: f32) -> f32 {
+        let [x0, x1, x2] = *history;
+        let mut peak = x2.abs();
+        for t in [0.25_f32, 0.5_f32, 0.75_f32] {
+            // Catmull-Rom interpolation between x1 and x2.
+            let y = 0.5_f32
+                * (2_f32 * x1
+                    + (x2 - x0) * t
+                    + (2_f32 * x0 - 5_f32 * x1 + 4_f32 * x2 - next) * t * t
+                    + (3_f32 * x1 - x0 - 3_f32 * x2 + next) * t * t * t);
+            peak = peak.max(y.abs());
+        }
+        peak
+    }
+
+    fn mute(&mut self) {
+        self.buffer_left.fill(0_f32);
+        self.buffer_right.fill(0_f32);
+        self.required_gain.fill(1_f32);
+        self.history_left = [0_f32; 3];
+        self.history_right = [0_f32; 3];
+        self.gain = 1_f32;
+    }
+}
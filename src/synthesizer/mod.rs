@@ -1,13 +1,32 @@
 pub mod voice;
 
 mod chorus;
-use core::cmp;
+use core::{cmp, mem};
 use std::sync::Arc;
 
-use chorus::*;
+pub use chorus::*;
 
 mod reverb;
-use reverb::*;
+pub use reverb::*;
+
+mod master;
+pub use master::*;
+
+mod insertion;
+pub use insertion::*;
+
+mod multi_output;
+pub use multi_output::*;
+
+mod realtime;
+pub use realtime::*;
+
+mod quantizer;
+pub use quantizer::Dither;
+pub(crate) use quantizer::Quantizer;
+
+mod sysex;
+use sysex::*;
 
 mod settings;
 pub use settings::*;
@@ -17,19 +36,41 @@ pub use error::*;
 
 mod array_math;
 use array_math::*;
+#[cfg(test)]
+pub(crate) use array_math::{Kernel, Ramp};
 
 mod loop_mode;
 pub use loop_mode::*;
 
+mod voice_stealing;
+pub use voice_stealing::*;
+
+mod filter;
+pub use filter::*;
+
+mod envelope_curve;
+pub use envelope_curve::*;
+
 mod channel;
 use channel::*;
 
-use crate::{prelude::*, utils};
+mod mpe;
+pub use mpe::*;
+
+use crate::prelude::*;
 use bevy_platform::{collections::HashMap, prelude::*};
 use midix::prelude::ChannelVoiceMessage;
-use voice::{RegionPair, Voice};
+use voice::{RegionPair, Voice, VoiceCollection};
 
 /// An instance of the SoundFont synthesizer.
+///
+/// # Remarks
+///
+/// The voices are allocated up front for the maximum polyphony,
+/// so the channel messages and the rendering do not allocate memory after construction.
+/// This makes them safe to call from a real-time audio thread.
+/// The exceptions are noted on the methods that allocate,
+/// such as [`Synthesizer::set_sound_font`] and the parallel rendering.
 pub struct Synthesizer {
     pub(crate) sound_font: Arc<SoundFont>,
     pub(crate) sample_rate: i32,
@@ -38,27 +79,43 @@ pub struct Synthesizer {
 
     settings: SynthesizerSettings,
 
-    preset_lookup: HashMap<i32, usize>,
-    default_preset: usize,
+    preset_lookup: PresetLookup,
 
     channels: Vec<SynthChannel>,
 
-    voices: Vec<Voice>,
+    mpe: MpeConfiguration,
+
+    voices: VoiceCollection,
 
     block_left: Vec<f32>,
     block_right: Vec<f32>,
 
-    inverse_block_size: f32,
-
+    // The length of the current block, which is shorter than the block size
+    // when a sequencer splits the block at an event.
+    block_length: usize,
+    next_block_length: usize,
     block_read: usize,
 
     master_volume: f32,
 
-    effects: Option<Effects>,
+    reverb_params: ReverbParams,
+    chorus_params: ChorusParams,
+
+    // The effects are allocated even when disabled, so that enabling them does not allocate.
+    reverb: ReverbEffect,
+    chorus: ChorusEffect,
+    master_chain: Option<MasterChain>,
+
+    gs_insertion: InsertionSysExState,
+    xg_insertion: InsertionSysExState,
+
+    stems: Option<Stems>,
+    quantizer: Quantizer,
 }
 
 impl Synthesizer {
     /// The number of channels.
+    const CHANNEL_COUNT: usize = 16;
     /// The percussion channel.
     pub const PERCUSSION_CHANNEL: usize = 9;
 
@@ -73,51 +130,30 @@ impl Synthesizer {
         settings: &SynthesizerSettings,
     ) -> Result<Self, SynthesizerError> {
         settings.validate()?;
+        let settings = &settings.resolve_deprecated();
 
-        let mut preset_lookup: HashMap<i32, usize> = HashMap::new();
-
-        let mut min_preset_id = i32::MAX;
-        let mut default_preset: usize = 0;
-
-        sound_font
-            .presets
-            .iter()
-            .enumerate()
-            .for_each(|(i, preset)| {
-                // The preset ID is Int32, where the upper 16 bits represent the bank number
-                // and the lower 16 bits represent the patch number.
-                // This ID is used to search for presets by the combination of bank number
-                // and patch number.
-                let preset_id = (preset.bank_number << 16) | preset.patch_number;
-                preset_lookup.insert(preset_id, i);
-
-                // The preset with the minimum ID number will be default.
-                // If the SoundFont is GM compatible, the piano will be chosen.
-                if preset_id < min_preset_id {
-                    default_preset = i;
-                    min_preset_id = preset_id;
-                }
-            });
+        let preset_lookup = PresetLookup::new(&sound_font);
 
-        const CHANNEL_COUNT: usize = 16;
-        let channels: Vec<SynthChannel> = (0..CHANNEL_COUNT)
-            .map(|i| SynthChannel::new(i == Synthesizer::PERCUSSION_CHANNEL))
+        let channels: Vec<SynthChannel> = (0..Synthesizer::CHANNEL_COUNT)
+            .map(|i| {
+                let mut channel = SynthChannel::new(i == Synthesizer::PERCUSSION_CHANNEL);
+                channel.idle_insertion_effect = Some(InsertionEffect::new(settings));
+                channel
+            })
             .collect();
 
         let block_left: Vec<f32> = vec![0_f32; settings.block_size];
         let block_right: Vec<f32> = vec![0_f32; settings.block_size];
 
-        let inverse_block_size = 1_f32 / settings.block_size as f32;
-
         let block_read = settings.block_size;
 
         let master_volume = 0.5_f32;
 
-        let effects = if settings.enable_reverb_and_chorus {
-            Some(Effects::new(settings))
-        } else {
-            None
-        };
+        let reverb_params = ReverbParams::default();
+        let chorus_params = ChorusParams::default();
+
+        let reverb = ReverbEffect::new(settings, &reverb_params);
+        let chorus = ChorusEffect::new(settings, &chorus_params);
 
         Ok(Self {
             sound_font,
@@ -125,16 +161,27 @@ impl Synthesizer {
             block_size: settings.block_size,
             maximum_polyphony: settings.maximum_polyphony,
             preset_lookup,
-            default_preset,
             channels,
+            mpe: MpeConfiguration::default(),
             settings: *settings,
-            voices: Vec::with_capacity(settings.maximum_polyphony),
+            voices: VoiceCollection::new(settings),
             block_left,
             block_right,
-            inverse_block_size,
+            block_length: settings.block_size,
+            next_block_length: settings.block_size,
             block_read,
             master_volume,
-            effects,
+            reverb_params,
+            chorus_params,
+            reverb,
+            chorus,
+            master_chain: None,
+            gs_insertion: InsertionSysExState::new(),
+            xg_insertion: InsertionSysExState::new(),
+            stems: settings
+                .enable_multi_output
+                .then(|| Stems::new(Synthesizer::CHANNEL_COUNT, settings.block_size)),
+            quantizer: Quantizer::new(2),
         })
     }
 
@@ -167,8 +214,14 @@ impl Synthesizer {
                 0x00 => channel_info.set_bank(data2), // Bank Selection
                 0x01 => channel_info.set_modulation_coarse(data2), // Modulation Coarse
                 0x21 => channel_info.set_modulation_fine(data2), // Modulation Fine
-                0x06 => channel_info.data_entry_coarse(data2), // Data Entry Coarse
-                0x26 => channel_info.data_entry_fine(data2), // Data Entry Fine
+                0x06 => { // Data Entry Coarse
+                    channel_info.data_entry_coarse(data2);
+                    self.mpe_data_entry(channel, data2);
+                }
+                0x26 => { // Data Entry Fine
+                    channel_info.data_entry_fine(data2);
+                    self.sync_mpe_pitch_bend_range(channel);
+                }
                 0x07 => channel_info.set_volume_coarse(data2), // Channel Volume Coarse
                 0x27 => channel_info.set_volume_fine(data2), // Channel Volume Fine
                 0x0A => channel_info.set_pan_coarse(data2), // Pan Coarse
@@ -176,12 +229,18 @@ impl Synthesizer {
                 0x0B => channel_info.set_expression_coarse(data2), // Expression Coarse
                 0x2B => channel_info.set_expression_fine(data2), // Expression Fine
                 0x40 => channel_info.set_hold_pedal(data2), // Hold Pedal
+                0x47 => channel_info.set_resonance(data2), // Resonance
+                0x48 => channel_info.set_release_time(data2), // Release Time
+                0x49 => channel_info.set_attack_time(data2), // Attack Time
+                0x4A => channel_info.set_brightness(data2), // Brightness
+                0x4B => channel_info.set_decay_time(data2), // Decay Time
+                0x4C => channel_info.set_vibrato_rate(data2), // Vibrato Rate
+                0x4D => channel_info.set_vibrato_depth(data2), // Vibrato Depth
+                0x4E => channel_info.set_vibrato_delay(data2), // Vibrato Delay
                 0x5B => channel_info.set_reverb_send(data2), // Reverb Send
                 0x5D => channel_info.set_chorus_send(data2), // Chorus Send
-                //Note, this used to not use data 2
-                0x63 => channel_info.set_nrpn_coarse(), // NRPN Coarse
-                //Note: this used to not use data 2
-                0x62 => channel_info.set_nrpn_fine(), // NRPN Fine
+                0x63 => channel_info.set_nrpn_coarse(data2), // NRPN Coarse
+                0x62 => channel_info.set_nrpn_fine(data2), // NRPN Fine
                 0x65 => channel_info.set_rpn_coarse(data2), // RPN Coarse
                 0x64 => channel_info.set_rpn_fine(data2), // RPN Fine
 
@@ -191,11 +250,189 @@ impl Synthesizer {
                 _ => (),
             },
             0xC0 => channel_info.set_patch(data1), // Program Change
+            0xD0 => channel_info.set_channel_pressure(data1), // Channel Pressure
             0xE0 => channel_info.set_pitch_bend(data1, data2), // Pitch Bend
             _ => (),
         }
     }
 
+    /// Processes a system exclusive message.
+    ///
+    /// The Roland GS and Yamaha XG parameter changes for the effects are supported.
+    /// Other messages are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The message. The leading `0xF0` and the trailing `0xF7` are optional.
+    ///
+    /// # Remarks
+    ///
+    /// This does not allocate memory, even when it changes the insertion effect type,
+    /// since the insertion effect of each channel is allocated at construction.
+    pub fn process_sysex(&mut self, data: &[u8]) {
+        if let Some(message) = GsDataSet::parse(data) {
+            for (address, value) in message.parameters() {
+                self.set_gs_parameter(address, value);
+            }
+        } else if let Some(message) = XgParameterChange::parse(data) {
+            for (address, value) in message.parameters() {
+                self.set_xg_parameter(address, value);
+            }
+        }
+    }
+
+    fn set_gs_parameter(&mut self, address: u32, value: u8) {
+        if (GS_CHORUS_MACRO..=GS_CHORUS_DEPTH).contains(&address) {
+            self.set_gs_chorus_parameter(address, value);
+            return;
+        }
+
+        if (GS_INSERTION_TYPE_MSB..=GS_INSERTION_PARAMETER_20).contains(&address) {
+            self.set_gs_insertion_parameter(address, value);
+            return;
+        }
+
+        if let Some((channel, GS_PART_REVERB_SEND)) = gs_part_parameter(address) {
+            self.channels[channel as usize].set_reverb_send(value);
+            return;
+        }
+
+        if let Some((channel, GS_PART_EFX_ASSIGN)) = gs_part_efx_parameter(address) {
+            let mask = 1_u16 << channel;
+            if value == 0 {
+                self.gs_insertion.channels &= !mask;
+                self.set_insertion_effect(channel, None);
+            } else {
+                self.gs_insertion.channels |= mask;
+                self.set_insertion_effect(channel, self.gs_insertion.params);
+            }
+            return;
+        }
+
+        let mut reverb_params = self.reverb_params;
+        match address {
+            GS_REVERB_MACRO | GS_REVERB_CHARACTER => {
+                if let Some(reverb_type) = ReverbType::from_gs(value) {
+                    reverb_params = reverb_type.get_params();
+                }
+            }
+            GS_REVERB_PRE_LPF => reverb_params.damping = value.min(7) as f32 / 7_f32,
+            GS_REVERB_LEVEL => reverb_params.level = value as f32 / 64_f32,
+            GS_REVERB_TIME => reverb_params.room_size = value as f32 / 127_f32,
+            GS_REVERB_DELAY_FEEDBACK => reverb_params.delay_feedback = value as f32 / 127_f32,
+            GS_REVERB_PRE_DELAY_TIME => reverb_params.pre_delay = value as f32 / 1000_f32,
+            _ => return,
+        }
+        self.set_reverb_params(reverb_params);
+    }
+
+    // Only the type, the main parameters of some types and the level are supported.
+    fn set_gs_insertion_parameter(&mut self, address: u32, value: u8) {
+        let state = &mut self.gs_insertion;
+        let x = value as f32 / 127_f32;
+        match address {
+            GS_INSERTION_TYPE_MSB | GS_INSERTION_TYPE_LSB => {
+                state.effect_type[(address - GS_INSERTION_TYPE_MSB) as usize] = value;
+                state.params =
+                    InsertionEffectType::from_gs(state.effect_type[0], state.effect_type[1])
+                        .map(|effect_type| effect_type.get_params());
+            }
+            _ => {
+                let Some(params) = state.params.as_mut() else {
+                    return;
+                };
+                let rate = 0.05_f32 + 0.05_f32 * value as f32;
+                match (params.effect_type, address - GS_INSERTION_PARAMETER_1 + 1) {
+                    (InsertionEffectType::Overdrive | InsertionEffectType::Distortion, 1) => {
+                        params.drive = x
+                    }
+                    (InsertionEffectType::Phaser, 2) => params.rate = rate,
+                    (InsertionEffectType::Phaser, 3) => params.depth = x,
+                    (InsertionEffectType::Phaser, 4) => params.feedback = 0.95_f32 * x,
+                    (InsertionEffectType::Phaser, 5) => params.mix = x,
+                    (InsertionEffectType::Tremolo, 2) => params.rate = rate,
+                    (InsertionEffectType::Tremolo, 3) => params.depth = x,
+                    (_, 20) => params.level = x,
+                    _ => return,
+                }
+            }
+        }
+
+        let (params, channels) = (state.params, state.channels);
+        self.set_insertion_effect_channels(params, channels);
+    }
+
+    // Only the type, the drive and the part assignment of the insertion block 1 are supported.
+    fn set_xg_parameter(&mut self, address: u32, value: u8) {
+        let state = &mut self.xg_insertion;
+        match address {
+            XG_INSERTION_TYPE_MSB | XG_INSERTION_TYPE_LSB => {
+                state.effect_type[(address - XG_INSERTION_TYPE_MSB) as usize] = value;
+                state.params =
+                    InsertionEffectType::from_xg(state.effect_type[0], state.effect_type[1])
+                        .map(|effect_type| effect_type.get_params());
+            }
+            XG_INSERTION_PARAMETER_1 => match state.params.as_mut() {
+                Some(params)
+                    if matches!(
+                        params.effect_type,
+                        InsertionEffectType::Overdrive | InsertionEffectType::Distortion
+                    ) =>
+                {
+                    params.drive = value as f32 / 127_f32
+                }
+                _ => return,
+            },
+            XG_INSERTION_PART => {
+                let channels = if (value as usize) < self.channels.len() {
+                    1_u16 << value
+                } else {
+                    0
+                };
+                let previous = state.channels & !channels;
+                state.channels = channels;
+                self.set_insertion_effect_channels(None, previous);
+            }
+            _ => return,
+        }
+
+        let (params, channels) = (self.xg_insertion.params, self.xg_insertion.channels);
+        self.set_insertion_effect_channels(params, channels);
+    }
+
+    fn set_insertion_effect_channels(
+        &mut self,
+        params: Option<InsertionEffectParams>,
+        channels: u16,
+    ) {
+        for channel in 0..self.channels.len() as u8 {
+            if channels & (1 << channel) != 0 {
+                self.set_insertion_effect(channel, params);
+            }
+        }
+    }
+
+    fn set_gs_chorus_parameter(&mut self, address: u32, value: u8) {
+        let mut chorus_params = self.chorus_params;
+        let x = value as f64 / 127.0;
+        match address {
+            GS_CHORUS_MACRO => {
+                if let Some(chorus_type) = ChorusType::from_gs(value) {
+                    chorus_params = chorus_type.get_params();
+                }
+            }
+            GS_CHORUS_LEVEL => chorus_params.level = value as f32 / 64_f32,
+            GS_CHORUS_FEEDBACK => chorus_params.feedback = value as f32 / 128_f32,
+            // Approximates the GS delay curve of 0.1 ms to 100 ms.
+            GS_CHORUS_DELAY => chorus_params.delay = 0.0001 + 0.0999 * x * x,
+            GS_CHORUS_RATE => chorus_params.rate = 0.05 + 0.05 * value as f64,
+            GS_CHORUS_DEPTH => chorus_params.depth = Chorus::MAXIMUM_DEPTH * x,
+            // The pre-LPF is not supported.
+            _ => return,
+        }
+        self.set_chorus_params(chorus_params);
+    }
+
     /// Stops a note.
     ///
     /// # Arguments
@@ -222,6 +459,11 @@ impl Synthesizer {
     /// * `key` - The key of the note.
     /// * `velocity` - The velocity of the note.
     pub fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        self.note_on_at(channel, key, velocity, 0);
+    }
+
+    // Starts a note at an offset within the next block.
+    pub(crate) fn note_on_at(&mut self, channel: u8, key: u8, velocity: u8, offset: usize) {
         if velocity == 0 {
             self.note_off(channel, key);
             return;
@@ -231,12 +473,21 @@ impl Synthesizer {
             return;
         }
 
-        let channel_info = &self.channels[channel as usize];
+        if let Some(limit) = self.channels[channel as usize].get_key_polyphony() {
+            self.voices.limit_key(channel, key, limit);
+        }
+
+        // The notes of an MPE member channel use the program and the sound controllers of the zone.
+        let channel_info = match self.channels[channel as usize].get_mpe_manager() {
+            Some(manager) => &self.channels[manager as usize],
+            None => &self.channels[channel as usize],
+        };
+        self.voices.begin_note(channel_info.get_sound_controllers());
 
         let preset_id = ((channel_info.get_bank_number() as i32) << 16)
             | channel_info.get_patch_number() as i32;
 
-        let preset = match self.preset_lookup.get(&preset_id) {
+        let preset = match self.preset_lookup.presets.get(&preset_id) {
             Some(value) => *value,
             None => {
                 // Try fallback to the GM sound set.
@@ -251,10 +502,10 @@ impl Synthesizer {
                 };
 
                 // If no corresponding preset was found. Use the default one...
-                if let Some(value) = self.preset_lookup.get(&gm_preset_id) {
+                if let Some(value) = self.preset_lookup.presets.get(&gm_preset_id) {
                     *value
                 } else {
-                    self.default_preset
+                    self.preset_lookup.default_preset
                 }
             }
         };
@@ -271,57 +522,63 @@ impl Synthesizer {
                         // If found, reuse it to avoid playing multiple voices with the same class at a time.
                         let exclusive_class = instrument_region.get_exclusive_class();
 
-                        if exclusive_class != 0 {
-                            for voice in self.voices.iter_mut() {
-                                if voice.exclusive_class == exclusive_class
+                        if exclusive_class != 0
+                            && let Some(index) = self.voices.iter().position(|voice| {
+                                !voice.is_dying()
+                                    && voice.exclusive_class == exclusive_class
                                     && voice.channel == channel
-                                {
-                                    //this is identical to what existed before. Instant drop.
-                                    *voice = Voice::new(
-                                        &self.settings,
-                                        &region_pair,
-                                        channel,
-                                        key,
-                                        velocity,
-                                    );
-                                    return;
-                                }
-                            }
+                            })
+                        {
+                            // The old voice is choked with a short fade.
+                            self.voices.steal(
+                                index,
+                                &self.settings,
+                                &region_pair,
+                                channel,
+                                key,
+                                velocity,
+                                offset,
+                            );
+                            return;
                         }
 
-                        // Check if we've reached the maximum polyphony limit
-                        if self.voices.len() >= self.maximum_polyphony {
-                            // Find the voice with the lowest priority to replace
-                            let mut candidate = 0;
-                            let mut lowest_priority = f32::MAX;
-
-                            for (i, voice) in self.voices.iter().enumerate() {
-                                let priority = voice.get_priority();
-                                if priority < lowest_priority {
-                                    lowest_priority = priority;
-                                    candidate = i;
-                                } else if priority == lowest_priority {
-                                    // Same priority - the older one should be more suitable for reuse
-                                    if voice.get_voice_length()
-                                        > self.voices[candidate].get_voice_length()
-                                    {
-                                        candidate = i;
-                                    }
-                                }
-                            }
-
-                            // Replace the voice with lowest priority
-                            self.voices[candidate] =
-                                Voice::new(&self.settings, &region_pair, channel, key, velocity);
+                        // Check if we've reached the polyphony limit of the channel,
+                        // and then the maximum polyphony limit
+                        let channel_full = self.channels[channel as usize]
+                            .get_polyphony()
+                            .is_some_and(|limit| {
+                                self.voices.get_channel_playing_count(channel) >= limit
+                            });
+                        if channel_full || self.voices.get_playing_count() >= self.maximum_polyphony
+                        {
+                            let candidate = self.settings.voice_stealing_policy.find_candidate(
+                                &self.voices,
+                                &self.channels,
+                                channel,
+                                key,
+                                channel_full,
+                            );
+
+                            // Replace the voice chosen by the stealing policy
+                            self.voices.steal(
+                                candidate,
+                                &self.settings,
+                                &region_pair,
+                                channel,
+                                key,
+                                velocity,
+                                offset,
+                            );
                         } else {
                             // We have room for a new voice
-                            self.voices.push(Voice::new(
+                            self.voices.start(
                                 &self.settings,
                                 &region_pair,
                                 channel,
                                 key,
                                 velocity,
-                            ));
+                                offset,
+                            );
                         }
                     }
                 }
@@ -352,7 +609,7 @@ impl Synthesizer {
     /// * `immediate` - If `true`, notes will stop immediately without the release sound.
     pub fn note_off_all_channel(&mut self, channel: u8, immediate: bool) {
         if immediate {
-            self.voices.retain(|voice| voice.channel != channel);
+            self.voices.retain_mut(|voice| voice.channel != channel);
         } else {
             self.voices.iter_mut().for_each(|voice| {
                 if voice.channel == channel {
@@ -382,22 +639,268 @@ impl Synthesizer {
         self.channels[channel as usize].reset_all_controllers();
     }
 
+    /// Sets the priority of a channel for voice stealing.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `priority` - The priority of the channel, from `0` to `127`. The default is `64`.
+    ///
+    /// # Remarks
+    ///
+    /// The voices of the channels with a lower priority are replaced first
+    /// when [`VoiceStealingPolicy::LowestChannelPriority`] is used.
+    /// The priority is kept when the synthesizer is reset.
+    pub fn set_channel_priority(&mut self, channel: u8, priority: u8) {
+        if let Some(channel) = self.channels.get_mut(channel as usize) {
+            channel.set_priority(priority.min(127));
+        }
+    }
+
+    /// Gets the priority of a channel for voice stealing.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_channel_priority(&self, channel: u8) -> u8 {
+        self.channels
+            .get(channel as usize)
+            .map(|channel| channel.get_priority())
+            .unwrap_or_default()
+    }
+
+    /// Sets the maximum number of voices of a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `limit` - The maximum number of voices, or `None` for no limit other than the maximum polyphony.
+    ///
+    /// # Remarks
+    ///
+    /// When the channel reaches the limit, a new note replaces a voice of the same channel
+    /// chosen by the voice stealing policy. The limit is at least `1`, and it is kept when the synthesizer is reset.
+    pub fn set_channel_polyphony(&mut self, channel: u8, limit: Option<usize>) {
+        if let Some(channel) = self.channels.get_mut(channel as usize) {
+            channel.set_polyphony(limit.map(|limit| limit.max(1)));
+        }
+    }
+
+    /// Gets the maximum number of voices of a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_channel_polyphony(&self, channel: u8) -> Option<usize> {
+        self.channels
+            .get(channel as usize)
+            .and_then(|channel| channel.get_polyphony())
+    }
+
+    /// Sets the maximum number of notes of the same key sounding at a time on a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `limit` - The maximum number of notes per key, or `None` to let repeated notes stack without a limit.
+    ///
+    /// # Remarks
+    ///
+    /// When a key is played again at the limit, the oldest note of the key stops with a short fade.
+    /// A limit of `1` retriggers the key, as the single assign mode of hardware GM modules does.
+    /// The voices of a layered preset count as one note.
+    /// The limit is at least `1`, and it is kept when the synthesizer is reset.
+    pub fn set_key_polyphony(&mut self, channel: u8, limit: Option<usize>) {
+        if let Some(channel) = self.channels.get_mut(channel as usize) {
+            channel.set_key_polyphony(limit.map(|limit| limit.max(1)));
+        }
+    }
+
+    /// Gets the maximum number of notes of the same key sounding at a time on a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_key_polyphony(&self, channel: u8) -> Option<usize> {
+        self.channels
+            .get(channel as usize)
+            .and_then(|channel| channel.get_key_polyphony())
+    }
+
+    /// Sets the response of the filter of a channel, overriding the filter of the regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `filter_type` - The response of the filter, or `None` to use the filter of each region.
+    ///
+    /// # Remarks
+    ///
+    /// The filter applies to the notes started afterwards.
+    /// It can also be selected with NRPN MSB `1` LSB `0x28`, whose data entry values
+    /// `0` to `4` select the low-pass, high-pass, band-pass, notch and peak filters.
+    /// The high-pass and band-pass filters are bypassed on the regions which leave the cutoff
+    /// at its default and do not modulate it, unless the brightness (CC74) moves the cutoff.
+    /// The filter is cleared when the synthesizer is reset.
+    pub fn set_channel_filter_type(&mut self, channel: u8, filter_type: Option<FilterType>) {
+        if let Some(channel) = self.channels.get_mut(channel as usize) {
+            channel.set_filter_type(filter_type);
+        }
+    }
+
+    /// Gets the response of the filter of a channel, if it overrides the filter of the regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_channel_filter_type(&self, channel: u8) -> Option<FilterType> {
+        self.channels
+            .get(channel as usize)
+            .and_then(|channel| channel.get_filter_type())
+    }
+
+    /// Sets the steepness of the filter of a channel, overriding the filter of the regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `slope` - The steepness of the filter, or `None` to use the filter of each region.
+    ///
+    /// # Remarks
+    ///
+    /// The slope applies to the notes started afterwards.
+    /// It can also be selected with NRPN MSB `1` LSB `0x29`, whose data entry values
+    /// `0` and `1` select the 2-pole and 4-pole filters.
+    /// The slope is cleared when the synthesizer is reset.
+    pub fn set_channel_filter_slope(&mut self, channel: u8, slope: Option<FilterSlope>) {
+        if let Some(channel) = self.channels.get_mut(channel as usize) {
+            channel.set_filter_slope(slope);
+        }
+    }
+
+    /// Gets the steepness of the filter of a channel, if it overrides the filter of the regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_channel_filter_slope(&self, channel: u8) -> Option<FilterSlope> {
+        self.channels
+            .get(channel as usize)
+            .and_then(|channel| channel.get_filter_slope())
+    }
+
+    /// Configures an MPE zone, as the MPE Configuration Message (RPN 6) does.
+    ///
+    /// The member channels of the zone get a pitch bend range of 48 semitones
+    /// and the manager channel gets a pitch bend range of 2 semitones.
+    /// If the zone overlaps with the other zone, the other zone is shrunk.
+    /// The channels which are no longer member channels get the default pitch bend range of 2 semitones.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - The zone to be configured.
+    /// * `member_channel_count` - The number of member channels. `0` disables the zone.
+    pub fn set_mpe_zone(&mut self, zone: MpeZone, member_channel_count: u8) {
+        let members: [bool; Synthesizer::CHANNEL_COUNT] =
+            core::array::from_fn(|i| self.mpe.get_manager(i as u8).is_some());
+        self.mpe
+            .set_member_channel_count(zone, member_channel_count);
+
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            let was_member = members[i];
+            let i = i as u8;
+            let manager = self.mpe.get_manager(i);
+            if was_member && manager.is_none() {
+                channel.set_pitch_bend_range(MpeConfiguration::DEFAULT_PITCH_BEND_RANGE);
+            } else if self.mpe.get_zone(i) == Some(zone) {
+                let range = if manager.is_some() {
+                    MpeConfiguration::MEMBER_PITCH_BEND_RANGE
+                } else {
+                    MpeConfiguration::MANAGER_PITCH_BEND_RANGE
+                };
+                channel.set_pitch_bend_range(range);
+            }
+            channel.set_mpe_manager(manager);
+        }
+    }
+
+    /// Gets the number of member channels of an MPE zone.
+    /// `0` means the zone is disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - The zone to be queried.
+    pub fn get_mpe_member_channel_count(&self, zone: MpeZone) -> u8 {
+        self.mpe.get_member_channel_count(zone)
+    }
+
+    fn mpe_data_entry(&mut self, channel: u8, value: u8) {
+        if self.channels[channel as usize].get_rpn() == Some(MpeConfiguration::RPN) {
+            // The configuration message is only valid on the manager channels.
+            if let Some(zone) = MpeZone::from_manager_channel(channel) {
+                self.set_mpe_zone(zone, value);
+            }
+        }
+
+        self.sync_mpe_pitch_bend_range(channel);
+    }
+
+    // A pitch bend range sent to any member channel applies to all the member channels of the zone.
+    fn sync_mpe_pitch_bend_range(&mut self, channel: u8) {
+        let Some(manager) = self.channels[channel as usize].get_mpe_manager() else {
+            return;
+        };
+
+        if self.channels[channel as usize].get_rpn() != Some(0) {
+            return;
+        }
+
+        let range = self.channels[channel as usize].get_pitch_bend_range_data();
+        for other in self.channels.iter_mut() {
+            if other.get_mpe_manager() == Some(manager) {
+                other.set_pitch_bend_range_data(range);
+            }
+        }
+    }
+
     /// Resets the synthesizer.
     pub fn reset(&mut self) {
         self.voices.clear();
+        self.mpe = MpeConfiguration::default();
 
         for channel in &mut self.channels {
             channel.reset();
         }
 
-        if let Some(effects) = self.effects.as_mut() {
-            effects.reverb.mute();
-            effects.chorus.mute();
+        self.reverb.reverb.mute();
+        self.chorus.chorus.mute();
+
+        if let Some(master_chain) = self.master_chain.as_mut() {
+            master_chain.mute();
+        }
+
+        for channel in &mut self.channels {
+            if let Some(effect) = channel.insertion_effect.as_mut() {
+                effect.mute();
+            }
         }
 
+        self.quantizer.reset();
+
+        self.block_length = self.block_size;
+        self.next_block_length = self.block_size;
         self.block_read = self.block_size;
     }
 
+    // Resets the channels without stopping the notes being played.
+    pub(crate) fn reset_channels(&mut self) {
+        self.mpe = MpeConfiguration::default();
+
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+    }
+
     /// Renders the waveform.
     ///
     /// # Arguments
@@ -413,157 +916,395 @@ impl Synthesizer {
             panic!("The output buffers for the left and right must be the same length.");
         }
 
-        let left_length = left.len();
+        self.render_frames(left.len(), |wrote, block_left, block_right| {
+            let rem = block_left.len();
+            left[wrote..wrote + rem].copy_from_slice(block_left);
+            right[wrote..wrote + rem].copy_from_slice(block_right);
+        });
+    }
 
-        let mut wrote = 0;
-        while wrote < left_length {
-            if self.block_read == self.block_size {
-                self.render_block();
-                self.block_read = 0;
-            }
+    /// Renders the waveform, returning an error instead of panicking
+    /// if the output buffers are not the same length.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The buffer of the left channel to store the rendered waveform.
+    /// * `right` - The buffer of the right channel to store the rendered waveform.
+    pub fn try_render(&mut self, left: &mut [f32], right: &mut [f32]) -> Result<(), RenderError> {
+        if left.len() != right.len() {
+            return Err(RenderError::BufferLengthMismatch(left.len(), right.len()));
+        }
 
-            let src_rem = self.block_size - self.block_read;
-            let dst_rem = left_length - wrote;
-            let rem = cmp::min(src_rem, dst_rem);
+        self.render(left, right);
+
+        Ok(())
+    }
 
-            for t in 0..rem {
-                left[wrote + t] = self.block_left[self.block_read + t];
-                right[wrote + t] = self.block_right[self.block_read + t];
+    /// Renders the waveform as interleaved stereo samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to store the rendered waveform, alternating left and right.
+    pub fn render_interleaved(&mut self, buffer: &mut [f32]) -> Result<(), RenderError> {
+        let frames = Synthesizer::get_interleaved_frames(buffer.len())?;
+
+        self.render_frames(frames, |wrote, block_left, block_right| {
+            let destination = buffer[2 * wrote..].chunks_exact_mut(2);
+            for (frame, (left, right)) in destination.zip(block_left.iter().zip(block_right)) {
+                frame[0] = *left;
+                frame[1] = *right;
             }
+        });
 
-            self.block_read += rem;
-            wrote += rem;
-        }
+        Ok(())
     }
 
-    fn render_block(&mut self) {
+    /// Renders the waveform as interleaved stereo 16-bit samples.
+    /// The samples are dithered as specified by `set_dither`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to store the rendered waveform, alternating left and right.
+    pub fn render_interleaved_i16(&mut self, buffer: &mut [i16]) -> Result<(), RenderError> {
+        self.render_quantized(buffer, 16, |x| x as i16)
+    }
+
+    /// Renders the waveform as interleaved stereo 24-bit samples,
+    /// stored in the lower 24 bits of `i32` values.
+    /// The samples are dithered as specified by `set_dither`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to store the rendered waveform, alternating left and right.
+    pub fn render_interleaved_i24(&mut self, buffer: &mut [i32]) -> Result<(), RenderError> {
+        self.render_quantized(buffer, 24, |x| x)
+    }
+
+    /// Renders the waveform as interleaved stereo 32-bit samples.
+    /// The samples are dithered as specified by `set_dither`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to store the rendered waveform, alternating left and right.
+    pub fn render_interleaved_i32(&mut self, buffer: &mut [i32]) -> Result<(), RenderError> {
+        self.render_quantized(buffer, 32, |x| x)
+    }
+
+    fn render_quantized<T>(
+        &mut self,
+        buffer: &mut [T],
+        bits: u32,
+        convert: impl Fn(i32) -> T,
+    ) -> Result<(), RenderError> {
+        let frames = Synthesizer::get_interleaved_frames(buffer.len())?;
+
+        // An empty quantizer takes its place while rendering, which does not allocate.
+        let mut quantizer = mem::replace(&mut self.quantizer, Quantizer::new(0));
+        self.render_frames(frames, |wrote, block_left, block_right| {
+            let destination = buffer[2 * wrote..].chunks_exact_mut(2);
+            for (frame, (left, right)) in destination.zip(block_left.iter().zip(block_right)) {
+                frame[0] = convert(quantizer.quantize(0, *left, bits));
+                frame[1] = convert(quantizer.quantize(1, *right, bits));
+            }
+        });
+        self.quantizer = quantizer;
+
+        Ok(())
+    }
+
+    fn get_interleaved_frames(length: usize) -> Result<usize, RenderError> {
+        if !length.is_multiple_of(2) {
+            return Err(RenderError::InterleavedLengthNotEven(length));
+        }
+
+        Ok(length / 2)
+    }
+
+    // Gets the number of frames left in the current block.
+    // When zero, the next render starts a new block.
+    pub(crate) fn get_block_remaining(&self) -> usize {
+        self.block_length - self.block_read
+    }
+
+    // Gets the length of the next block.
+    pub(crate) fn get_next_block_length(&self) -> usize {
+        self.next_block_length
+    }
+
+    // Shortens the next block, so that the events sent after it take effect at its end.
+    pub(crate) fn set_next_block_length(&mut self, length: usize) {
+        self.next_block_length = length.clamp(1, self.block_size);
+    }
+
+    // Renders the specified number of frames, passing the rendered blocks to `write`
+    // along with the number of frames written before.
+    fn render_frames(&mut self, length: usize, mut write: impl FnMut(usize, &[f32], &[f32])) {
+        let mut wrote = 0;
+        while wrote < length {
+            if self.block_read == self.block_length {
+                self.render_block();
+                self.block_read = 0;
+            }
+
+            let src_rem = self.block_length - self.block_read;
+            let dst_rem = length - wrote;
+            let rem = cmp::min(src_rem, dst_rem);
+
+            let range = self.block_read..self.block_read + rem;
+            write(
+                wrote,
+                &self.block_left[range.clone()],
+                &self.block_right[range],
+            );
+
+            self.block_read += rem;
+            wrote += rem;
+        }
+    }
+
+    /// Renders each MIDI channel to its own stereo buffer pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffers` - The buffers to store the rendered waveform of each channel
+    ///   and optionally the reverb and chorus returns.
+    ///
+    /// # Remarks
+    ///
+    /// This requires `enable_multi_output` in the settings.
+    /// All the buffers must be the same length.
+    /// The sum of the channels and the effect returns is the output of `render`
+    /// without the master chain.
+    pub fn render_multi(&mut self, buffers: &mut MultiOutputBuffers) {
+        if self.stems.is_none() {
+            panic!("Multi-output rendering must be enabled in the settings.");
+        }
+
+        if !buffers.check_length() {
+            panic!("The output buffers must be the same length.");
+        }
+
+        let length = buffers.len();
+
+        let mut wrote = 0;
+        while wrote < length {
+            if self.block_read == self.block_length {
+                self.render_block();
+                self.block_read = 0;
+            }
+
+            let src_rem = self.block_length - self.block_read;
+            let dst_rem = length - wrote;
+            let rem = cmp::min(src_rem, dst_rem);
+
+            if let Some(stems) = self.stems.as_ref() {
+                stems.copy_to(buffers, self.block_read, wrote, rem);
+            }
+
+            self.block_read += rem;
+            wrote += rem;
+        }
+    }
+
+    fn render_block(&mut self) {
+        let length = self.next_block_length;
+        self.block_length = length;
+        self.next_block_length = self.block_size;
+
         // the idea here is that if the voice cannot process, drop it.
         // A voice will not be able to process if it's been killed and is ready for release.
         self.voices
-            .retain_mut(|voice| voice.process(&self.sound_font.wave_data, &self.channels));
+            .process(&self.sound_font.wave_data, &self.channels, length);
 
         self.block_left.fill(0_f32);
         self.block_right.fill(0_f32);
 
-        for voice in self.voices.iter_mut() {
-            let previous_gain_left = self.master_volume * voice.previous_mix_gain_left;
-            let current_gain_left = self.master_volume * voice.current_mix_gain_left;
-            Synthesizer::write_block(
-                previous_gain_left,
-                current_gain_left,
-                &voice.block,
-                &mut self.block_left,
-                self.inverse_block_size,
-            );
-            let previous_gain_right = self.master_volume * voice.previous_mix_gain_right;
-            let current_gain_right = self.master_volume * voice.current_mix_gain_right;
-            Synthesizer::write_block(
-                previous_gain_right,
-                current_gain_right,
-                &voice.block,
-                &mut self.block_right,
-                self.inverse_block_size,
-            );
+        for channel in self.channels.iter_mut() {
+            if let Some(effect) = channel.insertion_effect.as_mut() {
+                effect.block_left.fill(0_f32);
+                effect.block_right.fill(0_f32);
+            }
         }
 
-        if let Some(effects) = self.effects.as_mut() {
-            let chorus = &mut effects.chorus;
-            let chorus_input_left = &mut effects.chorus_input_left[..];
-            let chorus_input_right = &mut effects.chorus_input_right[..];
-            let chorus_output_left = &mut effects.chorus_output_left[..];
-            let chorus_output_right = &mut effects.chorus_output_right[..];
-            chorus_input_left.fill(0_f32);
-            chorus_input_right.fill(0_f32);
-            for voice in self.voices.iter_mut() {
-                let previous_gain_left = voice.previous_chorus_send * voice.previous_mix_gain_left;
-                let current_gain_left = voice.current_chorus_send * voice.current_mix_gain_left;
-                Synthesizer::write_block(
-                    previous_gain_left,
-                    current_gain_left,
-                    &voice.block[..],
-                    chorus_input_left,
-                    self.inverse_block_size,
+        if let Some(stems) = self.stems.as_mut() {
+            stems.clear();
+        }
+
+        self.chorus.input_left.fill(0_f32);
+        self.chorus.input_right.fill(0_f32);
+        self.reverb.input.fill(0_f32);
+
+        // The voices of a channel with an insertion effect only reach the send effects through its sub-mix.
+        let inserted: [bool; Synthesizer::CHANNEL_COUNT] =
+            core::array::from_fn(|i| self.channels[i].insertion_effect.is_some());
+
+        let mix = VoiceMix {
+            dry: DryMix {
+                master_volume: self.master_volume,
+                block_left: &mut self.block_left[..length],
+                block_right: &mut self.block_right[..length],
+                channels: &mut self.channels,
+                stems: self.stems.as_mut(),
+            },
+            chorus: self.settings.enable_chorus.then_some(&mut self.chorus),
+            reverb: self.settings.enable_reverb.then_some(&mut self.reverb),
+            inserted: &inserted,
+            length,
+        };
+        #[cfg(feature = "parallel")]
+        if self.voices.parallel {
+            mix.write_parallel(&self.voices);
+        } else {
+            mix.write(&self.voices);
+        }
+        #[cfg(not(feature = "parallel"))]
+        mix.write(&self.voices);
+
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            if let Some(effect) = channel.insertion_effect.as_mut() {
+                effect.process(length);
+                if let Some(stems) = self.stems.as_mut() {
+                    let [left, right] = &mut stems.channels[i];
+                    ArrayMath::multiply_add(
+                        self.master_volume,
+                        &effect.block_left[..length],
+                        &mut left[..length],
+                    );
+                    ArrayMath::multiply_add(
+                        self.master_volume,
+                        &effect.block_right[..length],
+                        &mut right[..length],
+                    );
+                }
+                ArrayMath::multiply_add(
+                    self.master_volume,
+                    &effect.block_left[..length],
+                    &mut self.block_left[..length],
                 );
-                let previous_gain_right =
-                    voice.previous_chorus_send * voice.previous_mix_gain_right;
-                let current_gain_right = voice.current_chorus_send * voice.current_mix_gain_right;
-                Synthesizer::write_block(
-                    previous_gain_right,
-                    current_gain_right,
-                    &voice.block[..],
-                    chorus_input_right,
-                    self.inverse_block_size,
+                ArrayMath::multiply_add(
+                    self.master_volume,
+                    &effect.block_right[..length],
+                    &mut self.block_right[..length],
                 );
             }
+        }
+
+        if self.settings.enable_chorus {
+            let effect = &mut self.chorus;
+            let chorus = &mut effect.chorus;
+            let chorus_input_left = &mut effect.input_left[..length];
+            let chorus_input_right = &mut effect.input_right[..length];
+            let chorus_output_left = &mut effect.output_left[..length];
+            let chorus_output_right = &mut effect.output_right[..length];
+            for channel in self.channels.iter() {
+                if let Some(insertion) = channel.insertion_effect.as_ref() {
+                    let send = channel.get_chorus_send();
+                    ArrayMath::multiply_add(
+                        send,
+                        &insertion.block_left[..length],
+                        chorus_input_left,
+                    );
+                    ArrayMath::multiply_add(
+                        send,
+                        &insertion.block_right[..length],
+                        chorus_input_right,
+                    );
+                }
+            }
             chorus.process(
                 chorus_input_left,
                 chorus_input_right,
                 chorus_output_left,
                 chorus_output_right,
             );
+            let chorus_gain = self.master_volume * self.chorus_params.level;
             ArrayMath::multiply_add(
-                self.master_volume,
+                chorus_gain,
                 chorus_output_left,
-                &mut self.block_left[..],
+                &mut self.block_left[..length],
             );
             ArrayMath::multiply_add(
-                self.master_volume,
+                chorus_gain,
                 chorus_output_right,
-                &mut self.block_right[..],
+                &mut self.block_right[..length],
             );
+            if let Some(stems) = self.stems.as_mut() {
+                let [left, right] = &mut stems.chorus;
+                ArrayMath::multiply_add(chorus_gain, chorus_output_left, &mut left[..length]);
+                ArrayMath::multiply_add(chorus_gain, chorus_output_right, &mut right[..length]);
+            }
+        }
 
-            let reverb = &mut effects.reverb;
-            let reverb_input = &mut effects.reverb_input[..];
-            let reverb_output_left = &mut effects.reverb_output_left[..];
-            let reverb_output_right = &mut effects.reverb_output_right[..];
-            reverb_input.fill(0_f32);
-            for voice in self.voices.iter_mut() {
-                let previous_gain = reverb.get_input_gain()
-                    * voice.previous_reverb_send
-                    * (voice.previous_mix_gain_left + voice.previous_mix_gain_right);
-                let current_gain = reverb.get_input_gain()
-                    * voice.current_reverb_send
-                    * (voice.current_mix_gain_left + voice.current_mix_gain_right);
-                Synthesizer::write_block(
-                    previous_gain,
-                    current_gain,
-                    &voice.block[..],
-                    &mut reverb_input[..],
-                    self.inverse_block_size,
-                );
+        if self.settings.enable_reverb {
+            let effect = &mut self.reverb;
+            let reverb = &mut effect.reverb;
+            let reverb_input = &mut effect.input[..length];
+            let reverb_output_left = &mut effect.output_left[..length];
+            let reverb_output_right = &mut effect.output_right[..length];
+            for channel in self.channels.iter() {
+                if let Some(insertion) = channel.insertion_effect.as_ref() {
+                    let send = reverb.get_input_gain() * channel.get_reverb_send();
+                    ArrayMath::multiply_add(send, &insertion.block_left[..length], reverb_input);
+                    ArrayMath::multiply_add(send, &insertion.block_right[..length], reverb_input);
+                }
             }
 
             reverb.process(reverb_input, reverb_output_left, reverb_output_right);
             ArrayMath::multiply_add(
                 self.master_volume,
                 reverb_output_left,
-                &mut self.block_left[..],
+                &mut self.block_left[..length],
             );
             ArrayMath::multiply_add(
                 self.master_volume,
                 reverb_output_right,
-                &mut self.block_right[..],
+                &mut self.block_right[..length],
             );
+            if let Some(stems) = self.stems.as_mut() {
+                let [left, right] = &mut stems.reverb;
+                ArrayMath::multiply_add(
+                    self.master_volume,
+                    reverb_output_left,
+                    &mut left[..length],
+                );
+                ArrayMath::multiply_add(
+                    self.master_volume,
+                    reverb_output_right,
+                    &mut right[..length],
+                );
+            }
         }
-    }
 
-    fn write_block(
-        previous_gain: f32,
-        current_gain: f32,
-        source: &[f32],
-        destination: &mut [f32],
-        inverse_block_size: f32,
-    ) {
-        if previous_gain.max(current_gain) < utils::NON_AUDIBLE {
-            return;
+        if let Some(master_chain) = self.master_chain.as_mut() {
+            master_chain.process(
+                &mut self.block_left[..length],
+                &mut self.block_right[..length],
+            );
         }
+    }
 
-        if (current_gain - previous_gain).abs() < utils::NON_AUDIBLE {
-            ArrayMath::multiply_add(current_gain, source, destination);
-        } else {
-            let step = inverse_block_size * (current_gain - previous_gain);
-            ArrayMath::multiply_add_slope(previous_gain, step, source, destination);
-        }
+    /// Sets whether the voices are rendered across the compute task pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value indicating whether the voices are rendered in parallel.
+    ///
+    /// # Remarks
+    ///
+    /// The output is identical either way. Parallel rendering is disabled by default,
+    /// since it allocates while dispatching the work, which a real-time audio thread should avoid.
+    /// Only the rendering of the voices is spread across the worker threads.
+    /// Mixing them is not: the dry mix is a single task alongside the chorus and reverb sends,
+    /// so it does not scale with the threads.
+    #[cfg(feature = "parallel")]
+    pub fn set_parallel(&mut self, value: bool) {
+        self.voices.parallel = value;
+    }
+
+    /// Gets the value indicating whether the voices are rendered across the compute task pool.
+    #[cfg(feature = "parallel")]
+    pub fn get_parallel(&self) -> bool {
+        self.voices.parallel
     }
 
     /// Gets the SoundFont used as the audio source.
@@ -571,6 +1312,37 @@ impl Synthesizer {
         &self.sound_font
     }
 
+    /// Replaces the SoundFont used for synthesis.
+    ///
+    /// # Arguments
+    ///
+    /// * `sound_font` - The new SoundFont instance.
+    ///
+    /// # Remarks
+    ///
+    /// The notes being played are stopped immediately,
+    /// while the programs and controllers of the channels are kept.
+    /// This allocates the preset lookup of the new SoundFont and may drop the previous one,
+    /// so use [`SynthController`] to swap it from another thread while rendering.
+    pub fn set_sound_font(&mut self, sound_font: Arc<SoundFont>) {
+        let preset_lookup = PresetLookup::new(&sound_font);
+        self.replace_sound_font(sound_font, preset_lookup);
+    }
+
+    // Replaces the SoundFont with its preset lookup built in advance,
+    // and returns the previous ones so that the caller decides where they are dropped.
+    pub(crate) fn replace_sound_font(
+        &mut self,
+        sound_font: Arc<SoundFont>,
+        preset_lookup: PresetLookup,
+    ) -> (Arc<SoundFont>, PresetLookup) {
+        self.voices.clear();
+        (
+            mem::replace(&mut self.sound_font, sound_font),
+            mem::replace(&mut self.preset_lookup, preset_lookup),
+        )
+    }
+
     /// Gets the sample rate for synthesis.
     pub fn get_sample_rate(&self) -> i32 {
         self.sample_rate
@@ -586,9 +1358,194 @@ impl Synthesizer {
         self.maximum_polyphony
     }
 
-    /// Gets the value indicating whether reverb and chorus are enabled.
+    /// Gets the number of voices currently playing.
+    ///
+    /// # Remarks
+    ///
+    /// The voices fading out after being stolen or choked are not counted.
+    pub fn get_active_voice_count(&self) -> usize {
+        self.voices.get_playing_count()
+    }
+
+    /// Gets the value indicating whether reverb and chorus are both enabled.
     pub fn get_enable_reverb_and_chorus(&self) -> bool {
-        self.effects.is_some()
+        self.settings.enable_reverb && self.settings.enable_chorus
+    }
+
+    /// Gets the value indicating whether reverb is enabled.
+    pub fn get_enable_reverb(&self) -> bool {
+        self.settings.enable_reverb
+    }
+
+    /// Enables or bypasses the reverb.
+    /// The reverb is allocated at construction, so this does not allocate memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value indicating whether reverb is enabled.
+    pub fn set_enable_reverb(&mut self, value: bool) {
+        // The tail left from before the bypass is not heard when re-enabled.
+        if value && !self.settings.enable_reverb {
+            self.reverb.reverb.mute();
+        }
+
+        self.settings.enable_reverb = value;
+    }
+
+    /// Gets the value indicating whether chorus is enabled.
+    pub fn get_enable_chorus(&self) -> bool {
+        self.settings.enable_chorus
+    }
+
+    /// Enables or bypasses the chorus.
+    /// The chorus is allocated at construction, so this does not allocate memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value indicating whether chorus is enabled.
+    pub fn set_enable_chorus(&mut self, value: bool) {
+        // The tail left from before the bypass is not heard when re-enabled.
+        if value && !self.settings.enable_chorus {
+            self.chorus.chorus.mute();
+        }
+
+        self.settings.enable_chorus = value;
+    }
+
+    /// Gets the parameters of the reverb.
+    pub fn get_reverb_params(&self) -> ReverbParams {
+        self.reverb_params
+    }
+
+    /// Sets the parameters of the reverb.
+    /// The change is smoothed out over a short time to avoid zipper noise.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The new parameters of the reverb.
+    pub fn set_reverb_params(&mut self, params: ReverbParams) {
+        self.reverb_params = params;
+
+        self.reverb.reverb.set_params(&params);
+    }
+
+    /// Sets the parameters of the reverb to those of a GS reverb type.
+    ///
+    /// # Arguments
+    ///
+    /// * `reverb_type` - The reverb type.
+    pub fn set_reverb_type(&mut self, reverb_type: ReverbType) {
+        self.set_reverb_params(reverb_type.get_params());
+    }
+
+    /// Gets the parameters of the chorus.
+    pub fn get_chorus_params(&self) -> ChorusParams {
+        self.chorus_params
+    }
+
+    /// Sets the parameters of the chorus.
+    /// This does not allocate memory, so it can be called while rendering.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The new parameters of the chorus.
+    pub fn set_chorus_params(&mut self, params: ChorusParams) {
+        self.chorus_params = params;
+
+        self.chorus.chorus.set_params(self.sample_rate, &params);
+    }
+
+    /// Sets the parameters of the chorus to those of a GS chorus type.
+    ///
+    /// # Arguments
+    ///
+    /// * `chorus_type` - The chorus type.
+    pub fn set_chorus_type(&mut self, chorus_type: ChorusType) {
+        self.set_chorus_params(chorus_type.get_params());
+    }
+
+    /// Gets the parameters of the insertion effect of a channel,
+    /// or `None` if the channel has no insertion effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be queried.
+    pub fn get_insertion_effect(&self, channel: u8) -> Option<InsertionEffectParams> {
+        self.channels
+            .get(channel as usize)?
+            .insertion_effect
+            .as_ref()
+            .map(|effect| effect.get_params())
+    }
+
+    /// Sets the insertion effect of a channel.
+    /// The insertion effect processes the sub-mix of the channel before the send effects.
+    /// The effect of each channel is allocated up front,
+    /// so this does not allocate memory, even when the effect type changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to be configured.
+    /// * `params` - The parameters of the insertion effect, or `None` to remove it.
+    pub fn set_insertion_effect(&mut self, channel: u8, params: Option<InsertionEffectParams>) {
+        let Some(channel) = self.channels.get_mut(channel as usize) else {
+            return;
+        };
+
+        match params {
+            Some(params) => {
+                if channel.insertion_effect.is_none() {
+                    channel.insertion_effect = channel.idle_insertion_effect.take();
+                    if let Some(effect) = channel.insertion_effect.as_mut() {
+                        effect.mute();
+                    }
+                }
+                channel
+                    .insertion_effect
+                    .get_or_insert_with(|| InsertionEffect::new(&self.settings))
+                    .set_params(&params);
+            }
+            None => {
+                if channel.insertion_effect.is_some() {
+                    channel.idle_insertion_effect = channel.insertion_effect.take();
+                }
+            }
+        }
+    }
+
+    /// Gets the parameters of the master chain, or `None` if the master chain is disabled.
+    pub fn get_master_chain_params(&self) -> Option<MasterChainParams> {
+        self.master_chain.as_ref().map(|chain| chain.get_params())
+    }
+
+    /// Sets the parameters of the master chain, which processes the output after the effects.
+    /// The master chain is disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The new parameters of the master chain, or `None` to disable it.
+    pub fn set_master_chain_params(&mut self, params: Option<MasterChainParams>) {
+        match (params, self.master_chain.as_mut()) {
+            (Some(params), Some(chain)) => chain.set_params(&params),
+            (Some(params), None) => {
+                self.master_chain = Some(MasterChain::new(&self.settings, &params))
+            }
+            (None, _) => self.master_chain = None,
+        }
+    }
+
+    /// Gets the dithering applied by the integer render methods.
+    pub fn get_dither(&self) -> Dither {
+        self.quantizer.get_dither()
+    }
+
+    /// Sets the dithering applied by the integer render methods.
+    ///
+    /// # Arguments
+    ///
+    /// * `dither` - The dithering.
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.quantizer.set_dither(dither);
     }
 
     /// Gets the master volume.
@@ -606,31 +1563,282 @@ impl Synthesizer {
     }
 }
 
-struct Effects {
+struct ReverbEffect {
     reverb: Reverb,
-    reverb_input: Vec<f32>,
-    reverb_output_left: Vec<f32>,
-    reverb_output_right: Vec<f32>,
+    input: Vec<f32>,
+    output_left: Vec<f32>,
+    output_right: Vec<f32>,
+}
 
+impl ReverbEffect {
+    fn new(settings: &SynthesizerSettings, params: &ReverbParams) -> Self {
+        Self {
+            reverb: Reverb::new(settings.sample_rate, params),
+            input: vec![0_f32; settings.block_size],
+            output_left: vec![0_f32; settings.block_size],
+            output_right: vec![0_f32; settings.block_size],
+        }
+    }
+
+    // Gets the target of the voice in the input, which only has the voices of the channels without an insertion effect.
+    fn target(&mut self, voice: &Voice, inserted: &[bool], length: usize) -> MixTarget<'_> {
+        if inserted[voice.channel as usize] {
+            return empty_target();
+        }
+        let input_gain = self.reverb.get_input_gain();
+        let previous_gain = input_gain
+            * voice.previous_reverb_send
+            * (voice.previous_mix_gain_left + voice.previous_mix_gain_right);
+        let current_gain = input_gain
+            * voice.current_reverb_send
+            * (voice.current_mix_gain_left + voice.current_mix_gain_right);
+        Ramp::target(
+            previous_gain,
+            current_gain,
+            1_f32 / length as f32,
+            &mut self.input[..length],
+        )
+    }
+}
+
+struct ChorusEffect {
     chorus: Chorus,
-    chorus_input_left: Vec<f32>,
-    chorus_input_right: Vec<f32>,
-    chorus_output_left: Vec<f32>,
-    chorus_output_right: Vec<f32>,
+    input_left: Vec<f32>,
+    input_right: Vec<f32>,
+    output_left: Vec<f32>,
+    output_right: Vec<f32>,
+}
+
+impl ChorusEffect {
+    fn new(settings: &SynthesizerSettings, params: &ChorusParams) -> Self {
+        Self {
+            chorus: Chorus::new(settings.sample_rate, params),
+            input_left: vec![0_f32; settings.block_size],
+            input_right: vec![0_f32; settings.block_size],
+            output_left: vec![0_f32; settings.block_size],
+            output_right: vec![0_f32; settings.block_size],
+        }
+    }
+
+    // Gets the targets of the voice in the inputs, which only have the voices of the channels without an insertion effect.
+    fn targets(&mut self, voice: &Voice, inserted: &[bool], length: usize) -> [MixTarget<'_>; 2] {
+        if inserted[voice.channel as usize] {
+            return [empty_target(), empty_target()];
+        }
+        let inverse_block_size = 1_f32 / length as f32;
+        [
+            Ramp::target(
+                voice.previous_chorus_send * voice.previous_mix_gain_left,
+                voice.current_chorus_send * voice.current_mix_gain_left,
+                inverse_block_size,
+                &mut self.input_left[..length],
+            ),
+            Ramp::target(
+                voice.previous_chorus_send * voice.previous_mix_gain_right,
+                voice.current_chorus_send * voice.current_mix_gain_right,
+                inverse_block_size,
+                &mut self.input_right[..length],
+            ),
+        ]
+    }
+}
+
+// The buses the voices are mixed into before the effects are processed.
+struct VoiceMix<'a> {
+    dry: DryMix<'a>,
+    chorus: Option<&'a mut ChorusEffect>,
+    reverb: Option<&'a mut ReverbEffect>,
+    inserted: &'a [bool],
+    // The length of the block.
+    length: usize,
+}
+
+impl VoiceMix<'_> {
+    // Mixes each voice into all the buses in a single pass.
+    fn write(self, voices: &[Voice]) {
+        let Self {
+            mut dry,
+            mut chorus,
+            mut reverb,
+            inserted,
+            length,
+        } = self;
+        for voice in voices.iter() {
+            let [dry_left, dry_right, stem_left, stem_right] = dry.targets(voice, length);
+            let [chorus_left, chorus_right] = match chorus.as_deref_mut() {
+                Some(chorus) => chorus.targets(voice, inserted, length),
+                None => [empty_target(), empty_target()],
+            };
+            let reverb_input = match reverb.as_deref_mut() {
+                Some(reverb) => reverb.target(voice, inserted, length),
+                None => empty_target(),
+            };
+            ArrayMath::multiply_add_ramps(
+                &voice.block[..length],
+                &mut [
+                    dry_left,
+                    dry_right,
+                    stem_left,
+                    stem_right,
+                    chorus_left,
+                    chorus_right,
+                    reverb_input,
+                ],
+            );
+        }
+    }
+
+    // Each bus still sums the voices in order, so mixing the buses concurrently gives the same output.
+    // The mix of the voices itself is not parallelized: the dry bus is a single task,
+    // since the kernels compute the ramp gains from the start of the block,
+    // and a task per sample range would round the gains differently.
+    #[cfg(feature = "parallel")]
+    fn write_parallel(self, voices: &[Voice]) {
+        use bevy_tasks::{ComputeTaskPool, TaskPool};
+
+        let Self {
+            mut dry,
+            chorus,
+            reverb,
+            inserted,
+            length,
+        } = self;
+        ComputeTaskPool::get_or_init(TaskPool::default).scope(|scope| {
+            scope.spawn(async move {
+                for voice in voices.iter() {
+                    let mut targets = dry.targets(voice, length);
+                    ArrayMath::multiply_add_ramps(&voice.block[..length], &mut targets);
+                }
+            });
+            if let Some(chorus) = chorus {
+                scope.spawn(async move {
+                    for voice in voices.iter() {
+                        let mut targets = chorus.targets(voice, inserted, length);
+                        ArrayMath::multiply_add_ramps(&voice.block[..length], &mut targets);
+                    }
+                });
+            }
+            if let Some(reverb) = reverb {
+                scope.spawn(async move {
+                    for voice in voices.iter() {
+                        let target = reverb.target(voice, inserted, length);
+                        ArrayMath::multiply_add_ramps(&voice.block[..length], &mut [target]);
+                    }
+                });
+            }
+        });
+    }
+}
+
+// The destinations of the dry signal of the voices.
+struct DryMix<'a> {
+    master_volume: f32,
+    block_left: &'a mut [f32],
+    block_right: &'a mut [f32],
+    channels: &'a mut [SynthChannel],
+    stems: Option<&'a mut Stems>,
+}
+
+impl DryMix<'_> {
+    // Gets the targets of the voice in the main mix and its stem.
+    fn targets(&mut self, voice: &Voice, length: usize) -> [MixTarget<'_>; 4] {
+        let channel = voice.channel as usize;
+        let inverse_block_size = 1_f32 / length as f32;
+        // The voices of a channel with an insertion effect are mixed into the sub-mix of the channel,
+        // which goes to the master volume, the stem and the send effects after the insertion effect.
+        let (volume, block_left, block_right, stem) =
+            match self.channels[channel].insertion_effect.as_mut() {
+                Some(effect) => (
+                    1_f32,
+                    &mut effect.block_left[..length],
+                    &mut effect.block_right[..length],
+                    None,
+                ),
+                None => (
+                    self.master_volume,
+                    &mut self.block_left[..length],
+                    &mut self.block_right[..length],
+                    self.stems
+                        .as_deref_mut()
+                        .map(|stems| &mut stems.channels[channel]),
+                ),
+            };
+        let previous_gain_left = volume * voice.previous_mix_gain_left;
+        let current_gain_left = volume * voice.current_mix_gain_left;
+        let previous_gain_right = volume * voice.previous_mix_gain_right;
+        let current_gain_right = volume * voice.current_mix_gain_right;
+        let [stem_left, stem_right] = match stem {
+            Some([left, right]) => [
+                Ramp::target(
+                    previous_gain_left,
+                    current_gain_left,
+                    inverse_block_size,
+                    &mut left[..length],
+                ),
+                Ramp::target(
+                    previous_gain_right,
+                    current_gain_right,
+                    inverse_block_size,
+                    &mut right[..length],
+                ),
+            ],
+            None => [empty_target(), empty_target()],
+        };
+        [
+            Ramp::target(
+                previous_gain_left,
+                current_gain_left,
+                inverse_block_size,
+                block_left,
+            ),
+            Ramp::target(
+                previous_gain_right,
+                current_gain_right,
+                inverse_block_size,
+                block_right,
+            ),
+            stem_left,
+            stem_right,
+        ]
+    }
 }
 
-impl Effects {
-    fn new(settings: &SynthesizerSettings) -> Effects {
+// The presets of a SoundFont indexed by their bank and patch numbers.
+pub(crate) struct PresetLookup {
+    presets: HashMap<i32, usize>,
+    default_preset: usize,
+}
+
+impl PresetLookup {
+    pub(crate) fn new(sound_font: &SoundFont) -> Self {
+        let mut presets: HashMap<i32, usize> = HashMap::new();
+
+        let mut min_preset_id = i32::MAX;
+        let mut default_preset: usize = 0;
+
+        sound_font
+            .presets
+            .iter()
+            .enumerate()
+            .for_each(|(i, preset)| {
+                // The preset ID is Int32, where the upper 16 bits represent the bank number
+                // and the lower 16 bits represent the patch number.
+                // This ID is used to search for presets by the combination of bank number
+                // and patch number.
+                let preset_id = (preset.bank_number << 16) | preset.patch_number;
+                presets.insert(preset_id, i);
+
+                // The preset with the minimum ID number will be default.
+                // If the SoundFont is GM compatible, the piano will be chosen.
+                if preset_id < min_preset_id {
+                    default_preset = i;
+                    min_preset_id = preset_id;
+                }
+            });
+
         Self {
-            reverb: Reverb::new(settings.sample_rate),
-            reverb_input: vec![0_f32; settings.block_size],
-            reverb_output_left: vec![0_f32; settings.block_size],
-            reverb_output_right: vec![0_f32; settings.block_size],
-            chorus: Chorus::new(settings.sample_rate, 0.002, 0.0019, 0.4),
-            chorus_input_left: vec![0_f32; settings.block_size],
-            chorus_input_right: vec![0_f32; settings.block_size],
-            chorus_output_left: vec![0_f32; settings.block_size],
-            chorus_output_right: vec![0_f32; settings.block_size],
+            presets,
+            default_preset,
         }
     }
 }
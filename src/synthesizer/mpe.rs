@@ -0,0 +1,103 @@
+/// Identifies one of the two zones of MIDI Polyphonic Expression (MPE).
+///
+/// A zone consists of a manager channel, whose messages apply to the whole zone,
+/// and a number of member channels, each of which carries a single note together
+/// with its own pitch bend, pressure and timbre (CC74).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeZone {
+    /// The zone managed by the first channel. Member channels are allocated upwards.
+    Lower,
+    /// The zone managed by the last channel. Member channels are allocated downwards.
+    Upper,
+}
+
+impl MpeZone {
+    /// Gets the manager channel of the zone.
+    pub fn get_manager_channel(&self) -> u8 {
+        match self {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
+
+    pub(crate) fn from_manager_channel(channel: u8) -> Option<Self> {
+        match channel {
+            0 => Some(MpeZone::Lower),
+            15 => Some(MpeZone::Upper),
+            _ => None,
+        }
+    }
+}
+
+/// The MPE zone layout of the synthesizer.
+///
+/// The two zones share the sixteen channels, so configuring one zone
+/// shrinks the other one if they would overlap.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct MpeConfiguration {
+    lower_member_count: u8,
+    upper_member_count: u8,
+}
+
+impl MpeConfiguration {
+    /// The registered parameter number of the MPE Configuration Message.
+    pub(crate) const RPN: u16 = 6;
+    /// The default pitch bend range of member channels in semitones.
+    pub(crate) const MEMBER_PITCH_BEND_RANGE: u8 = 48;
+    /// The default pitch bend range of manager channels in semitones.
+    pub(crate) const MANAGER_PITCH_BEND_RANGE: u8 = 2;
+    /// The pitch bend range in semitones restored on the channels leaving a zone.
+    pub(crate) const DEFAULT_PITCH_BEND_RANGE: u8 = 2;
+
+    const MAXIMUM_MEMBER_COUNT: u8 = 15;
+
+    pub(crate) fn get_member_channel_count(&self, zone: MpeZone) -> u8 {
+        match zone {
+            MpeZone::Lower => self.lower_member_count,
+            MpeZone::Upper => self.upper_member_count,
+        }
+    }
+
+    pub(crate) fn set_member_channel_count(&mut self, zone: MpeZone, count: u8) {
+        let count = count.min(MpeConfiguration::MAXIMUM_MEMBER_COUNT);
+
+        // Both managers and all members must fit in 16 channels.
+        let remaining = (MpeConfiguration::MAXIMUM_MEMBER_COUNT - 1).saturating_sub(count);
+        match zone {
+            MpeZone::Lower => {
+                self.lower_member_count = count;
+                self.upper_member_count = self.upper_member_count.min(remaining);
+            }
+            MpeZone::Upper => {
+                self.upper_member_count = count;
+                self.lower_member_count = self.lower_member_count.min(remaining);
+            }
+        }
+
+        // A zone that lost its manager channel to the other zone is disabled.
+        if self.lower_member_count == MpeConfiguration::MAXIMUM_MEMBER_COUNT {
+            self.upper_member_count = 0;
+        }
+        if self.upper_member_count == MpeConfiguration::MAXIMUM_MEMBER_COUNT {
+            self.lower_member_count = 0;
+        }
+    }
+
+    /// Gets the zone which the channel belongs to, either as a manager or as a member.
+    pub(crate) fn get_zone(&self, channel: u8) -> Option<MpeZone> {
+        if self.lower_member_count > 0 && channel <= self.lower_member_count {
+            Some(MpeZone::Lower)
+        } else if self.upper_member_count > 0 && channel >= 15 - self.upper_member_count {
+            Some(MpeZone::Upper)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the manager channel if the channel is a member channel of a zone.
+    pub(crate) fn get_manager(&self, channel: u8) -> Option<u8> {
+        self.get_zone(channel)
+            .map(|zone| zone.get_manager_channel())
+            .filter(|manager| *manager != channel)
+    }
+}
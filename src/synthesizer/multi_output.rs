@@ -0,0 +1,114 @@
+use bevy_platform::prelude::*;
+
+/// The output buffers for rendering each MIDI channel separately.
+///
+/// # Remarks
+///
+/// See [`Synthesizer::render_multi`](super::Synthesizer::render_multi).
+pub struct MultiOutputBuffers {
+    /// The left and right buffers of each MIDI channel.
+    pub channels: Vec<[Vec<f32>; 2]>,
+    /// The left and right buffers of the reverb return, or `None` to discard the reverb.
+    pub reverb: Option<[Vec<f32>; 2]>,
+    /// The left and right buffers of the chorus return, or `None` to discard the chorus.
+    pub chorus: Option<[Vec<f32>; 2]>,
+}
+
+impl MultiOutputBuffers {
+    /// Initializes a new set of output buffers for all the MIDI channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - The length of each buffer.
+    /// * `effect_returns` - The value indicating whether the reverb and chorus returns are rendered.
+    pub fn new(length: usize, effect_returns: bool) -> Self {
+        let stereo = || [vec![0_f32; length], vec![0_f32; length]];
+
+        Self {
+            channels: (0..16).map(|_| stereo()).collect(),
+            reverb: effect_returns.then(stereo),
+            chorus: effect_returns.then(stereo),
+        }
+    }
+
+    /// Gets the length of the buffers.
+    pub fn len(&self) -> usize {
+        self.channels
+            .first()
+            .map(|[left, _]| left.len())
+            .unwrap_or_default()
+    }
+
+    /// Gets the value indicating whether the buffers are empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn buffers(&mut self) -> impl Iterator<Item = &mut Vec<f32>> {
+        self.channels
+            .iter_mut()
+            .chain(self.reverb.iter_mut())
+            .chain(self.chorus.iter_mut())
+            .flat_map(|buffers| buffers.iter_mut())
+    }
+
+    pub(crate) fn check_length(&mut self) -> bool {
+        let length = self.len();
+        self.buffers().all(|buffer| buffer.len() == length)
+    }
+}
+
+// The per-channel mix of the current block, rendered alongside the main mix.
+pub(crate) struct Stems {
+    pub(crate) channels: Vec<[Vec<f32>; 2]>,
+    pub(crate) reverb: [Vec<f32>; 2],
+    pub(crate) chorus: [Vec<f32>; 2],
+}
+
+impl Stems {
+    pub(crate) fn new(channel_count: usize, block_size: usize) -> Self {
+        let stereo = || [vec![0_f32; block_size], vec![0_f32; block_size]];
+
+        Self {
+            channels: (0..channel_count).map(|_| stereo()).collect(),
+            reverb: stereo(),
+            chorus: stereo(),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for buffer in self
+            .channels
+            .iter_mut()
+            .chain([&mut self.reverb, &mut self.chorus])
+            .flat_map(|buffers| buffers.iter_mut())
+        {
+            buffer.fill(0_f32);
+        }
+    }
+
+    pub(crate) fn copy_to(
+        &self,
+        buffers: &mut MultiOutputBuffers,
+        source: usize,
+        destination: usize,
+        length: usize,
+    ) {
+        let copy = |from: &[Vec<f32>; 2], to: &mut [Vec<f32>; 2]| {
+            for (from, to) in from.iter().zip(to.iter_mut()) {
+                to[destination..destination + length]
+                    .copy_from_slice(&from[source..source + length]);
+            }
+        };
+
+        for (from, to) in self.channels.iter().zip(buffers.channels.iter_mut()) {
+            copy(from, to);
+        }
+        if let Some(reverb) = buffers.reverb.as_mut() {
+            copy(&self.reverb, reverb);
+        }
+        if let Some(chorus) = buffers.chorus.as_mut() {
+            copy(&self.chorus, chorus);
+        }
+    }
+}
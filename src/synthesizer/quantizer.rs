@@ -0,0 +1,72 @@
+use bevy_platform::prelude::*;
+
+/// Specifies the dithering applied when the output is converted to integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// The samples are rounded to the nearest integers.
+    None,
+    /// Triangular (TPDF) dither of 2 LSB peak-to-peak is added before rounding.
+    Triangular,
+    /// Triangular dither with first-order noise shaping,
+    /// which moves the quantization noise to high frequencies.
+    NoiseShaped,
+}
+
+// Converts the samples to integers of a specified bit depth.
+pub(crate) struct Quantizer {
+    dither: Dither,
+    random: u32,
+    // The quantization error of the previous sample of each channel.
+    error: Vec<f64>,
+}
+
+impl Quantizer {
+    pub(crate) fn new(channel_count: usize) -> Self {
+        Self {
+            dither: Dither::None,
+            random: 0x12345678,
+            error: vec![0.0; channel_count],
+        }
+    }
+
+    pub(crate) fn get_dither(&self) -> Dither {
+        self.dither
+    }
+
+    pub(crate) fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+        self.reset();
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.error.fill(0.0);
+    }
+
+    pub(crate) fn quantize(&mut self, channel: usize, value: f32, bits: u32) -> i32 {
+        let scale = (1_i64 << (bits - 1)) as f64;
+        let mut x = value as f64 * scale;
+
+        let dither = match self.dither {
+            Dither::None => 0.0,
+            Dither::Triangular | Dither::NoiseShaped => self.next_random() - self.next_random(),
+        };
+
+        if self.dither == Dither::NoiseShaped {
+            x -= self.error[channel];
+        }
+
+        let quantized = (x + dither).round().clamp(-scale, scale - 1.0);
+        // A clipped sample must not blow up the error feedback.
+        self.error[channel] = (quantized - x).clamp(-1.0, 1.0);
+
+        quantized as i32
+    }
+
+    // Gets a uniform random number between 0 and 1 (xorshift).
+    fn next_random(&mut self) -> f64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random as f64 / 4294967296.0
+    }
+}
@@ -0,0 +1,307 @@
+use core::{
+    cmp,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::sync::Arc;
+
+use bevy_platform::prelude::*;
+use crossbeam_queue::ArrayQueue;
+use midix::prelude::ChannelVoiceMessage;
+
+use crate::prelude::*;
+
+/// A command sent from a [`SynthController`] to the [`SynthRenderer`].
+#[derive(Debug, Clone)]
+pub enum SynthCommand {
+    /// Processes a MIDI message.
+    MidiMessage(ChannelVoiceMessage),
+    /// Stops all the notes, immediately if `true`.
+    NoteOffAll(bool),
+    /// Sets the master volume.
+    MasterVolume(f32),
+    /// Sets the reverb parameters.
+    ReverbParams(ReverbParams),
+    /// Sets the chorus parameters.
+    ChorusParams(ChorusParams),
+    /// Replaces the SoundFont.
+    ///
+    /// The presets are indexed by the controller, and the previous SoundFont is handed back
+    /// to the controllers to be dropped there. A replacement waits until the previous one
+    /// has been released by [`SynthController::send_at`] or [`SynthController::release_retired`].
+    SoundFont(Arc<SoundFont>),
+    /// Resets the synthesizer.
+    Reset,
+}
+
+// A command prepared by a controller, so that the renderer does not need to allocate.
+enum Command {
+    // A command other than replacing the SoundFont.
+    Synth(SynthCommand),
+    SoundFont(Arc<SoundFont>, PresetLookup),
+}
+
+impl From<SynthCommand> for Command {
+    fn from(command: SynthCommand) -> Self {
+        match command {
+            SynthCommand::SoundFont(sound_font) => {
+                let preset_lookup = PresetLookup::new(&sound_font);
+                Command::SoundFont(sound_font, preset_lookup)
+            }
+            command => Command::Synth(command),
+        }
+    }
+}
+
+impl From<Command> for SynthCommand {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::Synth(command) => command,
+            Command::SoundFont(sound_font, _) => SynthCommand::SoundFont(sound_font),
+        }
+    }
+}
+
+struct TimedCommand {
+    frame: u64,
+    command: Command,
+}
+
+// The state shared between the controllers and the renderer.
+struct Shared {
+    commands: ArrayQueue<TimedCommand>,
+    // The SoundFont replaced on the audio thread, to be released by a controller.
+    // The renderer only replaces the SoundFont while this slot is free,
+    // so that nothing is ever dropped on the audio thread.
+    retired: ArrayQueue<(Arc<SoundFont>, PresetLookup)>,
+    position: AtomicU64,
+}
+
+/// A handle to control a [`SynthRenderer`] from other threads.
+///
+/// # Remarks
+///
+/// The commands are pushed into a bounded lock-free queue,
+/// which the renderer drains at each block boundary.
+/// The blocks are shortened to end at the frames of the commands.
+#[derive(Clone)]
+pub struct SynthController {
+    shared: Arc<Shared>,
+}
+
+/// The audio thread side of a synthesizer driven by [`SynthController`]s.
+pub struct SynthRenderer {
+    synthesizer: Synthesizer,
+    shared: Arc<Shared>,
+    // The commands received but scheduled after the current position.
+    pending: Vec<TimedCommand>,
+    position: u64,
+}
+
+impl Synthesizer {
+    /// Splits the synthesizer into a controller for other threads and a renderer for the audio thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of commands waiting to be processed.
+    pub fn split(self, capacity: usize) -> (SynthController, SynthRenderer) {
+        let capacity = capacity.max(1);
+        let shared = Arc::new(Shared {
+            commands: ArrayQueue::new(capacity),
+            retired: ArrayQueue::new(1),
+            position: AtomicU64::new(0),
+        });
+
+        let controller = SynthController {
+            shared: shared.clone(),
+        };
+        let renderer = SynthRenderer {
+            synthesizer: self,
+            shared,
+            pending: Vec::with_capacity(capacity),
+            position: 0,
+        };
+
+        (controller, renderer)
+    }
+}
+
+impl SynthController {
+    /// Sends a command to be processed at the start of the next block.
+    /// Returns the command back if the queue is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to be sent.
+    pub fn send(&self, command: SynthCommand) -> Result<(), SynthCommand> {
+        self.send_at(0, command)
+    }
+
+    /// Sends a command to be processed at the specified frame.
+    /// Returns the command back if the queue is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The number of frames rendered since the split at which the command takes effect.
+    ///   The renderer ends its blocks at the frames of the commands, so every command takes effect exactly at this frame.
+    ///   A frame in the past is processed at the start of the next block.
+    /// * `command` - The command to be sent.
+    pub fn send_at(&self, frame: u64, command: SynthCommand) -> Result<(), SynthCommand> {
+        self.release_retired();
+
+        let command = Command::from(command);
+        self.shared
+            .commands
+            .push(TimedCommand { frame, command })
+            .map_err(|timed| timed.command.into())
+    }
+
+    /// Drops the SoundFont replaced by the renderer, if any.
+    ///
+    /// # Remarks
+    ///
+    /// This is done on every send, so it only needs to be called
+    /// when a SoundFont is replaced without sending other commands afterwards.
+    pub fn release_retired(&self) {
+        self.shared.retired.pop();
+    }
+
+    /// Sends a MIDI message. Returns the message back if the queue is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The MIDI message to be processed.
+    pub fn process_midi_message(&self, message: ChannelVoiceMessage) -> Result<(), SynthCommand> {
+        self.send(SynthCommand::MidiMessage(message))
+    }
+
+    /// Gets the number of frames rendered since the split.
+    pub fn get_position(&self) -> u64 {
+        self.shared.position.load(Ordering::Acquire)
+    }
+}
+
+impl SynthRenderer {
+    /// Renders the waveform, processing the commands at their frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The buffer of the left channel to store the rendered waveform.
+    /// * `right` - The buffer of the right channel to store the rendered waveform.
+    ///
+    /// # Remarks
+    ///
+    /// The output buffers for the left and right must be the same length.
+    /// This does not allocate or release memory.
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        if left.len() != right.len() {
+            panic!("The output buffers for the left and right must be the same length.");
+        }
+
+        let length = left.len();
+        let mut wrote = 0;
+        while wrote < length {
+            if self.synthesizer.get_block_remaining() == 0 {
+                self.process_commands();
+            }
+
+            let rem = cmp::min(
+                match self.synthesizer.get_block_remaining() {
+                    0 => self.synthesizer.get_next_block_length(),
+                    remaining => remaining,
+                },
+                length - wrote,
+            );
+
+            self.synthesizer.render(
+                &mut left[wrote..wrote + rem],
+                &mut right[wrote..wrote + rem],
+            );
+
+            self.position += rem as u64;
+            wrote += rem;
+        }
+
+        self.shared.position.store(self.position, Ordering::Release);
+    }
+
+    // Processes the commands due at the current position, in the order they were sent,
+    // and ends the next block at the frame of the following command.
+    fn process_commands(&mut self) {
+        while self.pending.len() < self.pending.capacity() {
+            match self.shared.commands.pop() {
+                Some(command) => self.pending.push(command),
+                None => break,
+            }
+        }
+
+        let mut next_frame = None;
+        let mut i = 0;
+        while i < self.pending.len() {
+            let frame = self.pending[i].frame;
+            if frame > self.position {
+                next_frame = Some(next_frame.map_or(frame, |next: u64| next.min(frame)));
+                i += 1;
+                continue;
+            }
+
+            // The SoundFont is replaced after the previous one has been released,
+            // and the commands sent after it wait for it.
+            if matches!(self.pending[i].command, Command::SoundFont(..))
+                && self.shared.retired.is_full()
+            {
+                break;
+            }
+
+            let command = self.pending.remove(i).command;
+            self.apply(command);
+        }
+
+        if let Some(frame) = next_frame {
+            let length = frame - self.position;
+            if length < self.synthesizer.get_block_size() as u64 {
+                self.synthesizer.set_next_block_length(length as usize);
+            }
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        let command = match command {
+            Command::Synth(command) => command,
+            Command::SoundFont(sound_font, preset_lookup) => {
+                let previous = self
+                    .synthesizer
+                    .replace_sound_font(sound_font, preset_lookup);
+                // The slot has been checked to be free, and only the renderer fills it.
+                let _ = self.shared.retired.push(previous);
+                return;
+            }
+        };
+
+        match command {
+            SynthCommand::MidiMessage(message) => self.synthesizer.process_midi_message(message),
+            SynthCommand::NoteOffAll(immediate) => self.synthesizer.note_off_all(immediate),
+            SynthCommand::MasterVolume(value) => self.synthesizer.set_master_volume(value),
+            SynthCommand::ReverbParams(params) => self.synthesizer.set_reverb_params(params),
+            SynthCommand::ChorusParams(params) => self.synthesizer.set_chorus_params(params),
+            SynthCommand::SoundFont(_) => {
+                unreachable!("The SoundFonts are prepared by the controller.")
+            }
+            SynthCommand::Reset => self.synthesizer.reset(),
+        }
+    }
+
+    /// Gets the number of frames rendered since the split.
+    pub fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    /// Gets the synthesizer.
+    pub fn get_synthesizer(&self) -> &Synthesizer {
+        &self.synthesizer
+    }
+
+    /// Gets the synthesizer.
+    pub fn get_synthesizer_mut(&mut self) -> &mut Synthesizer {
+        &mut self.synthesizer
+    }
+}
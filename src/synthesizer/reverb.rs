@@ -1,7 +1,142 @@
 use core::cmp;
 
 use bevy_platform::prelude::*;
+
+/// Specifies the parameters of the reverb effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReverbParams {
+    /// The size of the room, from `0.0` to `1.0`. Larger rooms have a longer decay.
+    pub room_size: f32,
+    /// The high frequency damping of the reflections, from `0.0` to `1.0`.
+    pub damping: f32,
+    /// The stereo width of the reverb output, from `0.0` (mono) to `1.0`.
+    pub width: f32,
+    /// The output level of the reverb. `1.0` is the default level.
+    pub level: f32,
+    /// The delay before the reverb starts, in seconds.
+    pub pre_delay: f32,
+    /// The amount of the pre-delayed signal fed back into the pre-delay, from `0.0` to `1.0`.
+    /// This turns the pre-delay into an echo, which is used by the delay types.
+    pub delay_feedback: f32,
+}
+
+impl Default for ReverbParams {
+    fn default() -> Self {
+        Self {
+            room_size: Reverb::INITIAL_ROOM,
+            damping: Reverb::INITIAL_DAMP,
+            width: Reverb::INITIAL_WIDTH,
+            level: 1.0,
+            pre_delay: 0.0,
+            delay_feedback: 0.0,
+        }
+    }
+}
+
+impl ReverbParams {
+    fn clamped(&self) -> Self {
+        Self {
+            room_size: self.room_size.clamp(0.0, 1.0),
+            damping: self.damping.clamp(0.0, 1.0),
+            width: self.width.clamp(0.0, 1.0),
+            level: self.level.max(0.0),
+            pre_delay: self.pre_delay.clamp(0.0, Reverb::MAXIMUM_PRE_DELAY),
+            delay_feedback: self.delay_feedback.clamp(0.0, 0.95),
+        }
+    }
+
+    fn approach(&mut self, target: &ReverbParams, amount: f32) -> bool {
+        fn step(current: &mut f32, target: f32, amount: f32) -> bool {
+            let diff = target - *current;
+            if diff == 0.0 {
+                return false;
+            }
+
+            if diff.abs() < 1.0E-4 {
+                *current = target;
+            } else {
+                *current += amount * diff;
+            }
+            true
+        }
+
+        let mut changed = step(&mut self.room_size, target.room_size, amount);
+        changed |= step(&mut self.damping, target.damping, amount);
+        changed |= step(&mut self.width, target.width, amount);
+        changed |= step(&mut self.level, target.level, amount);
+        changed |= step(&mut self.pre_delay, target.pre_delay, amount);
+        changed |= step(&mut self.delay_feedback, target.delay_feedback, amount);
+        changed
+    }
+}
+
+/// Specifies the reverb types (macros) of the Roland GS standard.
+///
+/// The GS reverb types are approximated with the parameters of the reverb effect.
+/// The delay types use the pre-delay with feedback in front of the reverb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReverbType {
+    /// A small, bright room.
+    Room1,
+    /// A small, dark room.
+    Room2,
+    /// A medium room.
+    Room3,
+    /// A concert hall.
+    Hall1,
+    /// A large concert hall. This is the default of GS devices.
+    Hall2,
+    /// A plate reverb.
+    Plate,
+    /// An echo.
+    Delay,
+    /// An echo with a wide stereo image.
+    PanningDelay,
+}
+
+impl ReverbType {
+    /// Gets the reverb type from the value of the GS reverb macro (0 to 7).
+    pub fn from_gs(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ReverbType::Room1),
+            1 => Some(ReverbType::Room2),
+            2 => Some(ReverbType::Room3),
+            3 => Some(ReverbType::Hall1),
+            4 => Some(ReverbType::Hall2),
+            5 => Some(ReverbType::Plate),
+            6 => Some(ReverbType::Delay),
+            7 => Some(ReverbType::PanningDelay),
+            _ => None,
+        }
+    }
+
+    /// Gets the parameters of the reverb type.
+    pub fn get_params(&self) -> ReverbParams {
+        let (room_size, damping, width, pre_delay, delay_feedback) = match self {
+            ReverbType::Room1 => (0.40, 0.60, 0.8, 0.0, 0.0),
+            ReverbType::Room2 => (0.30, 0.70, 0.7, 0.0, 0.0),
+            ReverbType::Room3 => (0.50, 0.40, 0.9, 0.0, 0.0),
+            ReverbType::Hall1 => (0.70, 0.55, 1.0, 0.010, 0.0),
+            ReverbType::Hall2 => (0.80, 0.40, 1.0, 0.015, 0.0),
+            ReverbType::Plate => (0.75, 0.15, 1.0, 0.0, 0.0),
+            ReverbType::Delay => (0.10, 0.80, 0.5, 0.250, 0.35),
+            ReverbType::PanningDelay => (0.10, 0.80, 1.0, 0.300, 0.30),
+        };
+
+        ReverbParams {
+            room_size,
+            damping,
+            width,
+            level: 1.0,
+            pre_delay,
+            delay_feedback,
+        }
+    }
+}
+
 pub(crate) struct Reverb {
+    sample_rate: i32,
+
     left_combfilters: Vec<CombFilter>,
     left_allpassfilters: Vec<AllPassFilter>,
 
@@ -17,6 +152,12 @@ pub(crate) struct Reverb {
     wet1: f32,
     wet2: f32,
     width: f32,
+
+    pre_delay: PreDelay,
+
+    // The parameters are moved towards the target block by block to avoid zipper noise.
+    params: ReverbParams,
+    target_params: ReverbParams,
 }
 
 impl Reverb {
@@ -31,6 +172,11 @@ impl Reverb {
     const INITIAL_WIDTH: f32 = 1.0;
     const STEREO_SPREAD: usize = 23;
 
+    /// The maximum pre-delay in seconds.
+    pub(crate) const MAXIMUM_PRE_DELAY: f32 = 0.5;
+    /// The time constant of the parameter smoothing in seconds.
+    const SMOOTHING_TIME: f32 = 0.05;
+
     const CF_TUNING_L1: usize = 1116;
     const CF_TUNING_R1: usize = 1116 + Reverb::STEREO_SPREAD;
     const CF_TUNING_L2: usize = 1188;
@@ -56,7 +202,7 @@ impl Reverb {
     const APF_TUNING_L4: usize = 225;
     const APF_TUNING_R4: usize = 225 + Reverb::STEREO_SPREAD;
 
-    pub(crate) fn new(sample_rate: i32) -> Self {
+    pub(crate) fn new(sample_rate: i32, params: &ReverbParams) -> Self {
         let left_combfilters: Vec<CombFilter> = vec![
             CombFilter::new(Reverb::scale_tuning(sample_rate, Reverb::CF_TUNING_L1)),
             CombFilter::new(Reverb::scale_tuning(sample_rate, Reverb::CF_TUNING_L2)),
@@ -101,7 +247,11 @@ impl Reverb {
             apf.set_feedback(0.5_f32);
         }
 
+        let params = params.clamped();
+        let pre_delay_length = (sample_rate as f32 * Reverb::MAXIMUM_PRE_DELAY) as usize + 2;
+
         let mut reverb = Reverb {
+            sample_rate,
             left_combfilters,
             right_combfilters,
             left_allpassfilters,
@@ -115,16 +265,29 @@ impl Reverb {
             wet1: 0_f32,
             wet2: 0_f32,
             width: 0_f32,
+            pre_delay: PreDelay::new(pre_delay_length),
+            params,
+            target_params: params,
         };
 
-        reverb.set_wet(Reverb::INITIAL_WET);
-        reverb.set_room_size(Reverb::INITIAL_ROOM);
-        reverb.set_damp(Reverb::INITIAL_DAMP);
-        reverb.set_width(Reverb::INITIAL_WIDTH);
+        reverb.apply_params();
 
         reverb
     }
 
+    /// Sets the parameters. The change is smoothed over the following blocks.
+    pub(crate) fn set_params(&mut self, params: &ReverbParams) {
+        self.target_params = params.clamped();
+    }
+
+    fn apply_params(&mut self) {
+        let params = self.params;
+        self.set_wet(params.level * Reverb::INITIAL_WET);
+        self.set_room_size(params.room_size);
+        self.set_damp(params.damping);
+        self.set_width(params.width);
+    }
+
     pub fn mute(&mut self) {
         for cf in self.left_combfilters.iter_mut() {
             cf.mute();
@@ -141,6 +304,8 @@ impl Reverb {
         for apf in self.right_allpassfilters.iter_mut() {
             apf.mute();
         }
+
+        self.pre_delay.mute();
     }
 
     fn scale_tuning(sample_rate: i32, tuning: usize) -> usize {
@@ -149,11 +314,24 @@ impl Reverb {
 
     pub(crate) fn process(
         &mut self,
-        input: &[f32],
+        input: &mut [f32],
         output_left: &mut [f32],
         output_right: &mut [f32],
     ) {
         let input_length = input.len();
+
+        let amount = 1_f32
+            - (-(input_length as f32) / (Reverb::SMOOTHING_TIME * self.sample_rate as f32)).exp();
+        if self.params.approach(&self.target_params, amount) {
+            self.apply_params();
+        }
+
+        self.pre_delay.process(
+            input,
+            self.params.pre_delay * self.sample_rate as f32,
+            self.params.delay_feedback,
+        );
+        let input = &*input;
         let output_left_length = output_left.len();
         let output_right_length = output_right.len();
 
@@ -181,7 +359,7 @@ impl Reverb {
         }
 
         // With the default settings, we can skip this part.
-        if 1_f32 - self.wet1 > 1.0E-3_f32 || self.wet2 > 1.0E-3_f32 {
+        if (1_f32 - self.wet1).abs() > 1.0E-3_f32 || self.wet2 > 1.0E-3_f32 {
             for t in 0..input_length {
                 let left = output_left[t];
                 let right = output_right[t];
@@ -383,3 +561,64 @@ impl AllPassFilter {
         self.feedback = value;
     }
 }
+
+struct PreDelay {
+    buffer: Vec<f32>,
+
+    buffer_index: usize,
+
+    // The delay in samples used for the previous block.
+    delay: f32,
+}
+
+impl PreDelay {
+    fn new(buffer_size: usize) -> Self {
+        Self {
+            buffer: vec![0_f32; buffer_size],
+            buffer_index: 0,
+            delay: 0_f32,
+        }
+    }
+
+    fn mute(&mut self) {
+        self.buffer.fill(0_f32);
+    }
+
+    fn process(&mut self, block: &mut [f32], delay: f32, feedback: f32) {
+        // With the default settings, the pre-delay is bypassed.
+        if delay < 1_f32 && self.delay < 1_f32 && feedback == 0_f32 {
+            self.delay = delay;
+            return;
+        }
+
+        let buffer_length = self.buffer.len();
+        let delay = delay.min((buffer_length - 2) as f32);
+
+        // The delay time is ramped over the block so that changes do not click.
+        let step = (delay - self.delay) / block.len() as f32;
+        let mut current = self.delay;
+
+        for sample in block.iter_mut() {
+            let mut position = self.buffer_index as f32 - current;
+            if position < 0_f32 {
+                position += buffer_length as f32;
+            }
+
+            let index1 = position as usize % buffer_length;
+            let index2 = (index1 + 1) % buffer_length;
+            let a = position - position.floor();
+            let delayed = self.buffer[index1] + a * (self.buffer[index2] - self.buffer[index1]);
+
+            self.buffer[self.buffer_index] = *sample + feedback * delayed;
+            *sample = delayed;
+
+            self.buffer_index += 1;
+            if self.buffer_index == buffer_length {
+                self.buffer_index = 0;
+            }
+            current += step;
+        }
+
+        self.delay = delay;
+    }
+}
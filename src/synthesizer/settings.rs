@@ -9,17 +9,36 @@ pub struct SynthesizerSettings {
     pub block_size: usize,
     /// The number of maximum polyphony.
     pub maximum_polyphony: usize,
+    /// The value indicating whether reverb is enabled.
+    pub enable_reverb: bool,
+    /// The value indicating whether chorus is enabled.
+    pub enable_chorus: bool,
     /// The value indicating whether reverb and chorus are enabled.
+    /// Setting this to `false` disables both, regardless of `enable_reverb` and `enable_chorus`.
+    #[deprecated(note = "use `enable_reverb` and `enable_chorus` instead")]
     pub enable_reverb_and_chorus: bool,
+    /// The policy for choosing the voice to be replaced at the maximum polyphony.
+    pub voice_stealing_policy: VoiceStealingPolicy,
+    /// The shape of the volume envelope.
+    pub envelope_curve: EnvelopeCurve,
+    /// The value indicating whether the channels are also mixed separately for
+    /// [`Synthesizer::render_multi`], which costs an extra mix on every block.
+    pub enable_multi_output: bool,
 }
 
 impl Default for SynthesizerSettings {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             sample_rate: 44100,
             block_size: 64,
             maximum_polyphony: 64,
+            enable_reverb: true,
+            enable_chorus: true,
             enable_reverb_and_chorus: true,
+            voice_stealing_policy: VoiceStealingPolicy::Envelope,
+            envelope_curve: EnvelopeCurve::Specification,
+            enable_multi_output: false,
         }
     }
 }
@@ -37,6 +56,18 @@ impl SynthesizerSettings {
         }
     }
 
+    // Applies the deprecated settings to the ones replacing them.
+    #[allow(deprecated)]
+    pub(crate) fn resolve_deprecated(&self) -> Self {
+        let mut settings = *self;
+        if !settings.enable_reverb_and_chorus {
+            settings.enable_reverb = false;
+            settings.enable_chorus = false;
+            settings.enable_reverb_and_chorus = true;
+        }
+        settings
+    }
+
     pub(crate) fn validate(&self) -> Result<(), SynthesizerError> {
         SynthesizerSettings::check_sample_rate(self.sample_rate)?;
         SynthesizerSettings::check_block_size(self.block_size)?;
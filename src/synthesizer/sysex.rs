@@ -0,0 +1,165 @@
+// Roland GS and Yamaha XG system exclusive messages.
+//
+// A GS parameter change is sent as a Data Set (DT1) message:
+// F0 41 <device> 42 12 <address (3 bytes)> <data...> <checksum> F7
+//
+// An XG parameter change has no checksum:
+// F0 43 1<device> 4C <address (3 bytes)> <data...> F7
+
+const ROLAND_ID: u8 = 0x41;
+const GS_MODEL_ID: u8 = 0x42;
+const DATA_SET_COMMAND: u8 = 0x12;
+
+const YAMAHA_ID: u8 = 0x43;
+const XG_MODEL_ID: u8 = 0x4C;
+const PARAMETER_CHANGE_COMMAND: u8 = 0x10;
+
+pub(crate) const fn gs_address(high: u8, middle: u8, low: u8) -> u32 {
+    ((high as u32) << 14) | ((middle as u32) << 7) | low as u32
+}
+
+pub(crate) const GS_REVERB_MACRO: u32 = gs_address(0x40, 0x01, 0x30);
+pub(crate) const GS_REVERB_CHARACTER: u32 = gs_address(0x40, 0x01, 0x31);
+pub(crate) const GS_REVERB_PRE_LPF: u32 = gs_address(0x40, 0x01, 0x32);
+pub(crate) const GS_REVERB_LEVEL: u32 = gs_address(0x40, 0x01, 0x33);
+pub(crate) const GS_REVERB_TIME: u32 = gs_address(0x40, 0x01, 0x34);
+pub(crate) const GS_REVERB_DELAY_FEEDBACK: u32 = gs_address(0x40, 0x01, 0x35);
+pub(crate) const GS_REVERB_PRE_DELAY_TIME: u32 = gs_address(0x40, 0x01, 0x37);
+pub(crate) const GS_CHORUS_MACRO: u32 = gs_address(0x40, 0x01, 0x38);
+pub(crate) const GS_CHORUS_LEVEL: u32 = gs_address(0x40, 0x01, 0x3A);
+pub(crate) const GS_CHORUS_FEEDBACK: u32 = gs_address(0x40, 0x01, 0x3B);
+pub(crate) const GS_CHORUS_DELAY: u32 = gs_address(0x40, 0x01, 0x3C);
+pub(crate) const GS_CHORUS_RATE: u32 = gs_address(0x40, 0x01, 0x3D);
+pub(crate) const GS_CHORUS_DEPTH: u32 = gs_address(0x40, 0x01, 0x3E);
+pub(crate) const GS_INSERTION_TYPE_MSB: u32 = gs_address(0x40, 0x03, 0x00);
+pub(crate) const GS_INSERTION_TYPE_LSB: u32 = gs_address(0x40, 0x03, 0x01);
+pub(crate) const GS_INSERTION_PARAMETER_1: u32 = gs_address(0x40, 0x03, 0x03);
+pub(crate) const GS_INSERTION_PARAMETER_20: u32 = gs_address(0x40, 0x03, 0x16);
+
+/// Gets the channel of a GS part parameter (40 1x yy), where x is the part block.
+pub(crate) fn gs_part_parameter(address: u32) -> Option<(u8, u8)> {
+    gs_part_block(address, 0x10)
+}
+
+/// Gets the channel of a GS part EFX parameter (40 4x yy), where x is the part block.
+pub(crate) fn gs_part_efx_parameter(address: u32) -> Option<(u8, u8)> {
+    gs_part_block(address, 0x40)
+}
+
+// Gets the channel and the parameter of an address in a group of part blocks (40 gx yy).
+// The part blocks are ordered as 10, 1, 2, ..., 9, 11, ..., 16.
+fn gs_part_block(address: u32, group: u8) -> Option<(u8, u8)> {
+    let high = (address >> 14) as u8;
+    let middle = ((address >> 7) & 0x7F) as u8;
+    if high != 0x40 || middle & 0xF0 != group {
+        return None;
+    }
+
+    let channel = match middle & 0x0F {
+        0 => 9,
+        block @ 1..=9 => block - 1,
+        block => block,
+    };
+    Some((channel, (address & 0x7F) as u8))
+}
+
+pub(crate) const GS_PART_REVERB_SEND: u8 = 0x22;
+pub(crate) const GS_PART_EFX_ASSIGN: u8 = 0x22;
+
+pub(crate) const XG_INSERTION_TYPE_MSB: u32 = gs_address(0x03, 0x00, 0x00);
+pub(crate) const XG_INSERTION_TYPE_LSB: u32 = gs_address(0x03, 0x00, 0x01);
+pub(crate) const XG_INSERTION_PARAMETER_1: u32 = gs_address(0x03, 0x00, 0x02);
+pub(crate) const XG_INSERTION_PART: u32 = gs_address(0x03, 0x00, 0x0C);
+
+/// A parsed GS Data Set message.
+pub(crate) struct GsDataSet<'a> {
+    address: u32,
+    data: &'a [u8],
+}
+
+impl<'a> GsDataSet<'a> {
+    /// Parses a GS Data Set message.
+    /// The leading `0xF0` and the trailing `0xF7` are optional.
+    /// Returns `None` if the message is not a valid GS Data Set message.
+    pub(crate) fn parse(message: &'a [u8]) -> Option<Self> {
+        let message = message.strip_prefix(&[0xF0]).unwrap_or(message);
+        let message = message.strip_suffix(&[0xF7]).unwrap_or(message);
+
+        let [
+            ROLAND_ID,
+            _device,
+            GS_MODEL_ID,
+            DATA_SET_COMMAND,
+            body @ ..,
+            checksum,
+        ] = message
+        else {
+            return None;
+        };
+
+        if body.len() < 4 || body.iter().any(|value| *value > 0x7F) {
+            return None;
+        }
+
+        // The checksum makes the sum of the address, the data and itself a multiple of 128.
+        let sum = body.iter().fold(*checksum as u32, |acc, x| acc + *x as u32);
+        if !sum.is_multiple_of(128) {
+            return None;
+        }
+
+        Some(Self {
+            address: gs_address(body[0], body[1], body[2]),
+            data: &body[3..],
+        })
+    }
+
+    /// Iterates over the parameters in the message.
+    /// Consecutive data bytes are written to consecutive addresses.
+    pub(crate) fn parameters(&self) -> impl Iterator<Item = (u32, u8)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (self.address + i as u32, *value))
+    }
+}
+
+/// A parsed XG Parameter Change message.
+pub(crate) struct XgParameterChange<'a> {
+    address: u32,
+    data: &'a [u8],
+}
+
+impl<'a> XgParameterChange<'a> {
+    /// Parses an XG Parameter Change message.
+    /// The leading `0xF0` and the trailing `0xF7` are optional.
+    /// Returns `None` if the message is not a valid XG Parameter Change message.
+    pub(crate) fn parse(message: &'a [u8]) -> Option<Self> {
+        let message = message.strip_prefix(&[0xF0]).unwrap_or(message);
+        let message = message.strip_suffix(&[0xF7]).unwrap_or(message);
+
+        let [YAMAHA_ID, device, XG_MODEL_ID, body @ ..] = message else {
+            return None;
+        };
+
+        if device & 0xF0 != PARAMETER_CHANGE_COMMAND
+            || body.len() < 4
+            || body.iter().any(|value| *value > 0x7F)
+        {
+            return None;
+        }
+
+        Some(Self {
+            address: gs_address(body[0], body[1], body[2]),
+            data: &body[3..],
+        })
+    }
+
+    /// Iterates over the parameters in the message.
+    /// Consecutive data bytes are written to consecutive addresses.
+    pub(crate) fn parameters(&self) -> impl Iterator<Item = (u32, u8)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (self.address + i as u32, *value))
+    }
+}
@@ -2,21 +2,28 @@ use core::f32::consts;
 
 use crate::prelude::*;
 
+#[derive(Clone, Copy, Default)]
+struct FilterState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
 pub(crate) struct BiQuadFilter {
     sample_rate: i32,
 
     active: bool,
+    four_pole: bool,
 
-    a0: f32,
-    a1: f32,
-    a2: f32,
-    a3: f32,
-    a4: f32,
+    coefficients: [f32; 5],
+    // The coefficients reached at the end of the ramp, when a playing filter is changed.
+    target: Option<[f32; 5]>,
+    steps: [f32; 5],
+    ramp_remaining: usize,
 
-    x1: f32,
-    x2: f32,
-    y1: f32,
-    y2: f32,
+    // The second stage is only used by the four-pole cascade.
+    stages: [FilterState; 2],
 }
 
 impl BiQuadFilter {
@@ -26,44 +33,177 @@ impl BiQuadFilter {
         Self {
             sample_rate: settings.sample_rate,
             active: false,
-            a0: 0_f32,
-            a1: 0_f32,
-            a2: 0_f32,
-            a3: 0_f32,
-            a4: 0_f32,
-            x1: 0_f32,
-            x2: 0_f32,
-            y1: 0_f32,
-            y2: 0_f32,
+            four_pole: false,
+            coefficients: [0_f32; 5],
+            target: None,
+            steps: [0_f32; 5],
+            ramp_remaining: 0,
+            stages: [FilterState::default(); 2],
         }
     }
 
     pub(crate) fn clear_buffer(&mut self) {
-        self.x1 = 0_f32;
-        self.x2 = 0_f32;
-        self.y1 = 0_f32;
-        self.y2 = 0_f32;
+        self.stages = [FilterState::default(); 2];
     }
 
-    pub(crate) fn set_low_pass_filter(&mut self, cutoff_frequency: f32, resonance: f32) {
-        if cutoff_frequency < 0.499_f32 * self.sample_rate as f32 {
-            self.active = true;
+    // Sets the filter at once, as the filter of a new note.
+    pub(crate) fn set_filter(
+        &mut self,
+        filter_type: FilterType,
+        slope: FilterSlope,
+        cutoff_frequency: f32,
+        resonance: f32,
+    ) {
+        self.four_pole = slope == FilterSlope::FourPole;
+        self.target = None;
+        match self.get_filter_coefficients(filter_type, cutoff_frequency, resonance) {
+            Some(coefficients) => {
+                self.active = true;
+                self.coefficients = coefficients;
+            }
+            None => self.active = false,
+        }
+    }
+
+    // Passes the signal through unchanged until the filter is changed again.
+    pub(crate) fn bypass(&mut self) {
+        self.active = false;
+        self.target = None;
+    }
+
+    // Changes the cutoff or the resonance of a playing filter.
+    // The coefficients move to the new values over the next `ramp_length` samples,
+    // so that the change stays smooth however the samples are split into calls of `process`.
+    pub(crate) fn change_filter(
+        &mut self,
+        filter_type: FilterType,
+        cutoff_frequency: f32,
+        resonance: f32,
+        ramp_length: usize,
+    ) {
+        match self.get_filter_coefficients(filter_type, cutoff_frequency, resonance) {
+            Some(coefficients) if self.active => {
+                let inverse_length = 1_f32 / ramp_length as f32;
+                self.steps = core::array::from_fn(|i| {
+                    inverse_length * (coefficients[i] - self.coefficients[i])
+                });
+                self.ramp_remaining = ramp_length;
+                self.target = Some(coefficients);
+            }
+            Some(coefficients) => {
+                self.active = true;
+                self.coefficients = coefficients;
+                self.target = None;
+            }
+            None => {
+                self.active = false;
+                self.target = None;
+            }
+        }
+    }
+
+    // Gets the normalized coefficients of a stage, or `None` if the filter should be bypassed.
+    fn get_filter_coefficients(
+        &self,
+        filter_type: FilterType,
+        cutoff_frequency: f32,
+        resonance: f32,
+    ) -> Option<[f32; 5]> {
+        let nyquist_limit = 0.499_f32 * self.sample_rate as f32;
+        if filter_type == FilterType::LowPass && cutoff_frequency >= nyquist_limit {
+            return None;
+        }
+
+        // Each stage of the cascade makes half of the resonance peak in decibels.
+        let resonance = if self.four_pole {
+            resonance.sqrt()
+        } else {
+            resonance
+        };
 
-            // This equation gives the Q value which makes the desired resonance peak.
-            // The error of the resultant peak height is less than 3%.
-            let q = resonance
-                - BiQuadFilter::RESONANCE_PEAK_OFFSET / (1_f32 + 6_f32 * (resonance - 1_f32));
+        // This equation gives the Q value which makes the desired resonance peak.
+        // The error of the resultant peak height is less than 3%.
+        let q =
+            resonance - BiQuadFilter::RESONANCE_PEAK_OFFSET / (1_f32 + 6_f32 * (resonance - 1_f32));
 
-            let w = 2_f32 * consts::PI * cutoff_frequency / self.sample_rate as f32;
+        let w = 2_f32 * consts::PI * cutoff_frequency.min(nyquist_limit) / self.sample_rate as f32;
+        let cosw = w.cos();
+        let alpha = w.sin() / (2_f32 * q);
+
+        let (a0, a1, a2, b0, b1, b2) = match filter_type {
+            FilterType::LowPass => (
+                1_f32 + alpha,
+                -2_f32 * cosw,
+                1_f32 - alpha,
+                (1_f32 - cosw) / 2_f32,
+                1_f32 - cosw,
+                (1_f32 - cosw) / 2_f32,
+            ),
+            FilterType::HighPass => (
+                1_f32 + alpha,
+                -2_f32 * cosw,
+                1_f32 - alpha,
+                (1_f32 + cosw) / 2_f32,
+                -(1_f32 + cosw),
+                (1_f32 + cosw) / 2_f32,
+            ),
+            FilterType::BandPass => (
+                1_f32 + alpha,
+                -2_f32 * cosw,
+                1_f32 - alpha,
+                alpha,
+                0_f32,
+                -alpha,
+            ),
+            FilterType::Notch => (
+                1_f32 + alpha,
+                -2_f32 * cosw,
+                1_f32 - alpha,
+                1_f32,
+                -2_f32 * cosw,
+                1_f32,
+            ),
+            FilterType::Peak => {
+                // The resonance is the gain at the center, with a bandwidth of about two octaves.
+                let a = resonance.sqrt();
+                let alpha = w.sin() / 2_f32;
+                (
+                    1_f32 + alpha / a,
+                    -2_f32 * cosw,
+                    1_f32 - alpha / a,
+                    1_f32 + alpha * a,
+                    -2_f32 * cosw,
+                    1_f32 - alpha * a,
+                )
+            }
+        };
+
+        Some(BiQuadFilter::normalize(a0, a1, a2, b0, b1, b2))
+    }
+
+    pub(crate) fn set_low_shelf_filter(&mut self, frequency: f32, gain: f32, q: f32) {
+        self.set_shelf_filter(frequency, gain, q, false);
+    }
+
+    pub(crate) fn set_high_shelf_filter(&mut self, frequency: f32, gain: f32, q: f32) {
+        self.set_shelf_filter(frequency, gain, q, true);
+    }
+
+    pub(crate) fn set_peaking_filter(&mut self, frequency: f32, gain: f32, q: f32) {
+        if gain != 0_f32 && frequency < 0.499_f32 * self.sample_rate as f32 {
+            self.active = true;
+
+            let a = 10_f32.powf(gain / 40_f32);
+            let w = 2_f32 * consts::PI * frequency / self.sample_rate as f32;
             let cosw = w.cos();
             let alpha = w.sin() / (2_f32 * q);
 
-            let b0 = (1_f32 - cosw) / 2_f32;
-            let b1 = 1_f32 - cosw;
-            let b2 = (1_f32 - cosw) / 2_f32;
-            let a0 = 1_f32 + alpha;
+            let b0 = 1_f32 + alpha * a;
+            let b1 = -2_f32 * cosw;
+            let b2 = 1_f32 - alpha * a;
+            let a0 = 1_f32 + alpha / a;
             let a1 = -2_f32 * cosw;
-            let a2 = 1_f32 - alpha;
+            let a2 = 1_f32 - alpha / a;
 
             self.set_coefficients(a0, a1, a2, b0, b1, b2);
         } else {
@@ -71,35 +211,126 @@ impl BiQuadFilter {
         }
     }
 
+    fn set_shelf_filter(&mut self, frequency: f32, gain: f32, q: f32, high: bool) {
+        if gain != 0_f32 && frequency < 0.499_f32 * self.sample_rate as f32 {
+            self.active = true;
+
+            let a = 10_f32.powf(gain / 40_f32);
+            let w = 2_f32 * consts::PI * frequency / self.sample_rate as f32;
+            // The high shelf is the mirror image of the low shelf.
+            let cosw = if high { -w.cos() } else { w.cos() };
+            let beta = 2_f32 * a.sqrt() * w.sin() / (2_f32 * q);
+
+            let b0 = a * ((a + 1_f32) - (a - 1_f32) * cosw + beta);
+            let b1 = 2_f32 * a * ((a - 1_f32) - (a + 1_f32) * cosw);
+            let b2 = a * ((a + 1_f32) - (a - 1_f32) * cosw - beta);
+            let a0 = (a + 1_f32) + (a - 1_f32) * cosw + beta;
+            let a1 = -2_f32 * ((a - 1_f32) + (a + 1_f32) * cosw);
+            let a2 = (a + 1_f32) + (a - 1_f32) * cosw - beta;
+
+            if high {
+                self.set_coefficients(a0, -a1, a2, b0, -b1, b2);
+            } else {
+                self.set_coefficients(a0, a1, a2, b0, b1, b2);
+            }
+        } else {
+            self.active = false;
+        }
+    }
+
     pub(crate) fn process(&mut self, block: &mut [f32]) {
-        let block_length = block.len();
+        let stage_count = if self.four_pole { 2 } else { 1 };
 
-        if self.active {
-            for input in block.iter_mut().take(block_length) {
-                let output = self.a0 * *input + self.a1 * self.x1 + self.a2 * self.x2
-                    - self.a3 * self.y1
-                    - self.a4 * self.y2;
+        if !self.active {
+            self.target = None;
+            for state in self.stages.iter_mut() {
+                for &input in block.iter().rev().take(2).rev() {
+                    state.x2 = state.x1;
+                    state.x1 = input;
+                }
+                state.y2 = state.x2;
+                state.y1 = state.x1;
+            }
+        } else if let Some(target) = self.target {
+            let ramp_length = self.ramp_remaining.min(block.len());
+            self.ramp_remaining -= ramp_length;
 
-                self.x2 = self.x1;
-                self.x1 = *input;
-                self.y2 = self.y1;
-                self.y1 = output;
+            let (ramping, rest) = block.split_at_mut(ramp_length);
+            let start = self.coefficients;
+            for state in self.stages.iter_mut().take(stage_count) {
+                self.coefficients = BiQuadFilter::process_ramp(state, start, self.steps, ramping);
+            }
 
-                *input = output;
+            // The rest of the block is only left when the ramp has ended.
+            if self.ramp_remaining == 0 {
+                self.coefficients = target;
+                self.target = None;
+            }
+            for state in self.stages.iter_mut().take(stage_count) {
+                BiQuadFilter::process_stage(state, self.coefficients, rest);
             }
         } else {
-            self.x2 = block[block_length - 2];
-            self.x1 = block[block_length - 1];
-            self.y2 = self.x2;
-            self.y1 = self.x1;
+            for state in self.stages.iter_mut().take(stage_count) {
+                BiQuadFilter::process_stage(state, self.coefficients, block);
+            }
         }
     }
 
+    fn process_stage(state: &mut FilterState, coefficients: [f32; 5], block: &mut [f32]) {
+        let [a0, a1, a2, a3, a4] = coefficients;
+
+        for input in block.iter_mut() {
+            let output =
+                a0 * *input + a1 * state.x1 + a2 * state.x2 - a3 * state.y1 - a4 * state.y2;
+
+            state.x2 = state.x1;
+            state.x1 = *input;
+            state.y2 = state.y1;
+            state.y1 = output;
+
+            *input = output;
+        }
+    }
+
+    // Processes a stage while the coefficients move by `steps` every sample.
+    // Returns the coefficients reached at the end of the block.
+    fn process_ramp(
+        state: &mut FilterState,
+        coefficients: [f32; 5],
+        steps: [f32; 5],
+        block: &mut [f32],
+    ) -> [f32; 5] {
+        let [mut a0, mut a1, mut a2, mut a3, mut a4] = coefficients;
+        let [d0, d1, d2, d3, d4] = steps;
+
+        for input in block.iter_mut() {
+            a0 += d0;
+            a1 += d1;
+            a2 += d2;
+            a3 += d3;
+            a4 += d4;
+
+            let output =
+                a0 * *input + a1 * state.x1 + a2 * state.x2 - a3 * state.y1 - a4 * state.y2;
+
+            state.x2 = state.x1;
+            state.x1 = *input;
+            state.y2 = state.y1;
+            state.y1 = output;
+
+            *input = output;
+        }
+
+        [a0, a1, a2, a3, a4]
+    }
+
     fn set_coefficients(&mut self, a0: f32, a1: f32, a2: f32, b0: f32, b1: f32, b2: f32) {
-        self.a0 = b0 / a0;
-        self.a1 = b1 / a0;
-        self.a2 = b2 / a0;
-        self.a3 = a1 / a0;
-        self.a4 = a2 / a0;
+        self.four_pole = false;
+        self.target = None;
+        self.coefficients = BiQuadFilter::normalize(a0, a1, a2, b0, b1, b2);
+    }
+
+    fn normalize(a0: f32, a1: f32, a2: f32, b0: f32, b1: f32, b2: f32) -> [f32; 5] {
+        [b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0]
     }
 }
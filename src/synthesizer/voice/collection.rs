@@ -0,0 +1,246 @@
+use core::{
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+use bevy_platform::prelude::*;
+
+use crate::prelude::*;
+
+use super::{RegionPair, SoundControllers, SynthChannel, Voice};
+
+// The active voices, backed by buffers allocated up front for the maximum polyphony,
+// so that starting and stopping notes does not allocate.
+pub(crate) struct VoiceCollection {
+    voices: Vec<Voice>,
+    // The sample buffers of the inactive voices.
+    free_blocks: Vec<Vec<f32>>,
+    // The value indicating whether each voice is still playing after processing the block.
+    #[cfg(feature = "parallel")]
+    playing: Vec<bool>,
+    #[cfg(feature = "parallel")]
+    pub(crate) parallel: bool,
+    // The identifier and the sound controllers of the current note, given to the voices it starts.
+    note: u64,
+    controllers: SoundControllers,
+}
+
+impl VoiceCollection {
+    // The number of extra slots for the voices fading out after being stolen or choked.
+    const DYING_VOICE_COUNT: usize = 8;
+
+    // The smallest number of voices worth handing to a worker thread.
+    #[cfg(feature = "parallel")]
+    const MINIMUM_CHUNK_SIZE: usize = 8;
+
+    pub(crate) fn new(settings: &SynthesizerSettings) -> Self {
+        Self {
+            voices: Vec::with_capacity(
+                settings.maximum_polyphony + VoiceCollection::DYING_VOICE_COUNT,
+            ),
+            free_blocks: (0..settings.maximum_polyphony + VoiceCollection::DYING_VOICE_COUNT)
+                .map(|_| vec![0_f32; settings.block_size])
+                .collect(),
+            #[cfg(feature = "parallel")]
+            playing: Vec::with_capacity(
+                settings.maximum_polyphony + VoiceCollection::DYING_VOICE_COUNT,
+            ),
+            #[cfg(feature = "parallel")]
+            parallel: false,
+            note: 0,
+            controllers: SoundControllers::default(),
+        }
+    }
+
+    // Renders the next block of every voice, `length` samples long, and removes the voices that have finished.
+    #[cfg(not(feature = "parallel"))]
+    pub(crate) fn process(&mut self, data: &[i16], channels: &[SynthChannel], length: usize) {
+        self.retain_mut(|voice| voice.process(data, channels, length));
+    }
+
+    // Renders the next block of the voices across the compute task pool.
+    // The voices are removed afterwards in their original order,
+    // so the result is the same as rendering them one by one.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn process(&mut self, data: &[i16], channels: &[SynthChannel], length: usize) {
+        use bevy_tasks::{ComputeTaskPool, TaskPool};
+
+        if !self.parallel || self.voices.len() <= VoiceCollection::MINIMUM_CHUNK_SIZE {
+            self.retain_mut(|voice| voice.process(data, channels, length));
+            return;
+        }
+
+        let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let chunk_size = self
+            .voices
+            .len()
+            .div_ceil(pool.thread_num().max(1))
+            .max(VoiceCollection::MINIMUM_CHUNK_SIZE);
+
+        self.playing.clear();
+        self.playing.resize(self.voices.len(), false);
+        pool.scope(|scope| {
+            for (voices, playing) in self
+                .voices
+                .chunks_mut(chunk_size)
+                .zip(self.playing.chunks_mut(chunk_size))
+            {
+                scope.spawn(async move {
+                    for (voice, playing) in voices.iter_mut().zip(playing.iter_mut()) {
+                        *playing = voice.process(data, channels, length);
+                    }
+                });
+            }
+        });
+
+        let mut playing = mem::take(&mut self.playing);
+        let mut i = 0;
+        self.retain_mut(|_| {
+            i += 1;
+            playing[i - 1]
+        });
+        playing.clear();
+        self.playing = playing;
+    }
+
+    // Starts a new voice. The caller must keep the number of voices within the maximum polyphony.
+    pub(crate) fn start(
+        &mut self,
+        settings: &SynthesizerSettings,
+        region: &RegionPair,
+        channel: u8,
+        key: u8,
+        velocity: u8,
+        start_offset: usize,
+    ) {
+        let block = self
+            .free_blocks
+            .pop()
+            .unwrap_or_else(|| vec![0_f32; settings.block_size]);
+        let mut voice = Voice::new(
+            settings,
+            region,
+            channel,
+            key,
+            velocity,
+            start_offset,
+            &self.controllers,
+            block,
+        );
+        voice.note = self.note;
+        self.voices.push(voice);
+    }
+
+    // Replaces the voice at `index` with a new voice.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn steal(
+        &mut self,
+        index: usize,
+        settings: &SynthesizerSettings,
+        region: &RegionPair,
+        channel: u8,
+        key: u8,
+        velocity: u8,
+        start_offset: usize,
+    ) {
+        self.stop(index);
+        self.start(settings, region, channel, key, velocity, start_offset);
+    }
+
+    // Stops the voice at `index` quickly.
+    // The voice fades out in one of the extra slots, or is cut if all of them are in use.
+    pub(crate) fn stop(&mut self, index: usize) {
+        let dying = self.voices.iter().filter(|voice| voice.is_dying()).count();
+        if dying < VoiceCollection::DYING_VOICE_COUNT {
+            self.voices[index].kill();
+        } else {
+            let mut voice = self.voices.remove(index);
+            self.free_blocks.push(mem::take(&mut voice.block));
+        }
+    }
+
+    // Starts a new note. The voices started until the next call belong to the note.
+    pub(crate) fn begin_note(&mut self, controllers: SoundControllers) {
+        self.note += 1;
+        self.controllers = controllers;
+    }
+
+    // Stops the oldest notes of the key on the channel until fewer than `limit` notes remain,
+    // so that the next note keeps the number of notes within the limit.
+    pub(crate) fn limit_key(&mut self, channel: u8, key: u8, limit: usize) {
+        let is_key =
+            |voice: &Voice| !voice.is_dying() && voice.channel == channel && voice.key == key;
+        loop {
+            let mut count = 0;
+            let mut oldest = u64::MAX;
+            for (i, voice) in self.voices.iter().enumerate() {
+                if !is_key(voice) {
+                    continue;
+                }
+                // The voices of a layered note are counted once.
+                if !self.voices[..i]
+                    .iter()
+                    .any(|other| is_key(other) && other.note == voice.note)
+                {
+                    count += 1;
+                }
+                oldest = oldest.min(voice.note);
+            }
+            if count < limit {
+                return;
+            }
+
+            while let Some(index) = self
+                .voices
+                .iter()
+                .position(|voice| is_key(voice) && voice.note == oldest)
+            {
+                self.stop(index);
+            }
+        }
+    }
+
+    // Gets the number of voices on the channel, excluding the voices fading out.
+    pub(crate) fn get_channel_playing_count(&self, channel: u8) -> usize {
+        self.voices
+            .iter()
+            .filter(|voice| !voice.is_dying() && voice.channel == channel)
+            .count()
+    }
+
+    // Gets the number of voices, excluding the voices fading out.
+    pub(crate) fn get_playing_count(&self) -> usize {
+        self.voices.iter().filter(|voice| !voice.is_dying()).count()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.retain_mut(|_| false);
+    }
+
+    // Removes the voices for which `f` returns false, keeping the order of the others.
+    // The buffers of the removed voices are kept for the next voices.
+    pub(crate) fn retain_mut(&mut self, mut f: impl FnMut(&mut Voice) -> bool) {
+        let free_blocks = &mut self.free_blocks;
+        self.voices.retain_mut(|voice| {
+            let playing = f(voice);
+            if !playing {
+                free_blocks.push(mem::take(&mut voice.block));
+            }
+            playing
+        });
+    }
+}
+
+impl Deref for VoiceCollection {
+    type Target = [Voice];
+
+    fn deref(&self) -> &Self::Target {
+        &self.voices
+    }
+}
+
+impl DerefMut for VoiceCollection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.voices
+    }
+}
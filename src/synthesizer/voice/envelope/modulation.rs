@@ -1,5 +1,6 @@
 use crate::{
     prelude::{voice::RegionPair, *},
+    synthesizer::SoundControllers,
     utils,
 };
 
@@ -7,6 +8,7 @@ use super::EnvelopeStage;
 
 pub struct ModulationEnvelope {
     sample_rate: i32,
+    curve: EnvelopeCurve,
 
     attack_slope: f64,
     decay_slope: f64,
@@ -27,22 +29,31 @@ pub struct ModulationEnvelope {
 }
 
 impl ModulationEnvelope {
-    pub fn new(settings: &SynthesizerSettings, region: &RegionPair, key: u8, velocity: u8) -> Self {
+    pub(crate) fn new(
+        settings: &SynthesizerSettings,
+        region: &RegionPair,
+        key: u8,
+        velocity: u8,
+        controllers: &SoundControllers,
+    ) -> Self {
         // According to the implementation of TinySoundFont, the attack time should be adjusted by the velocity.
         let delay = region.get_delay_modulation_envelope();
-        let attack = region.get_attack_modulation_envelope() * ((145 - velocity) as f32 / 144_f32);
+        let attack = controllers.attack_time
+            * region.get_attack_modulation_envelope()
+            * ((145 - velocity) as f32 / 144_f32);
         let hold = region.get_hold_modulation_envelope()
             * utils::key_number_to_multiplying_factor(
                 region.get_key_number_to_modulation_envelope_hold(),
                 key,
             );
-        let decay = region.get_decay_modulation_envelope()
+        let decay = controllers.decay_time
+            * region.get_decay_modulation_envelope()
             * utils::key_number_to_multiplying_factor(
                 region.get_key_number_to_modulation_envelope_decay(),
                 key,
             );
         let sustain = 1_f32 - region.get_sustain_modulation_envelope() / 100_f32;
-        let release = region.get_release_modulation_envelope();
+        let release = controllers.release_time * region.get_release_modulation_envelope();
         let attack_slope = 1_f64 / attack as f64;
         let decay_slope = 1_f64 / decay as f64;
         let release_slope = 1_f64 / release as f64;
@@ -60,6 +71,7 @@ impl ModulationEnvelope {
         let value = 0_f32;
         let mut new = Self {
             sample_rate: settings.sample_rate,
+            curve: settings.envelope_curve,
             attack_slope,
             decay_slope,
             release_slope,
@@ -87,10 +99,15 @@ impl ModulationEnvelope {
         if matches!(self.stage, EnvelopeStage::Release { .. }) {
             panic!("already released!");
         }
-        self.stage = EnvelopeStage::Release {
-            time: self.release_time,
-            level: self.value,
-        }
+        self.stage = match self.curve {
+            EnvelopeCurve::Specification => {
+                EnvelopeStage::release(self.processed_sample_count, self.sample_rate, self.value)
+            }
+            EnvelopeCurve::RustySynth => EnvelopeStage::Release {
+                time: self.release_time,
+                level: self.value,
+            },
+        };
     }
 
     pub fn process(&mut self, sample_count: usize) -> Option<f32> {
@@ -146,7 +163,14 @@ impl ModulationEnvelope {
                 (val > utils::NON_AUDIBLE).then_some(val)
             }
             EnvelopeStage::Release { time, level } => {
-                let val = ((level as f64 * self.release_slope * time) as f32).max(0.);
+                // The specification takes the release time for a change from 100% to 0%.
+                let val = match self.curve {
+                    EnvelopeCurve::Specification => {
+                        (level as f64 - self.release_slope * (current_time - time)) as f32
+                    }
+                    EnvelopeCurve::RustySynth => (level as f64 * self.release_slope * time) as f32,
+                }
+                .max(0.);
                 self.value = val;
 
                 Some(val)
@@ -1,5 +1,6 @@
 use crate::{
     prelude::{voice::RegionPair, *},
+    synthesizer::SoundControllers,
     utils,
 };
 
@@ -7,6 +8,7 @@ use super::EnvelopeStage;
 
 pub struct VolumeEnvelope {
     sample_rate: i32,
+    curve: EnvelopeCurve,
 
     attack_slope: f64,
     decay_slope: f64,
@@ -17,6 +19,7 @@ pub struct VolumeEnvelope {
     decay_start_time: f64,
 
     sustain_level: f32,
+    sustain_decibels: f32,
 
     processed_sample_count: usize,
     stage: EnvelopeStage,
@@ -25,26 +28,46 @@ pub struct VolumeEnvelope {
 }
 
 impl VolumeEnvelope {
-    pub fn new(settings: &SynthesizerSettings, region: &RegionPair, key: u8) -> Self {
+    // The level where the decay and the release of the specification end.
+    const MINIMUM_DECIBELS: f32 = -100_f32;
+
+    pub(crate) fn new(
+        settings: &SynthesizerSettings,
+        region: &RegionPair,
+        key: u8,
+        controllers: &SoundControllers,
+    ) -> Self {
         // If the release time is shorter than 10 ms, it will be clamped to 10 ms to avoid pop noise.
         let delay = region.get_delay_volume_envelope();
-        let attack = region.get_attack_volume_envelope();
+        let attack = controllers.attack_time * region.get_attack_volume_envelope();
         let hold = region.get_hold_volume_envelope()
             * utils::key_number_to_multiplying_factor(
                 region.get_key_number_to_volume_envelope_hold(),
                 key,
             );
-        let decay = region.get_decay_volume_envelope()
+        let decay = controllers.decay_time
+            * region.get_decay_volume_envelope()
             * utils::key_number_to_multiplying_factor(
                 region.get_key_number_to_volume_envelope_decay(),
                 key,
             );
-        let sustain = utils::decibels_to_linear(-region.get_sustain_volume_envelope());
-        let release = region.get_release_volume_envelope().max(0.01_f32);
-
+        let sustain_decibels =
+            (-region.get_sustain_volume_envelope()).clamp(VolumeEnvelope::MINIMUM_DECIBELS, 0_f32);
+        let sustain = utils::decibels_to_linear(sustain_decibels);
+        let release =
+            (controllers.release_time * region.get_release_volume_envelope()).max(0.01_f32);
+
+        // The decay and the release are exponential in amplitude for RustySynth,
+        // and in decibels per second for the specification.
+        let curve = settings.envelope_curve;
         let attack_slope = 1_f64 / attack as f64;
-        let decay_slope = -9.226_f64 / decay as f64;
-        let release_slope = -9.226_f64 / release as f64;
+        let (decay_slope, release_slope) = match curve {
+            EnvelopeCurve::Specification => (
+                VolumeEnvelope::MINIMUM_DECIBELS as f64 / decay as f64,
+                VolumeEnvelope::MINIMUM_DECIBELS as f64 / release as f64,
+            ),
+            EnvelopeCurve::RustySynth => (-9.226_f64 / decay as f64, -9.226_f64 / release as f64),
+        };
 
         let attack_start_time = delay as f64;
         let hold_start_time = attack_start_time + attack as f64;
@@ -58,8 +81,10 @@ impl VolumeEnvelope {
         let value = 0_f32;
         let mut new = Self {
             sample_rate: settings.sample_rate,
+            curve,
             release_slope,
             sustain_level,
+            sustain_decibels,
             processed_sample_count,
             stage,
             value,
@@ -130,7 +155,11 @@ impl VolumeEnvelope {
                 Some(0.)
             }
             EnvelopeStage::Attack => {
-                self.value = (self.attack_slope * (current_time - self.attack_start_time)) as f32;
+                let x = self.attack_slope * (current_time - self.attack_start_time);
+                self.value = match self.curve {
+                    EnvelopeCurve::Specification => VolumeEnvelope::convex(x) as f32,
+                    EnvelopeCurve::RustySynth => x as f32,
+                };
                 self.priority = 3.0 + self.value;
                 Some(self.value)
             }
@@ -140,24 +169,71 @@ impl VolumeEnvelope {
                 Some(1_f32)
             }
             EnvelopeStage::Decay => {
-                let val =
-                    (utils::exp_cutoff(self.decay_slope * (current_time - self.decay_start_time))
+                let elapsed = current_time - self.decay_start_time;
+                let val = match self.curve {
+                    EnvelopeCurve::Specification => {
+                        let decibels =
+                            ((self.decay_slope * elapsed) as f32).max(self.sustain_decibels);
+                        if decibels <= VolumeEnvelope::MINIMUM_DECIBELS {
+                            0_f32
+                        } else {
+                            utils::decibels_to_linear(decibels)
+                        }
+                    }
+                    EnvelopeCurve::RustySynth => (utils::exp_cutoff(self.decay_slope * elapsed)
                         as f32)
-                        .max(self.sustain_level);
+                        .max(self.sustain_level),
+                };
                 self.value = val;
                 self.priority = 1.0 + self.value;
-                (val > utils::NON_AUDIBLE).then_some(val)
+                self.get_audible_value()
             }
             EnvelopeStage::Release { time: start, level } => {
-                let val = (level as f64
-                    * utils::exp_cutoff(self.release_slope * (current_time - start)))
-                    as f32;
+                let elapsed = current_time - start;
+                let val = match self.curve {
+                    EnvelopeCurve::Specification => {
+                        let decibels = utils::linear_to_decibels(level)
+                            + (self.release_slope * elapsed) as f32;
+                        if decibels <= VolumeEnvelope::MINIMUM_DECIBELS {
+                            0_f32
+                        } else {
+                            utils::decibels_to_linear(decibels)
+                        }
+                    }
+                    EnvelopeCurve::RustySynth => {
+                        (level as f64 * utils::exp_cutoff(self.release_slope * elapsed)) as f32
+                    }
+                };
                 self.value = val;
                 self.priority = self.value;
-                Some(val)
+                self.get_audible_value()
             }
         }
     }
+
+    // The convex transform of the DLS level 2 specification,
+    // which rises quickly at first and levels off toward the peak.
+    fn convex(x: f64) -> f64 {
+        if x <= 0_f64 {
+            0_f64
+        } else {
+            (1_f64 + (40_f64 / 96_f64) * x.log10()).clamp(0_f64, 1_f64)
+        }
+    }
+
+    // Gets the current value, or `None` if the voice has become inaudible.
+    fn get_audible_value(&self) -> Option<f32> {
+        match self.curve {
+            // The specification lets the envelope fall to −100 dB.
+            EnvelopeCurve::Specification => (self.value > 0_f32).then_some(self.value),
+            EnvelopeCurve::RustySynth => (self.value > utils::NON_AUDIBLE).then_some(self.value),
+        }
+    }
+
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
     pub fn get_priority(&self) -> f32 {
         self.priority
     }
@@ -1,4 +1,4 @@
-use core::f32::consts;
+use core::{f32::consts, ops::Range};
 
 use bevy_platform::prelude::*;
 mod envelope;
@@ -10,15 +10,16 @@ mod oscillator;
 use oscillator::*;
 
 mod bi_quad_filter;
-use bi_quad_filter::*;
+pub(crate) use bi_quad_filter::*;
+
+mod collection;
+pub(crate) use collection::*;
 
 use crate::{prelude::*, utils};
 
-use super::SynthChannel;
+use super::{SoundControllers, SynthChannel};
 
 pub(crate) struct Voice {
-    block_size: usize,
-
     vol_env: VolumeEnvelope,
     mod_env: ModulationEnvelope,
 
@@ -47,11 +48,21 @@ pub(crate) struct Voice {
     pub(crate) exclusive_class: i32,
     pub(crate) channel: u8,
     pub(crate) key: u8,
+    // The note which started the voice. The voices of a layered note share it.
+    pub(crate) note: u64,
 
     note_gain: f32,
 
     cutoff: f32,
     resonance: f32,
+    filter_type: FilterType,
+    // The high-pass and band-pass filters are bypassed while the cutoff stays at its default,
+    // since they would remove almost the whole note there.
+    // The brightness (CC74) still sets the cutoff of these filters.
+    filter_open: bool,
+    // The filter Q in decibels, before the resonance controller (CC71) is applied.
+    filter_q: f32,
+    resonance_offset: f32,
 
     vib_lfo_to_pitch: f32,
     mod_lfo_to_pitch: f32,
@@ -75,15 +86,62 @@ pub(crate) struct Voice {
     voice_state: VoiceState,
     pub(crate) voice_length: usize,
     min_voice_length: usize,
+
+    // The number of silent samples before the note starts in the first block.
+    start_offset: usize,
+
+    // The number of samples left in the fade-out of a killed voice.
+    fade_out: Option<usize>,
+    fade_out_length: usize,
+
+    // The number of samples rendered since the note started.
+    processed_sample_count: usize,
+    // The number of samples left to the next control point.
+    control_remaining: usize,
+    // The pitch of the current control period.
+    pitch: f32,
+    // The vibrato LFO and the pitch change of the modulators at the last control point,
+    // from which the pitch follows the channel at the start of each block.
+    vibrato: f32,
+    modulation_pitch: f32,
+    // The gain of the envelope and the tremolo, which moves linearly between the control points.
+    amplitude: f32,
+    amplitude_target: f32,
+    amplitude_step: f32,
+    // Set when the voice ended within the last block.
+    finished: bool,
+}
+
+// The channel state read once per block.
+// The pitch follows it from the start of the block, and the rest from the next control point.
+struct ChannelControls {
+    vibrato_depth: f32,
+    brightness: f32,
+    pitch_change: f32,
+    resonance_offset: f32,
+    hold_pedal: bool,
 }
 
 impl Voice {
-    pub(crate) fn new(
+    // The length of the fade-out of a stolen or choked voice, in seconds.
+    const FADE_OUT_TIME: f32 = 0.002;
+
+    // The number of samples between the updates of the envelopes, the LFOs, the pitch and the filter.
+    // The control points are counted from the start of the note, independently of the block size,
+    // and restart when the note is released.
+    const CONTROL_PERIOD: usize = 32;
+
+    // Initializes a voice, using `block` as its sample buffer.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
         settings: &SynthesizerSettings,
         region: &RegionPair,
         channel: u8,
         key: u8,
         velocity: u8,
+        start_offset: usize,
+        controllers: &SoundControllers,
+        block: Vec<f32>,
     ) -> Self {
         // this is used elsewhere...really thinking we should
         // just use the region
@@ -103,9 +161,18 @@ impl Voice {
         };
 
         let cutoff = region.get_initial_filter_cutoff_frequency();
-        let resonance = utils::decibels_to_linear(region.get_initial_filter_q());
-
-        let vib_lfo_to_pitch = 0.01_f32 * region.get_vibrato_lfo_to_pitch() as f32;
+        let filter_q = region.get_initial_filter_q();
+        let resonance_offset = controllers.resonance;
+        let resonance = Voice::get_resonance(filter_q, resonance_offset);
+        let filter_type = controllers
+            .filter_type
+            .unwrap_or_else(|| region.get_filter_type());
+        let filter_slope = controllers
+            .filter_slope
+            .unwrap_or_else(|| region.get_filter_slope());
+
+        let vib_lfo_to_pitch =
+            0.01_f32 * (region.get_vibrato_lfo_to_pitch() as f32 + controllers.vibrato_depth);
         let mod_lfo_to_pitch = 0.01_f32 * region.get_modulation_lfo_to_pitch() as f32;
         let mod_env_to_pitch = 0.01_f32 * region.get_modulation_envelope_to_pitch() as f32;
 
@@ -113,6 +180,9 @@ impl Voice {
         let mod_env_to_cutoff = region.get_modulation_envelope_to_filter_cutoff_frequency();
         //todo: derivable and cheap.
         let dynamic_cutoff = mod_lfo_to_cutoff != 0 || mod_env_to_cutoff != 0;
+        let filter_open = matches!(filter_type, FilterType::HighPass | FilterType::BandPass)
+            && region.is_initial_filter_cutoff_maximum()
+            && !dynamic_cutoff;
 
         let mod_lfo_to_volume = region.get_modulation_lfo_to_volume();
         let dynamic_volume = mod_lfo_to_volume > 0.05_f32;
@@ -122,13 +192,14 @@ impl Voice {
         let instrument_reverb = 0.01_f32 * region.get_reverb_effects_send();
         let instrument_chorus = 0.01_f32 * region.get_chorus_effects_send();
 
-        let vol_env = VolumeEnvelope::new(settings, region, key);
-        let mod_env = ModulationEnvelope::new(settings, region, key, velocity);
+        let vol_env = VolumeEnvelope::new(settings, region, key, controllers);
+        let amplitude = vol_env.get_value();
+        let mod_env = ModulationEnvelope::new(settings, region, key, velocity, controllers);
 
         let vib_lfo = Lfo::new(
             settings,
-            region.get_delay_vibrato_lfo(),
-            region.get_frequency_vibrato_lfo(),
+            controllers.vibrato_delay * region.get_delay_vibrato_lfo(),
+            controllers.vibrato_rate * region.get_frequency_vibrato_lfo(),
         );
         let mod_lfo = Lfo::new(
             settings,
@@ -140,7 +211,10 @@ impl Voice {
 
         let mut filter = BiQuadFilter::new(settings);
         filter.clear_buffer();
-        filter.set_low_pass_filter(cutoff, resonance);
+        filter.set_filter(filter_type, filter_slope, cutoff, resonance);
+        if filter_open {
+            filter.bypass();
+        }
 
         let smoothed_cutoff = cutoff;
 
@@ -151,14 +225,13 @@ impl Voice {
         //???
         let min_voice_length = (settings.sample_rate / 500) as usize;
         Self {
-            block_size: settings.block_size,
             vol_env,
             mod_env,
             vib_lfo,
             mod_lfo,
             oscillator,
             filter,
-            block: vec![0_f32; settings.block_size],
+            block,
             previous_mix_gain_left: 0_f32,
             previous_mix_gain_right: 0_f32,
             current_mix_gain_left: 0_f32,
@@ -170,9 +243,14 @@ impl Voice {
             exclusive_class,
             channel,
             key,
+            note: 0,
             note_gain,
             cutoff,
             resonance,
+            filter_type,
+            filter_open,
+            filter_q,
+            resonance_offset,
             vib_lfo_to_pitch,
             mod_lfo_to_pitch,
             mod_env_to_pitch,
@@ -188,6 +266,18 @@ impl Voice {
             voice_state,
             voice_length,
             min_voice_length,
+            start_offset: start_offset.min(settings.block_size - 1),
+            fade_out: None,
+            fade_out_length: ((Voice::FADE_OUT_TIME * settings.sample_rate as f32) as usize).max(1),
+            processed_sample_count: 0,
+            control_remaining: 0,
+            pitch: key as f32,
+            vibrato: 0_f32,
+            modulation_pitch: 0_f32,
+            amplitude,
+            amplitude_target: amplitude,
+            amplitude_step: 0_f32,
+            finished: false,
         }
     }
 
@@ -197,15 +287,26 @@ impl Voice {
         }
     }
 
-    // /// Note stops immediately without a release sound.
-    // ///
-    // /// End is *supposed* to begin playing a release sound. this is the
-    // /// evil twin.
-    // ///
-    // /// This also means it will drop on the next process call.
-    // pub(crate) fn kill(&mut self) {
-    //     self.note_gain = 0_f32;
-    // }
+    /// Note stops quickly without a release sound.
+    ///
+    /// End is *supposed* to begin playing a release sound. this is the
+    /// evil twin. The voice fades out over a few milliseconds to avoid a click,
+    /// and drops on the process call after the fade.
+    pub(crate) fn kill(&mut self) {
+        if self.fade_out.is_none() {
+            // A voice which has not been rendered yet has nothing to fade out.
+            self.fade_out = Some(if self.voice_length == 0 {
+                0
+            } else {
+                self.fade_out_length
+            });
+        }
+    }
+
+    /// Get whether this voice is fading out after being killed.
+    pub(crate) fn is_dying(&self) -> bool {
+        self.fade_out.is_some()
+    }
 
     /// this is only called in one place: render_block. If I return false,
     /// I will die.
@@ -217,50 +318,84 @@ impl Voice {
     /// 3. mod env is just hanging around, so it's definitely not supposed to
     ///    return a bool
     ///
-    pub(crate) fn process(&mut self, data: &[i16], channels: &[SynthChannel]) -> bool {
-        if self.note_gain < utils::NON_AUDIBLE {
+    pub(crate) fn process(
+        &mut self,
+        data: &[i16],
+        channels: &[SynthChannel],
+        block_length: usize,
+    ) -> bool {
+        if self.note_gain < utils::NON_AUDIBLE || self.fade_out == Some(0) || self.finished {
             return false;
         }
 
         let channel_info = &channels[self.channel as usize];
 
-        self.release_if_necessary(channel_info);
-
-        let Some(vol_env) = self.vol_env.process(self.block_size) else {
-            return false;
-        };
-
-        let Some(mod_env) = self.mod_env.process(self.block_size) else {
-            return false;
-        };
-        let vib_lfo = self.vib_lfo.process();
-        let mod_lfo = self.mod_lfo.process();
-
-        let vib_pitch_change =
-            (0.01_f32 * channel_info.get_modulation() + self.vib_lfo_to_pitch) * vib_lfo;
-        let mod_pitch_change = self.mod_lfo_to_pitch * mod_lfo + self.mod_env_to_pitch * mod_env;
-        let channel_pitch_change = channel_info.get_tune() + channel_info.get_pitch_bend();
-        let pitch = self.key as f32 + vib_pitch_change + mod_pitch_change + channel_pitch_change;
-        if !self.oscillator.process(data, &mut self.block[..], pitch) {
-            return false;
+        // For a member channel of an MPE zone, the controllers of the manager channel apply to
+        // the whole zone, while the member channel carries the per-note expression.
+        let manager_info = channel_info
+            .get_mpe_manager()
+            .map(|manager| &channels[manager as usize]);
+        let zone_info = manager_info.unwrap_or(channel_info);
+
+        let mut pressure = channel_info.get_pressure();
+        let mut brightness = channel_info.get_brightness();
+        let mut channel_pitch_change = zone_info.get_tune() + zone_info.get_pitch_bend();
+        if manager_info.is_some() {
+            pressure += zone_info.get_pressure();
+            brightness += zone_info.get_brightness();
+            channel_pitch_change += channel_info.get_pitch_bend();
         }
 
-        if self.dynamic_cutoff {
-            let cents =
-                self.mod_lfo_to_cutoff as f32 * mod_lfo + self.mod_env_to_cutoff as f32 * mod_env;
-            let factor = utils::cents_to_multiplying_factor(cents);
-            let new_cutoff = factor * self.cutoff;
-
-            // The cutoff change is limited within x0.5 and x2 to reduce pop noise.
-            let lower_limit = 0.5_f32 * self.smoothed_cutoff;
-            let upper_limit = 2_f32 * self.smoothed_cutoff;
-
-            self.smoothed_cutoff = new_cutoff.clamp(lower_limit, upper_limit);
+        let controls = ChannelControls {
+            vibrato_depth: 0.01_f32 * (zone_info.get_modulation() + pressure),
+            brightness,
+            pitch_change: channel_pitch_change,
+            // The resonance controller also applies to playing notes.
+            resonance_offset: zone_info.get_resonance(),
+            hold_pedal: channel_info.get_hold_pedal() || zone_info.get_hold_pedal(),
+        };
 
-            self.filter
-                .set_low_pass_filter(self.smoothed_cutoff, self.resonance);
+        // The pitch bend and the vibrato depth apply from the start of the block.
+        self.update_pitch(&controls);
+
+        // A note starting after a short block waits for the next one.
+        let start = self.start_offset.min(block_length);
+        self.start_offset -= start;
+        self.block[..start].fill(0_f32);
+
+        // The block is rendered in segments which end at the control points,
+        // so that the output does not depend on the block size.
+        let mut t = start;
+        while t < block_length {
+            // A release cuts the control period short, so that it takes effect from the next sample.
+            let playing = if self.control_remaining == 0 || self.is_release_due(controls.hold_pedal)
+            {
+                self.update_controls(&controls)
+            } else {
+                true
+            };
+            let length = self.control_remaining.min(block_length - t);
+            let segment = t..t + length;
+
+            if playing
+                && self
+                    .oscillator
+                    .process(data, &mut self.block[segment.clone()], self.pitch)
+            {
+                self.filter.process(&mut self.block[segment.clone()]);
+                self.apply_amplitude(segment);
+                self.processed_sample_count += length;
+                self.control_remaining -= length;
+                t += length;
+            } else if t == start {
+                return false;
+            } else {
+                // The voice ends within the block, and is removed on the next call.
+                self.block[t..block_length].fill(0_f32);
+                self.finished = true;
+                break;
+            }
         }
-        self.filter.process(&mut self.block[..]);
 
         self.previous_mix_gain_left = self.current_mix_gain_left;
         self.previous_mix_gain_right = self.current_mix_gain_right;
@@ -268,17 +403,13 @@ impl Voice {
         self.previous_chorus_send = self.current_chorus_send;
 
         // According to the GM spec, the following value should be squared.
-        let ve = channel_info.get_volume() * channel_info.get_expression();
+        let ve = zone_info.get_volume() * zone_info.get_expression();
         let channel_gain = ve * ve;
 
-        let mut mix_gain = self.note_gain * channel_gain * vol_env;
-        if self.dynamic_volume {
-            let decibels = self.mod_lfo_to_volume * mod_lfo;
-            mix_gain *= utils::decibels_to_linear(decibels);
-        }
+        // The envelope is already applied to the block, so only the channel gain is left to the mix.
+        let mix_gain = self.note_gain * channel_gain;
 
-        let angle =
-            (consts::PI / 200_f32) * (channel_info.get_pan() + self.instrument_pan + 50_f32);
+        let angle = (consts::PI / 200_f32) * (zone_info.get_pan() + self.instrument_pan + 50_f32);
         if angle <= 0_f32 {
             self.current_mix_gain_left = mix_gain;
             self.current_mix_gain_right = 0_f32;
@@ -291,10 +422,10 @@ impl Voice {
         }
 
         self.current_reverb_send =
-            (channel_info.get_reverb_send() + self.instrument_reverb).clamp(0., 1.);
+            (zone_info.get_reverb_send() + self.instrument_reverb).clamp(0., 1.);
 
         self.current_chorus_send =
-            (channel_info.get_chorus_send() + self.instrument_chorus).clamp(0., 1.);
+            (zone_info.get_chorus_send() + self.instrument_chorus).clamp(0., 1.);
 
         if self.voice_length == 0 {
             self.previous_mix_gain_left = self.current_mix_gain_left;
@@ -303,17 +434,113 @@ impl Voice {
             self.previous_chorus_send = self.current_chorus_send;
         }
 
-        self.voice_length += self.block_size;
+        self.voice_length += block_length;
+
+        true
+    }
+
+    // Advances the envelopes and the LFOs to the next control point,
+    // and sets up the pitch, the filter and the amplitude ramp of the coming control period.
+    // Returns false if the voice has become inaudible.
+    fn update_controls(&mut self, controls: &ChannelControls) -> bool {
+        self.release_if_necessary(controls.hold_pedal);
+
+        let Some(vol_env) = self.vol_env.process(Voice::CONTROL_PERIOD) else {
+            return false;
+        };
+
+        let Some(mod_env) = self.mod_env.process(Voice::CONTROL_PERIOD) else {
+            return false;
+        };
+        let vib_lfo = self.vib_lfo.process(Voice::CONTROL_PERIOD);
+        let mod_lfo = self.mod_lfo.process(Voice::CONTROL_PERIOD);
+
+        self.vibrato = vib_lfo;
+        self.modulation_pitch = self.mod_lfo_to_pitch * mod_lfo + self.mod_env_to_pitch * mod_env;
+        self.update_pitch(controls);
+
+        let resonance_changed = controls.resonance_offset != self.resonance_offset;
+        if resonance_changed {
+            self.resonance_offset = controls.resonance_offset;
+            self.resonance = Voice::get_resonance(self.filter_q, controls.resonance_offset);
+        }
+
+        // Brightness is applied through the same path as the modulators,
+        // so that the cutoff change on playing notes is smoothed out.
+        if self.dynamic_cutoff
+            || controls.brightness != 0_f32
+            || self.smoothed_cutoff != self.cutoff
+            || resonance_changed
+        {
+            let cents = self.mod_lfo_to_cutoff as f32 * mod_lfo
+                + self.mod_env_to_cutoff as f32 * mod_env
+                + controls.brightness;
+            let factor = utils::cents_to_multiplying_factor(cents);
+            let new_cutoff = factor * self.cutoff;
+
+            // The cutoff change is limited within x0.5 and x2 to reduce pop noise.
+            let lower_limit = 0.5_f32 * self.smoothed_cutoff;
+            let upper_limit = 2_f32 * self.smoothed_cutoff;
+
+            self.smoothed_cutoff = new_cutoff.clamp(lower_limit, upper_limit);
+
+            if self.filter_open && controls.brightness == 0_f32 {
+                self.filter.bypass();
+            } else {
+                self.filter.change_filter(
+                    self.filter_type,
+                    self.smoothed_cutoff,
+                    self.resonance,
+                    Voice::CONTROL_PERIOD,
+                );
+            }
+        }
+
+        let mut amplitude = vol_env;
+        if self.dynamic_volume {
+            let decibels = self.mod_lfo_to_volume * mod_lfo;
+            amplitude *= utils::decibels_to_linear(decibels);
+        }
+
+        // The amplitude ramp starts exactly from the previous control point,
+        // or from the current amplitude if the control period was cut short.
+        if self.control_remaining == 0 {
+            self.amplitude = self.amplitude_target;
+        }
+        self.amplitude_target = amplitude;
+        self.amplitude_step = (amplitude - self.amplitude) / Voice::CONTROL_PERIOD as f32;
+        self.control_remaining = Voice::CONTROL_PERIOD;
 
         true
     }
 
-    fn release_if_necessary(&mut self, channel_info: &SynthChannel) {
-        if self.voice_length < self.min_voice_length {
-            return;
+    // Applies the amplitude ramp and the fade-out of a killed voice to the block sample by sample.
+    fn apply_amplitude(&mut self, segment: Range<usize>) {
+        for sample in self.block[segment].iter_mut() {
+            self.amplitude += self.amplitude_step;
+            let mut gain = self.amplitude;
+            if let Some(remaining) = self.fade_out {
+                gain *= remaining as f32 / self.fade_out_length as f32;
+                self.fade_out = Some(remaining.saturating_sub(1));
+            }
+            *sample *= gain;
         }
+    }
+
+    fn update_pitch(&mut self, controls: &ChannelControls) {
+        let vib_pitch_change = (controls.vibrato_depth + self.vib_lfo_to_pitch) * self.vibrato;
+        self.pitch =
+            self.key as f32 + vib_pitch_change + self.modulation_pitch + controls.pitch_change;
+    }
+
+    fn is_release_due(&self, hold_pedal: bool) -> bool {
+        self.voice_state == VoiceState::ReleaseRequested
+            && !hold_pedal
+            && self.processed_sample_count >= self.min_voice_length
+    }
 
-        if self.voice_state == VoiceState::ReleaseRequested && !channel_info.get_hold_pedal() {
+    fn release_if_necessary(&mut self, hold_pedal: bool) {
+        if self.is_release_due(hold_pedal) {
             self.vol_env.release();
             self.mod_env.release();
             self.oscillator.release();
@@ -322,6 +549,11 @@ impl Voice {
         }
     }
 
+    // Gets the linear resonance of the filter. The resonance is not lowered below 0 dB.
+    fn get_resonance(filter_q: f32, offset: f32) -> f32 {
+        utils::decibels_to_linear((filter_q + offset).max(0_f32))
+    }
+
     /// Get the priority of this voice for voice stealing decisions
     pub(crate) fn get_priority(&self) -> f32 {
         if self.note_gain < utils::NON_AUDIBLE {
@@ -331,6 +563,24 @@ impl Voice {
         }
     }
 
+    /// Get the output level of the last block.
+    /// A voice which has not been rendered yet is treated as the loudest.
+    pub(crate) fn get_level(&self) -> f32 {
+        if self.voice_length == 0 {
+            f32::MAX
+        } else {
+            let fade = self.fade_out.map_or(1_f32, |remaining| {
+                remaining as f32 / self.fade_out_length as f32
+            });
+            (self.current_mix_gain_left + self.current_mix_gain_right) * self.amplitude * fade
+        }
+    }
+
+    /// Get whether the note of this voice has been released, even if the hold pedal keeps it sounding.
+    pub(crate) fn is_released(&self) -> bool {
+        self.voice_state != VoiceState::Playing
+    }
+
     /// Get the voice length (number of samples processed)
     pub(crate) fn get_voice_length(&self) -> usize {
         self.voice_length
@@ -3,7 +3,6 @@ use crate::prelude::*;
 #[non_exhaustive]
 pub struct Lfo {
     sample_rate: i32,
-    block_size: usize,
 
     active: bool,
 
@@ -26,7 +25,6 @@ impl Lfo {
         }
         Self {
             sample_rate: settings.sample_rate,
-            block_size: settings.block_size,
             active,
             delay: slf_delay,
             period,
@@ -35,12 +33,12 @@ impl Lfo {
         }
     }
 
-    pub fn process(&mut self) -> f32 {
+    pub fn process(&mut self, sample_count: usize) -> f32 {
         if !self.active {
             return self.value;
         }
 
-        self.processed_sample_count += self.block_size;
+        self.processed_sample_count += sample_count;
 
         let current_time = self.processed_sample_count as f64 / self.sample_rate as f64;
 
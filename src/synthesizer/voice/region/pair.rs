@@ -48,10 +48,23 @@ impl<'a> RegionPair<'a> {
         )
     }
 
+    // The cutoff is at the top of its range by default, which only opens the low-pass filter.
+    pub fn is_initial_filter_cutoff_maximum(&self) -> bool {
+        self.gs(GeneratorType::INITIAL_FILTER_CUTOFF_FREQUENCY as usize) >= 13500
+    }
+
     pub fn get_initial_filter_q(&self) -> f32 {
         0.1_f32 * self.gs(GeneratorType::INITIAL_FILTER_Q as usize) as f32
     }
 
+    pub fn get_filter_type(&self) -> FilterType {
+        self.instrument.get_filter_type()
+    }
+
+    pub fn get_filter_slope(&self) -> FilterSlope {
+        self.instrument.get_filter_slope()
+    }
+
     pub fn get_modulation_lfo_to_filter_cutoff_frequency(&self) -> i32 {
         self.gs(GeneratorType::MODULATION_LFO_TO_FILTER_CUTOFF_FREQUENCY as usize)
     }
@@ -0,0 +1,79 @@
+use super::{SynthChannel, voice::Voice};
+
+/// Specifies which voice is replaced when a note starts at the maximum polyphony.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealingPolicy {
+    /// The voice in the latest stage of its volume envelope, with the lowest envelope level.
+    #[default]
+    Envelope,
+    /// The voice which started first.
+    Oldest,
+    /// The voice with the lowest output level.
+    Quietest,
+    /// A voice whose note has been released, before the voices still being held.
+    ReleasedFirst,
+    /// A voice playing the same note on the same channel, before the other voices.
+    SameNoteFirst,
+    /// A voice on the channel with the lowest priority.
+    ///
+    /// See [`Synthesizer::set_channel_priority`](super::Synthesizer::set_channel_priority).
+    LowestChannelPriority,
+}
+
+impl VoiceStealingPolicy {
+    // Finds the voice to be replaced by a new note, only among the voices of its channel if `within_channel` is set.
+    // The candidates are ordered by the policy, and the older voice wins a tie.
+    pub(crate) fn find_candidate(
+        self,
+        voices: &[Voice],
+        channels: &[SynthChannel],
+        channel: u8,
+        key: u8,
+        within_channel: bool,
+    ) -> usize {
+        let rank = |voice: &Voice| -> (f32, f32) {
+            match self {
+                VoiceStealingPolicy::Envelope => (voice.get_priority(), 0_f32),
+                VoiceStealingPolicy::Oldest => (0_f32, 0_f32),
+                VoiceStealingPolicy::Quietest => (voice.get_level(), 0_f32),
+                VoiceStealingPolicy::ReleasedFirst => (
+                    if voice.is_released() { 0_f32 } else { 1_f32 },
+                    voice.get_priority(),
+                ),
+                VoiceStealingPolicy::SameNoteFirst => (
+                    if voice.channel == channel && voice.key == key {
+                        0_f32
+                    } else {
+                        1_f32
+                    },
+                    voice.get_priority(),
+                ),
+                VoiceStealingPolicy::LowestChannelPriority => (
+                    channels[voice.channel as usize].get_priority() as f32,
+                    voice.get_priority(),
+                ),
+            }
+        };
+
+        let mut candidate = 0;
+        let mut lowest = (f32::MAX, f32::MAX);
+        for (i, voice) in voices.iter().enumerate() {
+            // The voices fading out have already been stolen.
+            if voice.is_dying() || (within_channel && voice.channel != channel) {
+                continue;
+            }
+            let rank = rank(voice);
+            if rank < lowest {
+                lowest = rank;
+                candidate = i;
+            } else if rank == lowest {
+                // Same rank - the older one should be more suitable for reuse
+                if voice.get_voice_length() > voices[candidate].get_voice_length() {
+                    candidate = i;
+                }
+            }
+        }
+
+        candidate
+    }
+}
@@ -0,0 +1,201 @@
+use core::cell::Cell;
+use std::alloc::{GlobalAlloc, Layout, System};
+
+use super::utils::*;
+use crate::prelude::*;
+
+// Counts the allocations made by the current thread, so that tests running in parallel
+// do not interfere with each other.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count();
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(|count| count.get());
+    f();
+    ALLOCATIONS.with(|count| count.get()) - before
+}
+
+#[test]
+fn note_events_and_rendering_do_not_allocate() {
+    let settings = SynthesizerSettings {
+        maximum_polyphony: 8,
+        ..Default::default()
+    };
+    let mut synth = Synthesizer::new(synthetic_sound_font(), &settings).unwrap();
+    synth.set_master_chain_params(Some(MasterChainParams::default()));
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+
+    let allocations = count_allocations(|| {
+        for _ in 0..3 {
+            // More notes than the maximum polyphony, so that voices are stolen.
+            for key in 40..60 {
+                synth.process_midi_message(raw_message(&[0x90 | (key % 3), key, 100]));
+            }
+            synth.process_midi_message(raw_message(&[0xB0, 0x07, 90]));
+            synth.process_midi_message(raw_message(&[0xB1, 0x40, 127]));
+            synth.process_midi_message(raw_message(&[0xC2, 0x01]));
+            synth.process_midi_message(raw_message(&[0xE0, 0x00, 0x50]));
+            synth.process_midi_message(raw_message(&[0xD0, 0x40]));
+            synth.render(&mut left, &mut right);
+
+            for key in 40..60 {
+                synth.process_midi_message(raw_message(&[0x80 | (key % 3), key, 0]));
+            }
+            synth.process_midi_message(raw_message(&[0xB1, 0x40, 0]));
+            for _ in 0..10 {
+                synth.render(&mut left, &mut right);
+            }
+
+            synth.note_on(9, 36, 100);
+            synth.note_off_all(true);
+            synth.reset();
+        }
+    });
+
+    assert!(left.iter().any(|x| *x != 0_f32) || right.iter().any(|x| *x != 0_f32));
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn renderer_does_not_allocate() {
+    let synth = Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    let (controller, mut renderer) = synth.split(64);
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+
+    for key in 40..60 {
+        controller
+            .send_at(
+                key as u64 * 10,
+                SynthCommand::MidiMessage(raw_message(&[0x90, key, 100])),
+            )
+            .unwrap();
+    }
+    controller
+        .send_at(500, SynthCommand::MasterVolume(0.3))
+        .unwrap();
+    controller
+        .send_at(5000, SynthCommand::NoteOffAll(false))
+        .unwrap();
+    controller
+        .send_at(
+            6000,
+            SynthCommand::ChorusParams(ChorusType::Flanger.get_params()),
+        )
+        .unwrap();
+    controller
+        .send_at(7000, SynthCommand::SoundFont(synthetic_sound_font()))
+        .unwrap();
+
+    let allocations = count_allocations(|| {
+        for _ in 0..10 {
+            renderer.render(&mut left, &mut right);
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn chorus_parameters_do_not_allocate() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+    synth.note_on(0, 60, 100);
+
+    let allocations = count_allocations(|| {
+        for rate in [0.1, 10.0, 0.4] {
+            synth.set_chorus_params(ChorusParams {
+                delay: 0.1,
+                depth: 0.01,
+                rate,
+                ..Default::default()
+            });
+            synth.render(&mut left, &mut right);
+        }
+        synth.set_chorus_type(ChorusType::Flanger);
+        synth.render(&mut left, &mut right);
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn effects_are_enabled_without_allocating() {
+    let mut synth = synthesizer();
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+
+    let allocations = count_allocations(|| {
+        for value in [true, false, true] {
+            synth.set_enable_reverb(value);
+            synth.set_enable_chorus(value);
+            synth.render(&mut left, &mut right);
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn insertion_effect_sysex_does_not_allocate() {
+    let mut synth = synthesizer();
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+    synth.note_on(2, 60, 100);
+
+    // XG: the rotary speaker, the stereo delay and the tremolo on the part 3, then no part.
+    let messages: [&[u8]; 5] = [
+        &[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x00, 0x45, 0x00, 0xF7],
+        &[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x0C, 0x02, 0xF7],
+        &[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x00, 0x06, 0x00, 0xF7],
+        &[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x00, 0x46, 0x00, 0xF7],
+        &[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x0C, 0x7F, 0xF7],
+    ];
+    let allocations = count_allocations(|| {
+        for message in messages.iter().chain(&messages[..2]) {
+            synth.process_sysex(message);
+            synth.render(&mut left, &mut right);
+        }
+    });
+
+    assert_eq!(allocations, 0);
+    assert_eq!(
+        synth.get_insertion_effect(2),
+        Some(InsertionEffectType::Rotary.get_params())
+    );
+}
@@ -0,0 +1,117 @@
+use crate::synthesizer::{Kernel, Ramp};
+
+// A deterministic signal with both signs and a wide range of magnitudes.
+fn signal(length: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 23) as f32 - 1_f32
+        })
+        .collect()
+}
+
+fn bits(buffer: &[f32]) -> Vec<u32> {
+    buffer.iter().map(|x| x.to_bits()).collect()
+}
+
+// Lengths around the vector widths, so that the remainders are covered.
+const LENGTHS: [usize; 9] = [0, 1, 3, 4, 7, 8, 17, 64, 67];
+
+#[test]
+fn multiply_add_matches_scalar() {
+    for length in LENGTHS {
+        let x = signal(length, 1);
+        let mut expected = signal(length, 2);
+        Kernel::Scalar.multiply_add(0.3_f32, &x, &mut expected);
+
+        for kernel in Kernel::supported() {
+            let mut destination = signal(length, 2);
+            kernel.multiply_add(0.3_f32, &x, &mut destination);
+            assert_eq!(bits(&destination), bits(&expected), "{kernel:?} {length}");
+        }
+    }
+}
+
+#[test]
+fn fused_ramps_match_scalar() {
+    let ramps = [
+        Ramp {
+            gain: 0.5_f32,
+            step: 0_f32,
+        },
+        Ramp {
+            gain: 0.1_f32,
+            step: 0.013_f32,
+        },
+        Ramp {
+            gain: 0.9_f32,
+            step: -0.007_f32,
+        },
+    ];
+
+    for length in LENGTHS {
+        let x = signal(length, 3);
+
+        // The reference mixes each destination separately.
+        let mut expected: Vec<Vec<f32>> = (0..3).map(|i| signal(length, 10 + i)).collect();
+        for (ramp, destination) in ramps.iter().zip(expected.iter_mut()) {
+            Kernel::Scalar.multiply_add_ramps(&x, &mut [(*ramp, &mut destination[..])]);
+        }
+
+        for kernel in Kernel::supported() {
+            let mut destinations: Vec<Vec<f32>> = (0..3).map(|i| signal(length, 10 + i)).collect();
+            let [first, second, third] = &mut destinations[..] else {
+                unreachable!()
+            };
+            kernel.multiply_add_ramps(
+                &x,
+                &mut [
+                    (ramps[0], &mut first[..]),
+                    (Ramp::default(), &mut []),
+                    (ramps[1], &mut second[..]),
+                    (ramps[2], &mut third[..]),
+                ],
+            );
+            for (destination, expected) in destinations.iter().zip(expected.iter()) {
+                assert_eq!(bits(destination), bits(expected), "{kernel:?} {length}");
+            }
+        }
+    }
+}
+
+#[test]
+fn constant_ramp_matches_multiply_add() {
+    let x = signal(67, 4);
+    let mut expected = signal(67, 5);
+    Kernel::Scalar.multiply_add(0.7_f32, &x, &mut expected);
+
+    for kernel in Kernel::supported() {
+        let mut destination = signal(67, 5);
+        let ramp = Ramp {
+            gain: 0.7_f32,
+            step: 0_f32,
+        };
+        kernel.multiply_add_ramps(&x, &mut [(ramp, &mut destination[..])]);
+        assert_eq!(bits(&destination), bits(&expected), "{kernel:?}");
+    }
+}
+
+#[test]
+fn inaudible_ramps_are_skipped() {
+    assert!(Ramp::new(0_f32, 0_f32, 1_f32 / 64_f32).is_none());
+    assert_eq!(
+        Ramp::new(0.5_f32, 0.5_f32, 1_f32 / 64_f32),
+        Some(Ramp {
+            gain: 0.5_f32,
+            step: 0_f32,
+        })
+    );
+    assert_eq!(
+        Ramp::new(0_f32, 1_f32, 1_f32 / 64_f32),
+        Some(Ramp {
+            gain: 0_f32,
+            step: 1_f32 / 64_f32,
+        })
+    );
+}
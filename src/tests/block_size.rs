@@ -0,0 +1,68 @@
+use super::utils::*;
+use crate::prelude::*;
+
+// Plays a note with vibrato and a release, rendered with the given block size.
+fn render_note(block_size: usize) -> Vec<f32> {
+    let mut synth = SynthesizerBuilder::new()
+        .settings(|settings| settings.block_size = block_size)
+        .build();
+    synth.process_midi_message(raw_message(&[0xB0, 0x01, 127]));
+    synth.process_midi_message(raw_message(&[0xB0, 0x4C, 127]));
+    synth.note_on(0, 60, 100);
+
+    let mut left = vec![0_f32; 8192];
+    let mut right = vec![0_f32; 8192];
+    synth.render(&mut left[..4096], &mut right[..4096]);
+    synth.note_off(0, 60);
+    synth.render(&mut left[4096..], &mut right[4096..]);
+    left
+}
+
+#[test]
+fn output_does_not_depend_on_the_block_size() {
+    let expected = render_note(64);
+    for block_size in [8, 32, 256, 1024] {
+        assert_eq!(render_note(block_size), expected, "{block_size}");
+    }
+}
+
+#[test]
+fn attack_is_not_stepped_by_large_blocks() {
+    // An attack of about 100 ms.
+    let mut synth = SynthesizerBuilder::new()
+        .settings(|settings| settings.block_size = 1024)
+        .instrument_generators(&[(GeneratorType::ATTACK_VOLUME_ENVELOPE, -4000)])
+        .build();
+    synth.note_on(0, 69, 100);
+    let mut left = vec![0_f32; 1024];
+    let mut right = vec![0_f32; 1024];
+    synth.render(&mut left, &mut right);
+
+    // The level rises within the first block, instead of holding the level reached at its end.
+    let period_peak = |period: usize| peak(&left[100 * period..100 * (period + 1)]);
+    assert!(period_peak(0) < 0.5 * period_peak(9));
+}
+
+#[test]
+fn release_starts_at_the_next_sample() {
+    // The note-off falls 16 samples after a control point.
+    let render = |release: bool| {
+        let mut synth = SynthesizerBuilder::new()
+            .settings(|settings| settings.block_size = 48)
+            .build();
+        synth.note_on(0, 69, 100);
+        let mut left = vec![0_f32; 480];
+        let mut right = vec![0_f32; 480];
+        synth.render(&mut left[..240], &mut right[..240]);
+        if release {
+            synth.note_off(0, 69);
+        }
+        synth.render(&mut left[240..], &mut right[240..]);
+        left
+    };
+
+    let held = render(false);
+    let released = render(true);
+    let first_difference = (0..held.len()).find(|&t| held[t] != released[t]);
+    assert_eq!(first_difference, Some(240));
+}
@@ -0,0 +1,302 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn gs_data_set(address: [u8; 3], data: &[u8]) -> Vec<u8> {
+    let mut message = vec![0xF0, 0x41, 0x10, 0x42, 0x12];
+    message.extend(address);
+    message.extend(data);
+    let sum = address
+        .iter()
+        .chain(data)
+        .fold(0_u32, |acc, x| acc + *x as u32);
+    message.push(((128 - sum % 128) % 128) as u8);
+    message.push(0xF7);
+    message
+}
+
+#[test]
+fn gs_reverb_macro_selects_reverb_type() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    assert_eq!(synth.get_reverb_params(), ReverbParams::default());
+
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x30], &[0x05]));
+    assert_eq!(synth.get_reverb_params(), ReverbType::Plate.get_params());
+
+    // Pre-delay time and level in a single message.
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x37], &[20]));
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x33], &[32]));
+    let params = synth.get_reverb_params();
+    assert_eq!(params.pre_delay, 0.02);
+    assert_eq!(params.level, 0.5);
+
+    // A message with a wrong checksum is ignored.
+    let mut message = gs_data_set([0x40, 0x01, 0x30], &[0x00]);
+    message[9] ^= 1;
+    synth.process_sysex(&message);
+    assert_eq!(synth.get_reverb_params(), params);
+}
+
+#[test]
+fn reverb_parameter_changes_are_smoothed() {
+    let mut synths: Vec<Synthesizer> = (0..2)
+        .map(|_| {
+            let mut synth =
+                Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+            synth.note_on(0, 69, 100);
+            render_peak(&mut synth, 4410);
+            synth
+        })
+        .collect();
+
+    synths[1].set_reverb_params(ReverbParams {
+        level: 4.0,
+        ..Default::default()
+    });
+
+    // Compares the outputs with and without the level change.
+    let mut max_differences = Vec::new();
+    for frames in [64, 22050, 64] {
+        let mut outputs = Vec::new();
+        for synth in synths.iter_mut() {
+            let mut left = vec![0_f32; frames];
+            let mut right = vec![0_f32; frames];
+            synth.render(&mut left, &mut right);
+            outputs.push(left);
+        }
+        let max_difference = outputs[0]
+            .iter()
+            .zip(outputs[1].iter())
+            .fold(0_f32, |max, (a, b)| max.max((a - b).abs()));
+        max_differences.push(max_difference);
+    }
+
+    // The first block after the change only moves a little towards the new level.
+    assert!(max_differences[2] > 0_f32);
+    assert!(
+        max_differences[0] < 0.2 * max_differences[2],
+        "the level changed abruptly: {max_differences:?}"
+    );
+}
+
+#[test]
+fn gs_chorus_macro_selects_chorus_type() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    assert_eq!(synth.get_chorus_params(), ChorusParams::default());
+
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x38], &[0x05]));
+    assert_eq!(synth.get_chorus_params(), ChorusType::Flanger.get_params());
+
+    // Level, feedback and delay in a single message.
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x3A], &[32, 64, 127]));
+    let params = synth.get_chorus_params();
+    assert_eq!(params.level, 0.5);
+    assert_eq!(params.feedback, 0.5);
+    assert!((params.delay - 0.1).abs() < 1e-9);
+}
+
+#[test]
+fn chorus_parameters_can_be_changed_while_playing() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    synth.process_midi_message(raw_message(&[0xB0, 0x5D, 127]));
+    synth.note_on(0, 69, 100);
+    render_peak(&mut synth, 4410);
+
+    for chorus_type in [
+        ChorusType::ShortDelayFeedback,
+        ChorusType::Flanger,
+        ChorusType::Chorus1,
+    ] {
+        synth.set_chorus_type(chorus_type);
+        let peak = render_peak(&mut synth, 4410);
+        assert!(peak.is_finite() && peak > 0_f32);
+    }
+
+    // Muting the chorus return only leaves the dry signal.
+    synth.set_chorus_params(ChorusParams {
+        level: 0.0,
+        ..Default::default()
+    });
+    assert!(render_peak(&mut synth, 4410) > 0_f32);
+}
+
+#[test]
+fn gs_chorus_depth_is_limited_by_the_delay() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x3C], &[0]));
+    synth.process_sysex(&gs_data_set([0x40, 0x01, 0x3E], &[127]));
+
+    // The shortest delay with the deepest modulation must not read past the buffer.
+    synth.process_midi_message(raw_message(&[0xB0, 0x5D, 127]));
+    synth.note_on(0, 69, 100);
+    let peak = render_peak(&mut synth, 44100);
+    assert!(peak.is_finite() && peak > 0_f32);
+}
+
+#[test]
+fn reverb_and_chorus_can_be_enabled_independently() {
+    let settings = SynthesizerSettings {
+        enable_reverb: false,
+        ..Default::default()
+    };
+    let mut synth = Synthesizer::new(synthetic_sound_font(), &settings).unwrap();
+    assert!(!synth.get_enable_reverb());
+    assert!(synth.get_enable_chorus());
+    assert!(!synth.get_enable_reverb_and_chorus());
+
+    synth.set_enable_reverb(true);
+    synth.set_enable_chorus(false);
+    assert!(synth.get_enable_reverb());
+    assert!(!synth.get_enable_chorus());
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_setting_disables_reverb_and_chorus() {
+    let settings = SynthesizerSettings {
+        enable_reverb_and_chorus: false,
+        ..Default::default()
+    };
+    let synth = Synthesizer::new(synthetic_sound_font(), &settings).unwrap();
+    assert!(!synth.get_enable_reverb());
+    assert!(!synth.get_enable_chorus());
+    assert!(!synth.get_enable_reverb_and_chorus());
+}
+
+#[test]
+fn bypassed_effects_match_disabled_effects() {
+    let dry_settings = SynthesizerSettings {
+        enable_reverb: false,
+        enable_chorus: false,
+        ..Default::default()
+    };
+    let mut dry = Synthesizer::new(synthetic_sound_font(), &dry_settings).unwrap();
+    let mut bypassed =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    bypassed.set_enable_reverb(false);
+    bypassed.set_enable_chorus(false);
+
+    let mut outputs = Vec::new();
+    for synth in [&mut dry, &mut bypassed] {
+        synth.process_midi_message(raw_message(&[0xB0, 0x5B, 127]));
+        synth.process_midi_message(raw_message(&[0xB0, 0x5D, 127]));
+        synth.note_on(0, 69, 100);
+        let mut left = vec![0_f32; 4410];
+        let mut right = vec![0_f32; 4410];
+        synth.render(&mut left, &mut right);
+        outputs.push(left);
+    }
+    assert_eq!(outputs[0], outputs[1]);
+
+    // Re-enabling the reverb brings back the wet signal.
+    bypassed.set_enable_reverb(true);
+    let mut left = vec![0_f32; 4410];
+    let mut right = vec![0_f32; 4410];
+    dry.render(&mut left, &mut right);
+    let dry_left = left.clone();
+    bypassed.render(&mut left, &mut right);
+    assert_ne!(dry_left, left);
+}
+
+fn render_left(synth: &mut Synthesizer, frames: usize) -> Vec<f32> {
+    let mut left = vec![0_f32; frames];
+    let mut right = vec![0_f32; frames];
+    synth.render(&mut left, &mut right);
+    left
+}
+
+#[test]
+fn insertion_effects_process_the_channel() {
+    let mut dry =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    dry.note_on(0, 69, 100);
+    let dry_output = render_left(&mut dry, 8820);
+
+    for effect_type in [
+        InsertionEffectType::Distortion,
+        InsertionEffectType::Overdrive,
+        InsertionEffectType::Rotary,
+        InsertionEffectType::AutoWah,
+        InsertionEffectType::Phaser,
+        InsertionEffectType::Tremolo,
+        InsertionEffectType::StereoDelay,
+    ] {
+        let mut synth =
+            Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+        synth.set_insertion_effect(0, Some(effect_type.get_params()));
+        assert_eq!(
+            synth.get_insertion_effect(0),
+            Some(effect_type.get_params())
+        );
+        synth.note_on(0, 69, 100);
+        let output = render_left(&mut synth, 8820);
+        assert!(output.iter().all(|x| x.is_finite()));
+        assert!(output.iter().any(|x| x.abs() > 0.01), "{effect_type:?}");
+        assert_ne!(output, dry_output, "{effect_type:?}");
+    }
+}
+
+#[test]
+fn insertion_effects_do_not_affect_other_channels() {
+    let mut outputs = Vec::new();
+    for insertion in [None, Some(InsertionEffectType::Distortion.get_params())] {
+        let mut synth =
+            Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+        synth.set_insertion_effect(1, insertion);
+        synth.note_on(0, 69, 100);
+        outputs.push(render_left(&mut synth, 4410));
+    }
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn insertion_effects_are_configured_by_sysex() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+
+    // GS: Distortion with the level of 64, assigned to the part 1 (channel 0).
+    synth.process_sysex(&gs_data_set([0x40, 0x03, 0x00], &[0x01, 0x11]));
+    synth.process_sysex(&gs_data_set([0x40, 0x03, 0x16], &[64]));
+    assert_eq!(synth.get_insertion_effect(0), None);
+    synth.process_sysex(&gs_data_set([0x40, 0x41, 0x22], &[0x01]));
+    let params = synth.get_insertion_effect(0).unwrap();
+    assert_eq!(params.effect_type, InsertionEffectType::Distortion);
+    assert_eq!(params.level, 64_f32 / 127_f32);
+
+    // The part 10 is the channel 9.
+    synth.process_sysex(&gs_data_set([0x40, 0x40, 0x22], &[0x01]));
+    assert!(synth.get_insertion_effect(9).is_some());
+    synth.process_sysex(&gs_data_set([0x40, 0x41, 0x22], &[0x00]));
+    assert_eq!(synth.get_insertion_effect(0), None);
+
+    // XG: Rotary speaker assigned to the part 3, then moved to the part 4.
+    synth.process_sysex(&[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x00, 0x45, 0x00, 0xF7]);
+    synth.process_sysex(&[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x0C, 0x02, 0xF7]);
+    assert_eq!(
+        synth.get_insertion_effect(2),
+        Some(InsertionEffectType::Rotary.get_params())
+    );
+    synth.process_sysex(&[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x0C, 0x03, 0xF7]);
+    assert_eq!(synth.get_insertion_effect(2), None);
+    assert!(synth.get_insertion_effect(3).is_some());
+    synth.process_sysex(&[0xF0, 0x43, 0x10, 0x4C, 0x03, 0x00, 0x0C, 0x7F, 0xF7]);
+    assert_eq!(synth.get_insertion_effect(3), None);
+}
+
+#[test]
+fn gs_reverb_send_does_not_assign_insertion_effects() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    let params = InsertionEffectType::Distortion.get_params();
+    synth.set_insertion_effect(0, Some(params));
+
+    // The reverb send level of the part 1 (40 11 22), set to zero and then raised.
+    synth.process_sysex(&gs_data_set([0x40, 0x11, 0x22], &[0x00]));
+    assert_eq!(synth.get_insertion_effect(0), Some(params));
+    synth.process_sysex(&gs_data_set([0x40, 0x12, 0x22], &[0x7F]));
+    assert_eq!(synth.get_insertion_effect(1), None);
+    assert_eq!(synth.get_insertion_effect(0), Some(params));
+}
@@ -0,0 +1,108 @@
+use super::utils::*;
+use crate::prelude::*;
+
+// Builds a synthesizer using the envelope curve, with the volume envelope generators changed.
+fn builder(curve: EnvelopeCurve) -> SynthesizerBuilder {
+    SynthesizerBuilder::new().settings(|settings| settings.envelope_curve = curve)
+}
+
+// Renders and gets the peak level of each window of `length` samples in decibels.
+fn window_levels(synth: &mut Synthesizer, count: usize, length: usize) -> Vec<f32> {
+    (0..count)
+        .map(|_| 20_f32 * render_peak(synth, length).log10())
+        .collect()
+}
+
+#[test]
+fn release_falls_linearly_in_decibels() {
+    // The release of the synthetic SoundFont is about 100 ms.
+    // The windows are 5 periods of the note long, so that each peak is taken at the same phase.
+    let drop = |curve: EnvelopeCurve| {
+        let mut synth = builder(curve).build();
+        synth.note_on(0, 69, 100);
+        render_peak(&mut synth, 4410);
+        synth.note_off(0, 69);
+        let levels = window_levels(&mut synth, 6, 500);
+        let drops: Vec<f32> = levels.windows(2).map(|w| w[0] - w[1]).collect();
+        for d in &drops {
+            assert!((d - drops[1]).abs() < 1_f32, "{drops:?}");
+        }
+        drops[1]
+    };
+
+    // 100 dB over the release time for the specification, and 80 dB for RustySynth.
+    let release_time = 2_f32.powf(-3986_f32 / 1200_f32);
+    let window_time = 500_f32 / 44100_f32;
+    let specification = drop(EnvelopeCurve::Specification);
+    let rusty_synth = drop(EnvelopeCurve::RustySynth);
+    assert!(
+        (specification - 100_f32 * window_time / release_time).abs() < 0.2,
+        "{specification}"
+    );
+    assert!(
+        (rusty_synth - 80_f32 * window_time / release_time).abs() < 0.2,
+        "{rusty_synth}"
+    );
+}
+
+#[test]
+fn release_ends_at_minus_100_decibels() {
+    let mut synth = builder(EnvelopeCurve::Specification).build();
+    synth.note_on(0, 69, 100);
+    render_peak(&mut synth, 4410);
+    synth.note_off(0, 69);
+    // The levels are relative to the level of the note before the release.
+    let levels = window_levels(&mut synth, 9, 441);
+    assert_eq!(synth.get_active_voice_count(), 1);
+    assert!(levels[8] < levels[0] - 80_f32, "{levels:?}");
+    window_levels(&mut synth, 3, 441);
+    assert_eq!(synth.get_active_voice_count(), 0);
+}
+
+#[test]
+fn rusty_synth_release_ends_when_inaudible() {
+    let mut synth = builder(EnvelopeCurve::RustySynth).build();
+    synth.note_on(0, 69, 100);
+    render_peak(&mut synth, 4410);
+    synth.note_off(0, 69);
+    // The release falls by 80 dB over about 100 ms.
+    window_levels(&mut synth, 5, 441);
+    assert_eq!(synth.get_active_voice_count(), 1);
+    window_levels(&mut synth, 7, 441);
+    assert_eq!(synth.get_active_voice_count(), 0);
+}
+
+#[test]
+fn attack_is_convex_in_amplitude() {
+    // An attack of about 400 ms, measured at a quarter of it.
+    let quarter_level = |curve: EnvelopeCurve| {
+        let mut synth = builder(curve)
+            .instrument_generators(&[(GeneratorType::ATTACK_VOLUME_ENVELOPE, -1600)])
+            .build();
+        synth.note_on(0, 69, 100);
+        let levels = window_levels(&mut synth, 50, 441);
+        levels[9] - levels[49]
+    };
+
+    let specification = quarter_level(EnvelopeCurve::Specification);
+    let rusty_synth = quarter_level(EnvelopeCurve::RustySynth);
+    assert!(specification > -3_f32, "{specification}");
+    assert!(
+        (rusty_synth - 20_f32 * 0.25_f32.log10()).abs() < 1_f32,
+        "{rusty_synth}"
+    );
+}
+
+#[test]
+fn sustain_is_expressed_in_centibels() {
+    let level = |sustain: i16| {
+        let mut synth = builder(EnvelopeCurve::Specification)
+            .instrument_generators(&[(GeneratorType::SUSTAIN_VOLUME_ENVELOPE, sustain)])
+            .build();
+        synth.note_on(0, 69, 100);
+        window_levels(&mut synth, 10, 441)[9]
+    };
+
+    let attenuation = level(0) - level(200);
+    assert!((attenuation - 20_f32).abs() < 0.5, "{attenuation}");
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use super::utils::*;
+use crate::{prelude::*, synthesizer::voice::BiQuadFilter};
+
+// The cutoff of the sample frequency (441 Hz at key 69) in cents.
+const NOTE_CUTOFF: i16 = 6900;
+
+// Builds the synthetic SoundFont with the filter of its region changed.
+fn sound_font(cutoff: Option<i16>, filter: Option<(FilterType, FilterSlope)>) -> Arc<SoundFont> {
+    let mut sound_font = (*synthetic_sound_font()).clone();
+    for region in sound_font.get_instruments_mut()[0].get_regions_mut() {
+        if let Some(cutoff) = cutoff {
+            region.gs[GeneratorType::INITIAL_FILTER_CUTOFF_FREQUENCY as usize] = cutoff;
+        }
+        if let Some((filter_type, slope)) = filter {
+            region.set_filter(filter_type, slope);
+        }
+    }
+    Arc::new(sound_font)
+}
+
+// Plays key 69 and gets the peak after the filter has settled.
+fn note_peak(synth: &mut Synthesizer) -> f32 {
+    synth.note_on(0, 69, 100);
+    render_left(synth, 4096);
+    peak(&render_left(synth, 4096))
+}
+
+fn channel_filter_peak(
+    cutoff: Option<i16>,
+    filter_type: FilterType,
+    slope: FilterSlope,
+    controllers: &[(u8, u8)],
+) -> f32 {
+    let mut synth = SynthesizerBuilder::new()
+        .sound_font(sound_font(cutoff, None))
+        .build();
+    synth.set_channel_filter_type(0, Some(filter_type));
+    synth.set_channel_filter_slope(0, Some(slope));
+    for (controller, value) in controllers {
+        synth.process_midi_message(raw_message(&[0xB0, *controller, *value]));
+    }
+    note_peak(&mut synth)
+}
+
+fn open_peak() -> f32 {
+    note_peak(&mut synthesizer())
+}
+
+#[test]
+fn high_pass_removes_a_note_below_the_cutoff() {
+    let open = open_peak();
+    let cutoff = Some(NOTE_CUTOFF + 2400);
+    let two_pole = channel_filter_peak(cutoff, FilterType::HighPass, FilterSlope::TwoPole, &[]);
+    let four_pole = channel_filter_peak(cutoff, FilterType::HighPass, FilterSlope::FourPole, &[]);
+    assert!(two_pole < 0.1 * open, "{two_pole} {open}");
+    assert!(four_pole < 0.1 * two_pole, "{four_pole} {two_pole}");
+}
+
+#[test]
+fn four_pole_low_pass_is_steeper() {
+    let cutoff = Some(NOTE_CUTOFF - 1200);
+    let two_pole = channel_filter_peak(cutoff, FilterType::LowPass, FilterSlope::TwoPole, &[]);
+    let four_pole = channel_filter_peak(cutoff, FilterType::LowPass, FilterSlope::FourPole, &[]);
+    assert!(two_pole < 0.5 * open_peak());
+    assert!(four_pole < 0.5 * two_pole, "{four_pole} {two_pole}");
+}
+
+#[test]
+fn band_pass_and_notch_act_around_the_cutoff() {
+    let open = open_peak();
+    let cutoff = Some(NOTE_CUTOFF);
+
+    let band_pass = channel_filter_peak(cutoff, FilterType::BandPass, FilterSlope::TwoPole, &[]);
+    assert!((band_pass / open - 1_f32).abs() < 0.1, "{band_pass} {open}");
+
+    let away = Some(NOTE_CUTOFF + 2400);
+    let band_pass = channel_filter_peak(away, FilterType::BandPass, FilterSlope::TwoPole, &[]);
+    assert!(band_pass < 0.5 * open, "{band_pass} {open}");
+
+    let notch = channel_filter_peak(cutoff, FilterType::Notch, FilterSlope::TwoPole, &[]);
+    assert!(notch < 0.05 * open, "{notch} {open}");
+}
+
+#[test]
+fn peak_boosts_the_cutoff_by_the_resonance() {
+    let cutoff = Some(NOTE_CUTOFF);
+    let flat = channel_filter_peak(cutoff, FilterType::Peak, FilterSlope::TwoPole, &[]);
+    assert!((flat / open_peak() - 1_f32).abs() < 0.01);
+
+    let boosted = channel_filter_peak(
+        cutoff,
+        FilterType::Peak,
+        FilterSlope::TwoPole,
+        &[(0x47, 127)],
+    );
+    assert!(boosted > 8_f32 * flat, "{boosted} {flat}");
+
+    // The cascade keeps the total boost.
+    let cascaded = channel_filter_peak(
+        cutoff,
+        FilterType::Peak,
+        FilterSlope::FourPole,
+        &[(0x47, 127)],
+    );
+    assert!(
+        (cascaded / boosted - 1_f32).abs() < 0.1,
+        "{cascaded} {boosted}"
+    );
+}
+
+#[test]
+fn region_filter_is_overridden_by_the_channel() {
+    let high_pass = Some((FilterType::HighPass, FilterSlope::TwoPole));
+    let region = sound_font(Some(NOTE_CUTOFF + 2400), high_pass);
+    assert_eq!(
+        region.get_instruments()[0].get_regions()[0].get_filter_type(),
+        FilterType::HighPass
+    );
+
+    let open = open_peak();
+    let region_peak = note_peak(&mut SynthesizerBuilder::new().sound_font(region.clone()).build());
+    assert!(region_peak < 0.1 * open, "{region_peak} {open}");
+
+    let mut synth = SynthesizerBuilder::new().sound_font(region).build();
+    synth.set_channel_filter_type(0, Some(FilterType::LowPass));
+    let low_pass = note_peak(&mut synth);
+    assert!(low_pass > 0.9 * open, "{low_pass} {open}");
+}
+
+#[test]
+fn high_pass_and_band_pass_are_open_at_the_default_cutoff() {
+    let open = open_peak();
+    for filter_type in [FilterType::HighPass, FilterType::BandPass] {
+        let filter = Some((filter_type, FilterSlope::TwoPole));
+        let region_peak = note_peak(
+            &mut SynthesizerBuilder::new()
+                .sound_font(sound_font(None, filter))
+                .build(),
+        );
+        assert!(
+            region_peak > 0.99 * open,
+            "{filter_type:?} {region_peak} {open}"
+        );
+
+        let channel_peak = channel_filter_peak(None, filter_type, FilterSlope::FourPole, &[]);
+        assert!(
+            channel_peak > 0.99 * open,
+            "{filter_type:?} {channel_peak} {open}"
+        );
+    }
+}
+
+#[test]
+fn nrpn_selects_the_filter() {
+    let mut synth = synthesizer();
+    for (controller, value) in [(0x63, 1), (0x62, 0x28), (0x06, 1), (0x62, 0x29), (0x06, 1)] {
+        synth.process_midi_message(raw_message(&[0xB0, controller, value]));
+    }
+    assert_eq!(synth.get_channel_filter_type(0), Some(FilterType::HighPass));
+    assert_eq!(
+        synth.get_channel_filter_slope(0),
+        Some(FilterSlope::FourPole)
+    );
+
+    // Unknown values and other NRPNs are ignored.
+    for (controller, value) in [(0x62, 0x28), (0x06, 5), (0x62, 0x20), (0x06, 0)] {
+        synth.process_midi_message(raw_message(&[0xB0, controller, value]));
+    }
+    assert_eq!(synth.get_channel_filter_type(0), Some(FilterType::HighPass));
+
+    synth.reset();
+    assert_eq!(synth.get_channel_filter_type(0), None);
+    assert_eq!(synth.get_channel_filter_slope(0), None);
+}
+
+#[test]
+fn cutoff_changes_are_interpolated() {
+    let settings = SynthesizerSettings::default();
+    let input: Vec<f32> = (0..64)
+        .map(|t| (2_f32 * core::f32::consts::PI * t as f32 / 16_f32).sin())
+        .collect();
+
+    let filter = || {
+        let mut filter = BiQuadFilter::new(&settings);
+        filter.set_filter(FilterType::LowPass, FilterSlope::TwoPole, 500_f32, 1_f32);
+        filter.process(&mut input.clone());
+        filter
+    };
+    let next_block = |filter: &mut BiQuadFilter| {
+        let mut block = input.clone();
+        filter.process(&mut block);
+        block
+    };
+
+    let mut unchanged = filter();
+    let unchanged = next_block(&mut unchanged);
+
+    let mut abrupt = filter();
+    abrupt.set_filter(FilterType::LowPass, FilterSlope::TwoPole, 8000_f32, 1_f32);
+    let abrupt = next_block(&mut abrupt);
+
+    let mut smooth = filter();
+    smooth.change_filter(FilterType::LowPass, 8000_f32, 1_f32, 64);
+    let smooth_block = next_block(&mut smooth);
+
+    // The smoothed block starts near the old response and ends near the new one.
+    assert!((smooth_block[0] - unchanged[0]).abs() < 0.1 * (abrupt[0] - unchanged[0]).abs());
+    assert!((smooth_block[63] - abrupt[63]).abs() < 0.1 * (unchanged[63] - abrupt[63]).abs());
+
+    // The ramp continues across the calls.
+    let mut split = filter();
+    split.change_filter(FilterType::LowPass, 8000_f32, 1_f32, 64);
+    let mut split_block = input.clone();
+    let (first, second) = split_block.split_at_mut(24);
+    split.process(first);
+    split.process(second);
+    assert_eq!(split_block, smooth_block);
+}
@@ -0,0 +1,71 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn loud_synthesizer(master_chain: Option<MasterChainParams>) -> Synthesizer {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    synth.set_master_volume(4_f32);
+    synth.set_master_chain_params(master_chain);
+    for key in [57, 60, 64, 69, 72, 76] {
+        synth.note_on(0, key, 127);
+    }
+    synth
+}
+
+#[test]
+fn master_chain_is_disabled_by_default() {
+    let synth = Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    assert_eq!(synth.get_master_chain_params(), None);
+}
+
+#[test]
+fn limiter_keeps_output_below_ceiling() {
+    assert!(render_peak(&mut loud_synthesizer(None), 22050) > 1_f32);
+
+    let params = MasterChainParams::default();
+    let mut synth = loud_synthesizer(Some(params));
+    assert_eq!(synth.get_master_chain_params(), Some(params));
+
+    let ceiling = 10_f32.powf(params.limiter.unwrap().ceiling / 20_f32);
+    let peak = render_peak(&mut synth, 22050);
+    assert!(peak > 0.5 * ceiling);
+    assert!(peak <= ceiling, "{peak} exceeds {ceiling}");
+}
+
+#[test]
+fn flat_master_chain_does_not_change_output() {
+    let mut outputs = Vec::new();
+    for master_chain in [
+        None,
+        Some(MasterChainParams {
+            limiter: None,
+            ..Default::default()
+        }),
+    ] {
+        let mut synth = loud_synthesizer(master_chain);
+        let mut left = vec![0_f32; 4410];
+        let mut right = vec![0_f32; 4410];
+        synth.render(&mut left, &mut right);
+        outputs.push(left);
+    }
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn compressor_and_eq_reduce_level() {
+    let uncompressed = render_peak(&mut loud_synthesizer(None), 22050);
+
+    let mut params = MasterChainParams {
+        compressor: Some(CompressorParams::default()),
+        limiter: None,
+        ..Default::default()
+    };
+    let compressed = render_peak(&mut loud_synthesizer(Some(params)), 22050);
+    assert!(compressed < 0.5 * uncompressed);
+
+    // Cuts around the frequency of the synthetic sample (441 Hz).
+    params.compressor = None;
+    params.eq_bands[1].gain = -12_f32;
+    let equalized = render_peak(&mut loud_synthesizer(Some(params)), 22050);
+    assert!(equalized < 0.7 * uncompressed);
+}
@@ -1,4 +1,23 @@
 mod utils;
+
+mod allocations;
+mod array_math;
+mod block_size;
+mod effects;
+mod envelopes;
+mod filters;
+mod master;
+mod mpe;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod polyphony;
+mod realtime;
+mod render;
+mod report;
+mod sequencer;
+mod sound_controllers;
+mod voice_stealing;
+mod wav;
 use midix::prelude::*;
 use utils::*;
 
@@ -0,0 +1,98 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn configure_lower_zone(synth: &mut Synthesizer, member_channel_count: u8) {
+    // RPN 6 on the manager channel.
+    synth.process_midi_message(raw_message(&[0xB0, 0x65, 0x00]));
+    synth.process_midi_message(raw_message(&[0xB0, 0x64, 0x06]));
+    synth.process_midi_message(raw_message(&[0xB0, 0x06, member_channel_count]));
+}
+
+#[test]
+fn mpe_configuration_message_defines_zones() {
+    let mut synth = synthesizer();
+
+    configure_lower_zone(&mut synth, 7);
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Lower), 7);
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Upper), 0);
+
+    // The upper zone takes the remaining channels, then shrinks the lower zone.
+    synth.set_mpe_zone(MpeZone::Upper, 7);
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Lower), 7);
+    synth.set_mpe_zone(MpeZone::Upper, 10);
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Lower), 4);
+
+    // RPN 6 on a non-manager channel is ignored.
+    synth.process_midi_message(raw_message(&[0xB3, 0x65, 0x00]));
+    synth.process_midi_message(raw_message(&[0xB3, 0x64, 0x06]));
+    synth.process_midi_message(raw_message(&[0xB3, 0x06, 0x00]));
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Lower), 4);
+
+    synth.reset();
+    assert_eq!(synth.get_mpe_member_channel_count(MpeZone::Upper), 0);
+}
+
+#[test]
+fn mpe_member_pitch_bend_is_per_note() {
+    let mut synth = synthesizer();
+    configure_lower_zone(&mut synth, 15);
+
+    synth.note_on(2, 69, 100);
+    count_zero_crossings(&mut synth, 4410);
+    let unbent = count_zero_crossings(&mut synth, 4410);
+
+    // +12 semitones with the default member range of 48 semitones, sent to another note.
+    synth.process_midi_message(raw_message(&[0xE1, 0x00, 0x50]));
+    assert_eq!(count_zero_crossings(&mut synth, 4410), unbent);
+
+    synth.process_midi_message(raw_message(&[0xE2, 0x00, 0x50]));
+    count_zero_crossings(&mut synth, 4410);
+    let bent = count_zero_crossings(&mut synth, 4410);
+    assert!(
+        bent.abs_diff(2 * unbent) <= 2,
+        "expected an octave up: {unbent} -> {bent}"
+    );
+}
+
+#[test]
+fn channels_leaving_a_zone_get_the_default_pitch_bend_range() {
+    // Bends channel 2 by the maximum and gets the frequency ratio of the note.
+    let bend_ratio = |synth: &mut Synthesizer| {
+        synth.note_on(2, 69, 100);
+        count_zero_crossings(synth, 4410);
+        let unbent = count_zero_crossings(synth, 4410);
+        synth.process_midi_message(raw_message(&[0xE2, 0x7F, 0x7F]));
+        count_zero_crossings(synth, 4410);
+        let bent = count_zero_crossings(synth, 4410);
+        synth.note_off_all(true);
+        bent as f32 / unbent as f32
+    };
+    // +2 semitones.
+    let default_ratio = bend_ratio(&mut synthesizer());
+
+    let mut synth = synthesizer();
+    configure_lower_zone(&mut synth, 7);
+    configure_lower_zone(&mut synth, 0);
+    assert_eq!(bend_ratio(&mut synth), default_ratio);
+
+    // Shrinking the zone also releases the channels left out.
+    let mut synth = synthesizer();
+    configure_lower_zone(&mut synth, 7);
+    configure_lower_zone(&mut synth, 1);
+    assert_eq!(bend_ratio(&mut synth), default_ratio);
+}
+
+#[test]
+fn mpe_manager_controllers_apply_to_zone() {
+    let mut synth = synthesizer();
+    configure_lower_zone(&mut synth, 3);
+
+    synth.note_on(1, 69, 100);
+    synth.note_on(3, 72, 100);
+    assert!(render_peak(&mut synth, 4410) > 0.01);
+
+    // Channel volume on the manager channel silences every member channel.
+    synth.process_midi_message(raw_message(&[0xB0, 0x07, 0x00]));
+    render_peak(&mut synth, 441);
+    assert!(render_peak(&mut synth, 4410) < 1.0e-4);
+}
@@ -0,0 +1,83 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn render(parallel: bool) -> (Vec<f32>, Vec<f32>, MultiOutputBuffers) {
+    let settings = SynthesizerSettings {
+        enable_multi_output: true,
+        ..Default::default()
+    };
+    let mut synth = Synthesizer::new(synthetic_sound_font(), &settings).unwrap();
+    synth.set_parallel(parallel);
+    synth.set_master_chain_params(Some(MasterChainParams::default()));
+    synth.set_insertion_effect(
+        1,
+        Some(InsertionEffectParams {
+            effect_type: InsertionEffectType::Phaser,
+            drive: 0_f32,
+            rate: 0.5_f32,
+            depth: 0.8_f32,
+            delay: 0_f32,
+            feedback: 0.5_f32,
+            mix: 0.5_f32,
+            level: 1_f32,
+        }),
+    );
+
+    // Enough voices to be split across several worker threads.
+    for channel in 0..4 {
+        synth.process_midi_message(raw_message(&[0xB0 | channel, 91, 100]));
+        synth.process_midi_message(raw_message(&[0xB0 | channel, 93, 100]));
+        for key in 40..60 {
+            synth.note_on(channel, key, 60 + key);
+        }
+    }
+
+    let mut left = vec![0_f32; 4000];
+    let mut right = vec![0_f32; 4000];
+    synth.render(&mut left[..2000], &mut right[..2000]);
+    for key in (40..60).step_by(2) {
+        synth.note_off(0, key);
+    }
+    synth.render(&mut left[2000..], &mut right[2000..]);
+
+    let mut buffers = MultiOutputBuffers::new(2000, true);
+    synth.render_multi(&mut buffers);
+
+    (left, right, buffers)
+}
+
+#[test]
+fn parallel_rendering_is_bit_identical() {
+    let (left, right, buffers) = render(false);
+    let (parallel_left, parallel_right, parallel_buffers) = render(true);
+
+    assert!(left.iter().any(|x| *x != 0_f32));
+    assert_eq!(
+        left.iter().map(|x| x.to_bits()).collect::<Vec<_>>(),
+        parallel_left
+            .iter()
+            .map(|x| x.to_bits())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        right.iter().map(|x| x.to_bits()).collect::<Vec<_>>(),
+        parallel_right
+            .iter()
+            .map(|x| x.to_bits())
+            .collect::<Vec<_>>()
+    );
+    for (channel, parallel_channel) in buffers
+        .channels
+        .iter()
+        .zip(parallel_buffers.channels.iter())
+    {
+        for (buffer, parallel_buffer) in channel.iter().zip(parallel_channel.iter()) {
+            assert!(
+                buffer
+                    .iter()
+                    .zip(parallel_buffer.iter())
+                    .all(|(a, b)| a.to_bits() == b.to_bits())
+            );
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn render_block(synth: &mut Synthesizer) {
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    synth.render(&mut left, &mut right);
+}
+
+#[test]
+fn channel_polyphony_limits_the_channel() {
+    let mut synth = synthesizer();
+    synth.set_channel_polyphony(0, Some(3));
+    assert_eq!(synth.get_channel_polyphony(0), Some(3));
+    assert_eq!(synth.get_channel_polyphony(1), None);
+
+    for key in 60..66 {
+        synth.note_on(0, key, 100);
+        render_block(&mut synth);
+    }
+    synth.note_on(1, 60, 100);
+    synth.note_on(1, 62, 100);
+
+    assert_eq!(synth.get_active_voice_count(), 5);
+}
+
+#[test]
+fn repeated_notes_stack_by_default() {
+    let mut synth = synthesizer();
+    assert_eq!(synth.get_key_polyphony(0), None);
+
+    for _ in 0..3 {
+        synth.note_on(0, 60, 100);
+        render_block(&mut synth);
+    }
+
+    assert_eq!(synth.get_active_voice_count(), 3);
+}
+
+#[test]
+fn key_polyphony_of_one_retriggers() {
+    let mut synth = synthesizer();
+    synth.set_key_polyphony(0, Some(1));
+
+    for _ in 0..3 {
+        synth.note_on(0, 60, 100);
+        render_block(&mut synth);
+        assert_eq!(synth.get_active_voice_count(), 1);
+    }
+
+    // The other keys and channels are not limited.
+    synth.note_on(0, 62, 100);
+    synth.note_on(1, 60, 100);
+    synth.note_on(1, 60, 100);
+    assert_eq!(synth.get_active_voice_count(), 4);
+}
+
+#[test]
+fn key_polyphony_stops_the_oldest_note() {
+    let mut synth = synthesizer();
+    synth.set_key_polyphony(0, Some(2));
+    synth.note_on(0, 60, 30);
+    render_block(&mut synth);
+
+    // The same notes as the last two, without the quiet first note.
+    let mut expected = synthesizer();
+    render_block(&mut expected);
+
+    for synth in [&mut synth, &mut expected] {
+        synth.note_on(0, 60, 100);
+        render_block(synth);
+        synth.note_on(0, 60, 90);
+    }
+    assert_eq!(synth.get_active_voice_count(), 2);
+
+    // Once the first note has faded out, only the last two notes remain.
+    let render = |synth: &mut Synthesizer| {
+        let mut left = vec![0_f32; 1024];
+        let mut right = vec![0_f32; 1024];
+        synth.render(&mut left, &mut right);
+        left[512..].to_vec()
+    };
+    assert_eq!(render(&mut synth), render(&mut expected));
+}
+
+#[test]
+fn polyphony_limits_are_kept_on_reset() {
+    let mut synth = synthesizer();
+    synth.set_channel_polyphony(3, Some(0));
+    synth.set_key_polyphony(3, Some(0));
+    assert_eq!(synth.get_channel_polyphony(3), Some(1));
+    assert_eq!(synth.get_key_polyphony(3), Some(1));
+
+    synth.reset();
+    assert_eq!(synth.get_channel_polyphony(3), Some(1));
+    assert_eq!(synth.get_key_polyphony(3), Some(1));
+
+    synth.set_channel_polyphony(3, None);
+    assert_eq!(synth.get_channel_polyphony(3), None);
+}
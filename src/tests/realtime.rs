@@ -0,0 +1,180 @@
+use std::{sync::Arc, thread};
+
+use super::utils::*;
+use crate::prelude::*;
+
+fn split(capacity: usize) -> (SynthController, SynthRenderer) {
+    synthesizer().split(capacity)
+}
+
+#[test]
+fn controller_and_renderer_can_be_sent() {
+    fn assert_send<T: Send>() {}
+    assert_send::<SynthController>();
+    assert_send::<SynthRenderer>();
+}
+
+#[test]
+fn commands_are_sent_from_another_thread() {
+    let (controller, mut renderer) = split(16);
+
+    let sender = controller.clone();
+    thread::spawn(move || {
+        sender
+            .process_midi_message(raw_message(&[0x90, 69, 100]))
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    let mut left = vec![0_f32; 1000];
+    let mut right = vec![0_f32; 1000];
+    renderer.render(&mut left, &mut right);
+    assert!(first_sound(&left).unwrap() < note_on_latency() + 4);
+    assert_eq!(controller.get_position(), 1000);
+    assert_eq!(renderer.get_position(), 1000);
+}
+
+#[test]
+fn timestamped_commands_are_sample_accurate() {
+    let (controller, mut renderer) = split(16);
+    controller
+        .send_at(
+            1000,
+            SynthCommand::MidiMessage(raw_message(&[0x90, 69, 100])),
+        )
+        .unwrap();
+    controller
+        .send_at(3000, SynthCommand::NoteOffAll(true))
+        .unwrap();
+
+    let mut left = vec![0_f32; 700];
+    let mut right = vec![0_f32; 700];
+    let mut rendered = Vec::new();
+    for _ in 0..6 {
+        renderer.render(&mut left, &mut right);
+        rendered.extend_from_slice(&left);
+    }
+
+    let onset = first_sound(&rendered).unwrap() - note_on_latency();
+    assert!((1000..1003).contains(&onset), "{onset}");
+    // The note is stopped exactly at the frame.
+    assert!(rendered[3000..].iter().all(|x| *x == 0_f32));
+    assert!(rendered[2990..3000].iter().any(|x| *x != 0_f32));
+}
+
+#[test]
+fn note_off_in_the_block_of_its_note_on_follows_it() {
+    let render_note = |note_off: u64| {
+        let (controller, mut renderer) = SynthesizerBuilder::new()
+            .settings(|settings| settings.block_size = 256)
+            .build()
+            .split(16);
+        controller
+            .send_at(
+                1050,
+                SynthCommand::MidiMessage(raw_message(&[0x90, 69, 100])),
+            )
+            .unwrap();
+        controller
+            .send_at(
+                note_off,
+                SynthCommand::MidiMessage(raw_message(&[0x80, 69, 0])),
+            )
+            .unwrap();
+
+        let mut left = vec![0_f32; 2048];
+        let mut right = vec![0_f32; 2048];
+        renderer.render(&mut left, &mut right);
+        left
+    };
+
+    // The note-off at frame 1200 shares the block from frame 1024 with the note-on.
+    let short = render_note(1200);
+    let long = render_note(1500);
+    assert!(peak(&short[1050..1200]) > 0_f32);
+    assert_eq!(short[..1200], long[..1200]);
+    assert_ne!(short[1200..1500], long[1200..1500]);
+}
+
+#[test]
+fn full_queue_returns_the_command() {
+    let (controller, mut renderer) = split(2);
+    controller.send(SynthCommand::MasterVolume(0.1)).unwrap();
+    controller.send(SynthCommand::MasterVolume(0.2)).unwrap();
+    assert!(matches!(
+        controller.send(SynthCommand::MasterVolume(0.3)),
+        Err(SynthCommand::MasterVolume(_))
+    ));
+
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    renderer.render(&mut left, &mut right);
+    assert_eq!(renderer.get_synthesizer().get_master_volume(), 0.2);
+    controller.send(SynthCommand::MasterVolume(0.3)).unwrap();
+}
+
+#[test]
+fn sound_font_is_swapped_and_released_by_the_controller() {
+    let (controller, mut renderer) = split(4);
+    let previous = synthetic_sound_font();
+    renderer
+        .get_synthesizer_mut()
+        .set_sound_font(previous.clone());
+    controller
+        .process_midi_message(raw_message(&[0x90, 69, 100]))
+        .unwrap();
+
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    renderer.render(&mut left, &mut right);
+    assert_eq!(renderer.get_synthesizer().get_active_voice_count(), 1);
+
+    controller
+        .send(SynthCommand::SoundFont(synthetic_sound_font()))
+        .unwrap();
+    renderer.render(&mut left, &mut right);
+    assert_eq!(renderer.get_synthesizer().get_active_voice_count(), 0);
+    // The renderer hands the previous SoundFont back to be dropped by the controller.
+    assert_eq!(Arc::strong_count(&previous), 2);
+    controller.send(SynthCommand::Reset).unwrap();
+    assert_eq!(Arc::strong_count(&previous), 1);
+}
+
+#[test]
+fn sound_font_swap_waits_for_the_previous_one_to_be_released() {
+    let (controller, mut renderer) = split(4);
+    let first = synthetic_sound_font();
+    let second = synthetic_sound_font();
+    controller
+        .send(SynthCommand::SoundFont(first.clone()))
+        .unwrap();
+    controller
+        .send(SynthCommand::SoundFont(second.clone()))
+        .unwrap();
+    controller
+        .process_midi_message(raw_message(&[0x90, 69, 100]))
+        .unwrap();
+
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    renderer.render(&mut left, &mut right);
+    renderer.render(&mut left, &mut right);
+    // The second SoundFont and the note after it wait until the first swap is released.
+    assert!(core::ptr::eq(
+        renderer.get_synthesizer().get_sound_font(),
+        &*first
+    ));
+    assert_eq!(renderer.get_synthesizer().get_active_voice_count(), 0);
+
+    controller.release_retired();
+    renderer.render(&mut left, &mut right);
+    assert!(core::ptr::eq(
+        renderer.get_synthesizer().get_sound_font(),
+        &*second
+    ));
+    assert_eq!(renderer.get_synthesizer().get_active_voice_count(), 1);
+    assert_eq!(Arc::strong_count(&first), 2);
+    controller.release_retired();
+    assert_eq!(Arc::strong_count(&first), 1);
+}
@@ -0,0 +1,156 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn multi_output_settings() -> SynthesizerSettings {
+    SynthesizerSettings {
+        enable_multi_output: true,
+        ..Default::default()
+    }
+}
+
+fn play(synth: &mut Synthesizer) {
+    synth.process_midi_message(raw_message(&[0xB1, 0x5B, 100]));
+    synth.process_midi_message(raw_message(&[0xB1, 0x5D, 100]));
+    synth.note_on(0, 69, 100);
+    synth.note_on(1, 57, 100);
+}
+
+#[test]
+fn render_multi_splits_channels_and_effect_returns() {
+    let mut mixed =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    let mut split = Synthesizer::new(synthetic_sound_font(), &multi_output_settings()).unwrap();
+    split.set_insertion_effect(1, Some(InsertionEffectType::Tremolo.get_params()));
+    mixed.set_insertion_effect(1, Some(InsertionEffectType::Tremolo.get_params()));
+    play(&mut mixed);
+    play(&mut split);
+
+    // An odd length makes the blocks straddle the calls.
+    let length = 1000;
+    let mut buffers = MultiOutputBuffers::new(length, true);
+    let mut left = vec![0_f32; length];
+    let mut right = vec![0_f32; length];
+    for _ in 0..5 {
+        mixed.render(&mut left, &mut right);
+        split.render_multi(&mut buffers);
+    }
+
+    assert!(peak(&buffers.channels[0][0]) > 0_f32);
+    assert!(peak(&buffers.channels[1][0]) > 0_f32);
+    assert_eq!(peak(&buffers.channels[2][0]), 0_f32);
+    assert!(peak(&buffers.reverb.as_ref().unwrap()[0]) > 0_f32);
+    assert!(peak(&buffers.chorus.as_ref().unwrap()[1]) > 0_f32);
+
+    for (t, (left, right)) in left.iter().zip(right.iter()).enumerate() {
+        let sum = |side: usize| {
+            buffers
+                .channels
+                .iter()
+                .chain(buffers.reverb.iter())
+                .chain(buffers.chorus.iter())
+                .map(|buffers| buffers[side][t])
+                .sum::<f32>()
+        };
+        assert!((sum(0) - left).abs() < 1e-5);
+        assert!((sum(1) - right).abs() < 1e-5);
+    }
+}
+
+#[test]
+#[should_panic]
+fn render_multi_checks_buffer_length() {
+    let mut synth = Synthesizer::new(synthetic_sound_font(), &multi_output_settings()).unwrap();
+    let mut buffers = MultiOutputBuffers::new(64, false);
+    buffers.channels[3][1].pop();
+    synth.render_multi(&mut buffers);
+}
+
+#[test]
+#[should_panic]
+fn render_multi_requires_multi_output() {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    synth.render_multi(&mut MultiOutputBuffers::new(64, false));
+}
+
+fn new_playing_synthesizer() -> Synthesizer {
+    let mut synth =
+        Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+    play(&mut synth);
+    synth
+}
+
+#[test]
+fn render_interleaved_matches_planar_output() {
+    let mut planar = new_playing_synthesizer();
+    let mut interleaved = new_playing_synthesizer();
+
+    let mut left = vec![0_f32; 1001];
+    let mut right = vec![0_f32; 1001];
+    planar.render(&mut left, &mut right);
+    let mut buffer = vec![0_f32; 2002];
+    interleaved.render_interleaved(&mut buffer).unwrap();
+
+    for (t, frame) in buffer.chunks_exact(2).enumerate() {
+        assert_eq!(frame, [left[t], right[t]]);
+    }
+}
+
+#[test]
+fn render_checks_buffer_length_without_panicking() {
+    let mut synth = new_playing_synthesizer();
+    assert_eq!(
+        synth.try_render(&mut [0_f32; 10], &mut [0_f32; 9]),
+        Err(RenderError::BufferLengthMismatch(10, 9))
+    );
+    assert_eq!(
+        synth.render_interleaved(&mut [0_f32; 11]),
+        Err(RenderError::InterleavedLengthNotEven(11))
+    );
+    assert_eq!(
+        synth.render_interleaved_i16(&mut [0_i16; 3]),
+        Err(RenderError::InterleavedLengthNotEven(3))
+    );
+    assert!(synth.try_render(&mut [0_f32; 10], &mut [0_f32; 10]).is_ok());
+}
+
+#[test]
+fn render_integer_formats() {
+    let mut reference = new_playing_synthesizer();
+    reference.set_master_volume(8_f32);
+    let mut buffer = vec![0_f32; 2000];
+    reference.render_interleaved(&mut buffer).unwrap();
+
+    let mut synth = new_playing_synthesizer();
+    synth.set_master_volume(8_f32);
+    let mut output = vec![0_i16; 2000];
+    synth.render_interleaved_i16(&mut output).unwrap();
+    for (x, y) in buffer.iter().zip(output.iter()) {
+        let expected = (*x as f64 * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+        assert_eq!(*y, expected);
+    }
+    // The output is loud enough to clip.
+    assert!(output.contains(&i16::MAX));
+
+    let mut synth = new_playing_synthesizer();
+    let mut output = vec![0_i32; 2000];
+    synth.render_interleaved_i24(&mut output).unwrap();
+    assert!(output.iter().all(|x| (-(1 << 23)..(1 << 23)).contains(x)));
+    assert!(output.iter().any(|x| x.abs() > 1 << 16));
+}
+
+#[test]
+fn dither_adds_low_level_noise() {
+    for dither in [Dither::Triangular, Dither::NoiseShaped] {
+        let mut synth =
+            Synthesizer::new(synthetic_sound_font(), &SynthesizerSettings::default()).unwrap();
+        synth.set_dither(dither);
+        assert_eq!(synth.get_dither(), dither);
+
+        // Silence is dithered to a few LSBs.
+        let mut output = vec![0_i16; 4000];
+        synth.render_interleaved_i16(&mut output).unwrap();
+        assert!(output.iter().any(|x| *x != 0));
+        assert!(output.iter().all(|x| x.abs() <= 3), "{dither:?}");
+    }
+}
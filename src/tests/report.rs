@@ -0,0 +1,150 @@
+use super::utils::*;
+use crate::prelude::*;
+
+// Builds the synthetic SoundFont with some generators of its preset and instrument zones set.
+fn sound_font(preset: &[(u16, i16)], instrument: &[(u16, i16)]) -> SoundFont {
+    let mut sound_font = (*synthetic_sound_font()).clone();
+    for (generator, value) in preset {
+        sound_font.presets[0].regions[0].gs[*generator as usize] = *value;
+    }
+    for (generator, value) in instrument {
+        sound_font.instruments[0].regions[0].gs[*generator as usize] = *value;
+    }
+    sound_font
+}
+
+fn resolved_zones(sound_font: &SoundFont) -> Vec<ResolvedZone<'_>> {
+    let preset = sound_font
+        .get_presets()
+        .iter()
+        .find(|preset| preset.get_bank_number() == 0)
+        .unwrap();
+    SoundFontReport::new(sound_font).get_resolved_zones(&preset.get_regions()[0])
+}
+
+#[test]
+fn preset_values_are_added_to_the_instrument_values() {
+    let sound_font = sound_font(
+        &[
+            (GeneratorType::INITIAL_ATTENUATION, 100),
+            (GeneratorType::COARSE_TUNE, -12),
+            (GeneratorType::RELEASE_VOLUME_ENVELOPE, 1200),
+        ],
+        &[
+            (GeneratorType::INITIAL_ATTENUATION, 50),
+            (GeneratorType::COARSE_TUNE, 2),
+        ],
+    );
+    let zones = resolved_zones(&sound_font);
+    assert_eq!(zones.len(), 1);
+    let zone = &zones[0];
+    assert_eq!(zone.get_initial_attenuation(), 15_f32);
+    assert_eq!(zone.get_coarse_tune(), -10);
+
+    // The synthetic instrument releases at -3986 timecents.
+    let instrument = &sound_font.get_instruments()[0].get_regions()[0];
+    let release = 2_f32 * instrument.get_release_volume_envelope();
+    assert!((zone.get_release_volume_envelope() / release - 1_f32).abs() < 1e-4);
+}
+
+#[test]
+fn sums_are_clamped_to_the_generator_ranges() {
+    let sound_font = sound_font(
+        &[
+            (GeneratorType::PAN, 400),
+            (GeneratorType::COARSE_TUNE, 100),
+            (GeneratorType::INITIAL_ATTENUATION, -500),
+            (GeneratorType::REVERB_EFFECTS_SEND, 800),
+        ],
+        &[
+            (GeneratorType::PAN, 400),
+            (GeneratorType::COARSE_TUNE, 100),
+            (GeneratorType::INITIAL_ATTENUATION, 100),
+            (GeneratorType::REVERB_EFFECTS_SEND, 800),
+        ],
+    );
+    let zone = resolved_zones(&sound_font)[0];
+    assert_eq!(zone.get_pan(), 50_f32);
+    assert_eq!(zone.get_coarse_tune(), 120);
+    assert_eq!(zone.get_initial_attenuation(), 0_f32);
+    assert_eq!(zone.get_reverb_effects_send(), 100_f32);
+}
+
+#[test]
+fn zones_resolve_to_the_overlap_of_their_ranges() {
+    // Keys 40-80 and 60-100, velocities 0-127 and 64-127.
+    let overlapping = sound_font(
+        &[(GeneratorType::KEY_RANGE, 0x5028)],
+        &[
+            (GeneratorType::KEY_RANGE, 0x643C),
+            (GeneratorType::VELOCITY_RANGE, 0x7F40),
+        ],
+    );
+    let zone = resolved_zones(&overlapping)[0];
+    assert_eq!(zone.get_key_range(), (60, 80));
+    assert_eq!(zone.get_velocity_range(), (64, 127));
+
+    // Keys 0-40 and 60-100.
+    let disjoint = sound_font(
+        &[(GeneratorType::KEY_RANGE, 0x2800)],
+        &[(GeneratorType::KEY_RANGE, 0x643C)],
+    );
+    assert!(resolved_zones(&disjoint).is_empty());
+}
+
+#[test]
+fn json_escapes_text_and_nests_the_zones() {
+    let text = ReportValue::Text("a \"quoted\"\\name\n\u{1}".into());
+    assert_eq!(text.to_json(), r#""a \"quoted\"\\name\n\u0001""#);
+    assert_eq!(ReportValue::Float(f32::NAN).to_json(), "null");
+    assert_eq!(ReportValue::List(Vec::new()).to_json(), "[]");
+
+    let fields = ReportValue::Fields(vec![
+        ("name", ReportValue::Text("Sine".into())),
+        ("key_range", ReportValue::Range(0, 127)),
+        (
+            "zone_list",
+            ReportValue::List(vec![ReportValue::Fields(vec![(
+                "attenuation",
+                ReportValue::Float(1.5_f32),
+            )])]),
+        ),
+    ]);
+    assert_eq!(
+        fields.to_json(),
+        "{\n  \"name\": \"Sine\",\n  \"key_range\": [0, 127],\n  \"zone_list\": [\n    {\"attenuation\": 1.5}\n  ]\n}"
+    );
+}
+
+#[test]
+fn report_describes_the_resolved_zones() {
+    let sound_font = sound_font(&[(GeneratorType::INITIAL_ATTENUATION, 100)], &[]);
+    let report = SoundFontReport::new(&sound_font);
+
+    let fields = report.get_fields();
+    let ReportValue::List(presets) = &fields[2].1 else {
+        panic!("the presets should be a list");
+    };
+    let ReportValue::Fields(preset) = &presets[0] else {
+        panic!("a preset should be fields");
+    };
+    assert_eq!(preset[2], ("name", ReportValue::Text("Sine".into())));
+    let ReportValue::List(zones) = &preset[3].1 else {
+        panic!("the zones should be a list");
+    };
+    let ReportValue::Fields(zone) = &zones[0] else {
+        panic!("a zone should be fields");
+    };
+    let ReportValue::List(resolved) = &zone[3].1 else {
+        panic!("the resolved zones should be a list");
+    };
+    let ReportValue::Fields(resolved) = &resolved[0] else {
+        panic!("a resolved zone should be fields");
+    };
+    assert!(resolved.contains(&("attenuation", ReportValue::Float(10_f32))));
+
+    let text = report.to_text(true);
+    assert!(text.contains("      sample=Sine key_range=0-127"), "{text}");
+    assert!(text.contains("attenuation=10.0000"), "{text}");
+    assert!(!report.to_text(false).contains("[INSTRUMENTS]"));
+}
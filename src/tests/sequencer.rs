@@ -0,0 +1,444 @@
+use std::sync::Arc;
+
+use super::utils::*;
+use crate::prelude::*;
+
+fn variable_length(mut value: u32, bytes: &mut Vec<u8>) {
+    let mut buffer = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value > 0 {
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.extend(buffer.iter().rev());
+}
+
+// Builds a standard MIDI file from tracks of (delta ticks, event bytes).
+fn smf(format: u16, division: [u8; 2], tracks: &[&[(u32, &[u8])]]) -> Vec<u8> {
+    let mut bytes = b"MThd".to_vec();
+    bytes.extend(6_u32.to_be_bytes());
+    bytes.extend(format.to_be_bytes());
+    bytes.extend((tracks.len() as u16).to_be_bytes());
+    bytes.extend(division);
+
+    for track in tracks {
+        let mut data = Vec::new();
+        for (delta, event) in track.iter() {
+            variable_length(*delta, &mut data);
+            data.extend_from_slice(event);
+        }
+        variable_length(0, &mut data);
+        data.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+        bytes.extend(b"MTrk");
+        bytes.extend((data.len() as u32).to_be_bytes());
+        bytes.extend(data);
+    }
+
+    bytes
+}
+
+fn sequencer() -> MidiFileSequencer {
+    MidiFileSequencer::new(synthesizer())
+}
+
+#[test]
+fn tempo_map_resolves_event_times() {
+    // 96 ticks per quarter note, 120 BPM for one beat, then 60 BPM.
+    let data = smf(
+        1,
+        [0, 96],
+        &[
+            &[(96, &[0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40])],
+            &[(0, &[0x90, 60, 100]), (192, &[0x80, 60, 0])],
+        ],
+    );
+    let midi_file = MidiFile::from_bytes(&data).unwrap();
+
+    // 0.5 s for the first beat and 1 s for the second one.
+    assert!((midi_file.get_length() - 1.5).abs() < 1e-9);
+}
+
+#[test]
+fn smpte_timing_resolves_event_times() {
+    // 25 frames per second with 40 ticks per frame, i.e. 1 ms per tick.
+    let data = smf(
+        0,
+        [(-25_i8) as u8, 40],
+        &[&[(0, &[0x90, 60, 100]), (250, &[0x80, 60, 0])]],
+    );
+    let midi_file = MidiFile::from_bytes(&data).unwrap();
+
+    assert!((midi_file.get_length() - 0.25).abs() < 1e-9);
+}
+
+#[test]
+fn format_2_is_rejected() {
+    let data = smf(2, [0, 96], &[&[(0, &[0x90, 60, 100])]]);
+
+    assert!(matches!(
+        MidiFile::from_bytes(&data),
+        Err(MidiFileError::UnsupportedFormat)
+    ));
+}
+
+#[test]
+fn note_on_is_sample_accurate() {
+    // At 120 BPM and 480 ticks per quarter note, 100 ticks is 100 / 960 s.
+    let data = smf(0, [0x01, 0xE0], &[&[(100, &[0x90, 69, 100])]]);
+    let midi_file = Arc::new(MidiFile::from_bytes(&data).unwrap());
+    let mut sequencer = sequencer();
+    let expected = (44100.0 * 100.0 / 960.0_f64).round() as usize;
+    assert_ne!(expected % sequencer.get_synthesizer().get_block_size(), 0);
+
+    sequencer.play(midi_file, false);
+    // An odd length makes the blocks straddle the calls.
+    let mut left = vec![0_f32; 8000];
+    let mut right = vec![0_f32; 8000];
+    let mut rendered = vec![];
+    for _ in 0..2 {
+        sequencer.render(&mut left[..1001], &mut right[..1001]);
+        rendered.extend_from_slice(&left[..1001]);
+        sequencer.render(&mut left, &mut right);
+        rendered.extend_from_slice(&left);
+    }
+
+    let onset = first_sound(&rendered).unwrap() - note_on_latency();
+    assert!(
+        onset >= expected && onset <= expected + 2,
+        "{onset} vs {expected}"
+    );
+}
+
+#[test]
+fn note_off_and_controllers_are_sample_accurate() {
+    let render = |event: Option<&[u8]>| {
+        let mut track: Vec<(u32, &[u8])> = vec![(0, &[0x90, 69, 100])];
+        track.extend(event.map(|event| (100, event)));
+        track.push((480, &[0x80, 69, 0]));
+        let midi_file = Arc::new(MidiFile::from_bytes(&smf(0, [0x01, 0xE0], &[&track])).unwrap());
+        let mut sequencer = sequencer();
+        sequencer.play(midi_file, false);
+        render_seconds(&mut sequencer, 0.2)
+    };
+
+    let expected = (44100.0 * 100.0 / 960.0_f64).round() as usize;
+    let reference = render(None);
+    for event in [
+        &[0x80, 69, 0][..],
+        &[0xB0, 0x07, 0],
+        &[0xB0, 0x0A, 0],
+        &[0xE0, 0x00, 0x00],
+    ] {
+        let rendered = render(Some(event));
+        let difference = (0..rendered.len()).find(|&t| rendered[t] != reference[t]);
+        assert!(
+            difference.is_some_and(|t| t >= expected && t <= expected + 2),
+            "{event:?} {difference:?} vs {expected}"
+        );
+    }
+}
+
+#[test]
+fn playback_ends_or_loops() {
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[(0, &[0x90, 69, 100]), (480, &[0x80, 69, 0])]],
+    );
+    let midi_file = Arc::new(MidiFile::from_bytes(&data).unwrap());
+    let mut sequencer = sequencer();
+    assert!(sequencer.end_of_sequence());
+
+    let mut left = vec![0_f32; 4410];
+    let mut right = vec![0_f32; 4410];
+
+    sequencer.play(midi_file.clone(), false);
+    for _ in 0..4 {
+        sequencer.render(&mut left, &mut right);
+    }
+    assert!(!sequencer.end_of_sequence());
+    assert!((sequencer.get_position() - 0.4).abs() < 1e-9);
+    for _ in 0..2 {
+        sequencer.render(&mut left, &mut right);
+    }
+    assert!(sequencer.end_of_sequence());
+
+    sequencer.play(midi_file, true);
+    for _ in 0..7 {
+        sequencer.render(&mut left, &mut right);
+    }
+    assert!(!sequencer.end_of_sequence());
+    // The note restarts after the loop point.
+    assert!((sequencer.get_position() - 0.2).abs() < 1e-3);
+    assert!(first_sound(&left).is_some());
+
+    sequencer.stop();
+    assert!(sequencer.end_of_sequence());
+}
+
+fn render_seconds(sequencer: &mut MidiFileSequencer, seconds: f64) -> Vec<f32> {
+    let length = (44100.0 * seconds) as usize;
+    let mut left = vec![0_f32; length];
+    let mut right = vec![0_f32; length];
+    sequencer.render(&mut left, &mut right);
+    left
+}
+
+#[test]
+fn seek_chases_controllers() {
+    // 120 BPM and 480 ticks per quarter note, i.e. 960 ticks per second.
+    let controllers: [&[u8]; 3] = [&[0xB0, 0x07, 40], &[0xB0, 0x0A, 10], &[0xE0, 0x00, 0x60]];
+
+    // The controllers are sent at 0.05 s and the note at 0.6 s.
+    let seeked = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[
+            (48, controllers[0]),
+            (0, controllers[1]),
+            (0, controllers[2]),
+            (528, &[0x90, 69, 100]),
+        ]],
+    );
+    // The same state from the start, with the note at 0.1 s.
+    let expected = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[
+            (0, controllers[0]),
+            (0, controllers[1]),
+            (0, controllers[2]),
+            (96, &[0x90, 69, 100]),
+        ]],
+    );
+
+    let mut sequencer = sequencer();
+    sequencer.play(Arc::new(MidiFile::from_bytes(&seeked).unwrap()), false);
+    sequencer.seek(0.5, false);
+    assert!((sequencer.get_position() - 0.5).abs() < 1e-9);
+    let actual = render_seconds(&mut sequencer, 0.3);
+
+    sequencer.play(Arc::new(MidiFile::from_bytes(&expected).unwrap()), false);
+    let expected = render_seconds(&mut sequencer, 0.3);
+
+    assert!(first_sound(&actual).is_some());
+    assert_eq!(actual, expected);
+
+    // Seeking by tick follows the tempo map.
+    sequencer.play(Arc::new(MidiFile::from_bytes(&seeked).unwrap()), false);
+    sequencer.seek_to_tick(480, false);
+    assert!((sequencer.get_position() - 0.5).abs() < 1e-9);
+    assert_eq!(render_seconds(&mut sequencer, 0.3), expected);
+}
+
+#[test]
+fn seek_retriggers_held_notes() {
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[
+            (0, &[0x90, 69, 100]),
+            (0, &[0x90, 64, 100]),
+            (0, &[0xB1, 0x40, 127]),
+            (0, &[0x91, 57, 100]),
+            (96, &[0x81, 57, 0]),
+            (0, &[0x80, 64, 0]),
+            (960, &[0x80, 69, 0]),
+        ]],
+    );
+    let midi_file = Arc::new(MidiFile::from_bytes(&data).unwrap());
+    let mut sequencer = sequencer();
+
+    sequencer.play(midi_file.clone(), false);
+    sequencer.seek(0.5, false);
+    assert_eq!(first_sound(&render_seconds(&mut sequencer, 0.1)), None);
+
+    // The key 69 is held and the key 57 is sustained by the pedal, but 64 has been released.
+    sequencer.seek(0.5, true);
+    assert!(first_sound(&render_seconds(&mut sequencer, 0.1)).unwrap() < note_on_latency() + 4);
+    assert_eq!(sequencer.get_synthesizer().get_active_voice_count(), 2);
+}
+
+// A loop from 0.5 s to 1.5 s in a file of 2 s, with a note held across the seam (60),
+// a note held at the loop end only (64) and a note held at the loop start only (67).
+fn looped_file() -> Arc<MidiFile> {
+    let data = smf(
+        1,
+        [0x01, 0xE0],
+        &[
+            &[
+                (480, b"\xFF\x06\x09loopStart"),
+                (960, b"\xFF\x06\x07loopEnd"),
+            ],
+            &[
+                (0, &[0x90, 60, 100]),
+                (192, &[0x90, 67, 100]),
+                (576, &[0x80, 67, 0]),
+                (384, &[0x90, 64, 100]),
+                (576, &[0x80, 64, 0]),
+                (192, &[0x80, 60, 0]),
+            ],
+        ],
+    );
+    Arc::new(MidiFile::from_bytes(&data).unwrap())
+}
+
+#[test]
+fn loop_points_are_read_from_markers_and_cc111() {
+    let midi_file = looped_file();
+    assert!((midi_file.get_loop_start() - 0.5).abs() < 1e-9);
+    assert!((midi_file.get_loop_end() - 1.5).abs() < 1e-9);
+    assert!((midi_file.get_length() - 2.0).abs() < 1e-9);
+
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[(240, &[0xB0, 111, 0]), (720, &[0x90, 60, 100])]],
+    );
+    let midi_file = MidiFile::from_bytes(&data).unwrap();
+    assert!((midi_file.get_loop_start() - 0.25).abs() < 1e-9);
+    assert!((midi_file.get_loop_end() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn loop_seam_keeps_held_notes() {
+    let mut sequencer = sequencer();
+    sequencer.play(looped_file(), true);
+
+    render_seconds(&mut sequencer, 1.75);
+    assert!((sequencer.get_position() - 0.75).abs() < 1e-3);
+    // The notes 60 and 67 are held at this point of the loop, and 64 has been released.
+    assert_eq!(sequencer.get_synthesizer().get_active_voice_count(), 2);
+
+    // The note 60 still sounds after many loops.
+    for _ in 0..5 {
+        render_seconds(&mut sequencer, 1.0);
+    }
+    assert!((sequencer.get_position() - 0.75).abs() < 1e-3);
+    assert_eq!(sequencer.get_synthesizer().get_active_voice_count(), 2);
+    assert!(!sequencer.end_of_sequence());
+
+    // A range given by the API overrides the markers.
+    sequencer.set_loop_range(Some((0, 960)));
+    sequencer.play(looped_file(), true);
+    render_seconds(&mut sequencer, 1.5);
+    assert!((sequencer.get_position() - 0.5).abs() < 1e-3);
+}
+
+#[test]
+fn loop_seam_resets_controllers_set_within_the_loop() {
+    // The filter type is selected by NRPN at 0.75 s, within the loop from 0.5 s to 1 s.
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[
+            (0, &[0xB0, 0x07, 100]),
+            (480, &[0x90, 69, 100]),
+            (240, &[0xB0, 0x63, 1]),
+            (0, &[0xB0, 0x62, 0x28]),
+            (0, &[0xB0, 0x06, 1]),
+            (240, &[0x80, 69, 0]),
+        ]],
+    );
+    let mut sequencer = sequencer();
+    sequencer.set_loop_range(Some((480, 960)));
+    sequencer.play(Arc::new(MidiFile::from_bytes(&data).unwrap()), true);
+
+    render_seconds(&mut sequencer, 0.8);
+    let filter_type =
+        |sequencer: &MidiFileSequencer| sequencer.get_synthesizer().get_channel_filter_type(0);
+    assert_eq!(filter_type(&sequencer), Some(FilterType::HighPass));
+
+    render_seconds(&mut sequencer, 0.3);
+    assert!((sequencer.get_position() - 0.6).abs() < 1e-3);
+    assert_eq!(filter_type(&sequencer), None);
+    render_seconds(&mut sequencer, 0.2);
+    assert_eq!(filter_type(&sequencer), Some(FilterType::HighPass));
+}
+
+#[test]
+fn limited_loops_end_or_fade_out() {
+    let mut sequencer = sequencer();
+    sequencer.set_loop_count(Some(1));
+
+    // After one loop, playback goes on to the end of the file.
+    sequencer.play(looped_file(), true);
+    render_seconds(&mut sequencer, 2.9);
+    assert!(!sequencer.end_of_sequence());
+    assert!((sequencer.get_position() - 1.9).abs() < 1e-3);
+    render_seconds(&mut sequencer, 0.2);
+    assert!(sequencer.end_of_sequence());
+
+    // After one loop, playback fades out over 0.5 s from the seam.
+    sequencer.set_fade_out(0.5);
+    sequencer.play(looped_file(), true);
+    let before = render_seconds(&mut sequencer, 1.5);
+    let fading = render_seconds(&mut sequencer, 0.5);
+    assert!(sequencer.end_of_sequence());
+    let after = render_seconds(&mut sequencer, 0.5);
+
+    assert!(peak(&fading[..2205]) > 0_f32);
+    assert!(peak(&fading[19845..]) < peak(&before[..]) * 0.15);
+    assert_eq!(peak(&after), 0_f32);
+}
+
+#[test]
+fn offline_render_stops_when_voices_decay() {
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[(0, &[0x90, 69, 100]), (480, &[0x80, 69, 0])]],
+    );
+    let midi_file = Arc::new(MidiFile::from_bytes(&data).unwrap());
+    let settings = SynthesizerSettings::default();
+
+    let waveform = render_midi_file(&synthetic_sound_font(), &midi_file, &settings, 10.0).unwrap();
+    let length = waveform.left.len() as f64 / 44100.0;
+    assert_eq!(waveform.left.len(), waveform.right.len());
+    // The release of the note is about 100 ms.
+    assert!(length > 0.55 && length < 2.0, "{length}");
+    assert!(first_sound(&waveform.left).is_some());
+
+    // The tail is cut at the maximum length.
+    let waveform = render_midi_file(&synthetic_sound_font(), &midi_file, &settings, 0.0).unwrap();
+    assert!((waveform.left.len() as f64 / 44100.0 - 0.5).abs() < 0.01);
+}
+
+#[test]
+fn offline_render_keeps_the_reverb_tail() {
+    let data = smf(
+        0,
+        [0x01, 0xE0],
+        &[&[
+            (0, &[0xB0, 91, 127]),
+            (0, &[0x90, 69, 100]),
+            (480, &[0x80, 69, 0]),
+        ]],
+    );
+    let midi_file = Arc::new(MidiFile::from_bytes(&data).unwrap());
+    let dry = SynthesizerSettings {
+        enable_reverb: false,
+        ..Default::default()
+    };
+
+    let dry = render_midi_file(&synthetic_sound_font(), &midi_file, &dry, 10.0).unwrap();
+    let wet = render_midi_file(
+        &synthetic_sound_font(),
+        &midi_file,
+        &Default::default(),
+        10.0,
+    )
+    .unwrap();
+    assert!(
+        wet.left.len() > dry.left.len() + 44100 / 2,
+        "{} {}",
+        wet.left.len(),
+        dry.left.len()
+    );
+    assert!(wet.left.len() < 10 * 44100);
+
+    // The reverb has decayed at the end.
+    let end = &wet.left[wet.left.len() - 64..];
+    assert!(end.iter().all(|x| x.abs() < 1e-5));
+}
@@ -0,0 +1,113 @@
+use super::utils::*;
+
+// Renders a note played after the controllers are set.
+fn note_with_controllers(controllers: &[(u8, u8)]) -> Vec<f32> {
+    let mut synth = synthesizer();
+    for (controller, value) in controllers {
+        synth.process_midi_message(raw_message(&[0xB0, *controller, *value]));
+    }
+    synth.note_on(0, 60, 100);
+    render_left(&mut synth, 8192)
+}
+
+fn note_with_controller(controller: u8, value: u8) -> Vec<f32> {
+    note_with_controllers(&[(controller, value)])
+}
+
+#[test]
+fn centered_controllers_do_not_change_the_sound() {
+    let expected = note_with_controller(0x07, 100);
+    for controller in 0x47..=0x4E {
+        assert_eq!(
+            note_with_controller(controller, 64),
+            expected,
+            "{controller}"
+        );
+    }
+}
+
+#[test]
+fn release_time_shortens_the_release() {
+    let release = |value: u8| {
+        let mut synth = synthesizer();
+        synth.process_midi_message(raw_message(&[0xB0, 0x48, value]));
+        synth.note_on(0, 60, 100);
+        render_left(&mut synth, 4410);
+        synth.note_off(0, 60);
+        render_left(&mut synth, 2205);
+        synth.get_active_voice_count()
+    };
+
+    // The release of roughly 100 ms becomes four times shorter.
+    assert_eq!(release(64), 1);
+    assert_eq!(release(0), 0);
+}
+
+#[test]
+fn attack_time_slows_the_attack() {
+    let default = note_with_controller(0x49, 64);
+    let slow = note_with_controller(0x49, 127);
+
+    assert!(peak(&slow[..64]) < peak(&default[..64]));
+    assert!(peak(&slow[4096..]) > 0_f32);
+}
+
+#[test]
+fn vibrato_controllers_change_new_notes() {
+    let default = note_with_controller(0x4D, 64);
+    let deep = note_with_controller(0x4D, 127);
+    assert_ne!(deep, default);
+
+    // The rate and the delay change the vibrato added by the depth.
+    for controller in [0x4C, 0x4E] {
+        assert_ne!(
+            note_with_controllers(&[(0x4D, 127), (controller, 127)]),
+            deep,
+            "{controller}"
+        );
+    }
+}
+
+#[test]
+fn resonance_changes_playing_notes() {
+    let mut expected = synthesizer();
+    let mut synth = synthesizer();
+    for synth in [&mut expected, &mut synth] {
+        synth.note_on(0, 60, 100);
+        render_left(synth, 1024);
+    }
+
+    synth.process_midi_message(raw_message(&[0xB0, 0x47, 127]));
+    assert_ne!(
+        render_left(&mut synth, 1024),
+        render_left(&mut expected, 1024)
+    );
+
+    // Resetting the controller returns the playing note to the original filter.
+    synth.process_midi_message(raw_message(&[0xB0, 0x47, 64]));
+    render_left(&mut synth, 1024);
+    render_left(&mut expected, 1024);
+    assert_eq!(
+        render_left(&mut synth, 1024),
+        render_left(&mut expected, 1024)
+    );
+}
+
+#[test]
+fn sound_controllers_survive_reset_all_controllers() {
+    let expected = note_with_controller(0x48, 0);
+
+    let mut synth = synthesizer();
+    synth.process_midi_message(raw_message(&[0xB0, 0x48, 0]));
+    synth.process_midi_message(raw_message(&[0xB0, 0x79, 0]));
+    synth.note_on(0, 60, 100);
+    assert_eq!(render_left(&mut synth, 8192), expected);
+
+    // A full reset restores the defaults.
+    synth.reset();
+    synth.note_on(0, 60, 100);
+    assert_eq!(
+        render_left(&mut synth, 8192),
+        note_with_controller(0x48, 64)
+    );
+}
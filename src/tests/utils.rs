@@ -72,7 +72,10 @@ impl SynthesizerComparison {
 
         let midix_synth = crate::prelude::Synthesizer::new(
             Arc::new(midix_soundfont),
-            &crate::prelude::SynthesizerSettings::new(config.sample_rate),
+            &crate::prelude::SynthesizerSettings {
+                envelope_curve: crate::prelude::EnvelopeCurve::RustySynth,
+                ..crate::prelude::SynthesizerSettings::new(config.sample_rate)
+            },
         )?;
 
         let rusty_synth = rustysynth::Synthesizer::new(
@@ -451,3 +454,237 @@ impl TestScenario {
         }
     }
 }
+
+/// Builds a minimal in-memory SoundFont for tests that do not need a real bank.
+///
+/// The bank contains a looped sine wave (root key 69) used by two presets:
+/// `000:000` for melodic channels and `128:000` for the percussion channel.
+pub fn synthetic_sound_font() -> Arc<crate::prelude::SoundFont> {
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn list(list_type: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = list_type.to_vec();
+        for c in chunks {
+            data.extend(c);
+        }
+        chunk(b"LIST", &data)
+    }
+
+    fn name(value: &str, length: usize) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(length, 0);
+        bytes
+    }
+
+    fn generators(values: &[(u16, i16)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (generator_type, value) in values {
+            bytes.extend(generator_type.to_le_bytes());
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    const PERIOD: usize = 100;
+    const LENGTH: usize = 4400;
+
+    let mut smpl = Vec::new();
+    for t in 0..LENGTH {
+        let phase = 2.0 * std::f64::consts::PI * (t % PERIOD) as f64 / PERIOD as f64;
+        smpl.extend(((16000.0 * phase.sin()) as i16).to_le_bytes());
+    }
+    // Every sample must be followed by at least 46 zero-valued data points.
+    smpl.extend([0_u8; 92]);
+
+    let mut phdr = Vec::new();
+    for (preset_name, bank, bag) in [("Sine", 0_u16, 0_u16), ("Drums", 128, 1), ("EOP", 0, 2)] {
+        phdr.extend(name(preset_name, 20));
+        phdr.extend(0_u16.to_le_bytes());
+        phdr.extend(bank.to_le_bytes());
+        phdr.extend(bag.to_le_bytes());
+        phdr.extend([0_u8; 12]);
+    }
+
+    let mut pbag = Vec::new();
+    for (generator_index, modulator_index) in [(0_u16, 0_u16), (1, 0), (2, 0)] {
+        pbag.extend(generator_index.to_le_bytes());
+        pbag.extend(modulator_index.to_le_bytes());
+    }
+    let pgen = generators(&[(41, 0), (41, 0), (0, 0)]);
+
+    let mut inst = name("Sine", 20);
+    inst.extend(0_u16.to_le_bytes());
+    inst.extend(name("EOI", 20));
+    inst.extend(1_u16.to_le_bytes());
+
+    let mut ibag = Vec::new();
+    for (generator_index, modulator_index) in [(0_u16, 0_u16), (3, 0)] {
+        ibag.extend(generator_index.to_le_bytes());
+        ibag.extend(modulator_index.to_le_bytes());
+    }
+    // Loop continuously and release over roughly 100 ms.
+    let igen = generators(&[(54, 1), (38, -3986), (53, 0), (0, 0)]);
+
+    let mut shdr = name("Sine", 20);
+    for value in [
+        0_i32,
+        LENGTH as i32,
+        PERIOD as i32,
+        (LENGTH - PERIOD) as i32,
+        44100,
+    ] {
+        shdr.extend(value.to_le_bytes());
+    }
+    shdr.extend([69_u8, 0]);
+    shdr.extend(0_u16.to_le_bytes());
+    shdr.extend(1_u16.to_le_bytes());
+    shdr.extend(name("EOS", 46));
+
+    let mut ifil = 2_u16.to_le_bytes().to_vec();
+    ifil.extend(1_u16.to_le_bytes());
+
+    let mut body = b"sfbk".to_vec();
+    body.extend(list(
+        b"INFO",
+        &[chunk(b"ifil", &ifil), chunk(b"INAM", &name("Test", 8))],
+    ));
+    body.extend(list(b"sdta", &[chunk(b"smpl", &smpl)]));
+    body.extend(list(
+        b"pdta",
+        &[
+            chunk(b"phdr", &phdr),
+            chunk(b"pbag", &pbag),
+            chunk(b"pmod", &[0_u8; 10]),
+            chunk(b"pgen", &pgen),
+            chunk(b"inst", &inst),
+            chunk(b"ibag", &ibag),
+            chunk(b"imod", &[0_u8; 10]),
+            chunk(b"igen", &igen),
+            chunk(b"shdr", &shdr),
+        ],
+    ));
+    let bytes = chunk(b"RIFF", &body);
+
+    Arc::new(
+        crate::prelude::SoundFont::new_enforce_sanity_check(&mut Cursor::new(bytes))
+            .expect("the synthetic SoundFont should be valid"),
+    )
+}
+
+/// Gets the number of silent samples before a note of the synthetic SoundFont sounds,
+/// which comes from the delay of its volume envelope and does not depend on the block size.
+pub fn note_on_latency() -> usize {
+    let mut synth = crate::prelude::Synthesizer::new(synthetic_sound_font(), &Default::default())
+        .expect("the default settings should be valid");
+    synth.note_on(0, 69, 100);
+    let mut left = vec![0_f32; 1024];
+    let mut right = vec![0_f32; 1024];
+    synth.render(&mut left, &mut right);
+    first_sound(&left).expect("the note should sound")
+}
+
+/// Renders `frames` samples and returns the peak absolute value of both channels.
+pub fn render_peak(synth: &mut crate::prelude::Synthesizer, frames: usize) -> f32 {
+    let mut left = vec![0_f32; frames];
+    let mut right = vec![0_f32; frames];
+    synth.render(&mut left, &mut right);
+    left.iter()
+        .chain(right.iter())
+        .fold(0_f32, |peak, x| peak.max(x.abs()))
+}
+
+/// Parses a raw channel voice message, e.g. `[0xB0, 0x07, 100]`.
+pub fn raw_message(bytes: &[u8]) -> ChannelVoiceMessage {
+    use midix::prelude::FromLiveEventBytes;
+    ChannelVoiceMessage::from_bytes(bytes).expect("the message should be valid")
+}
+
+/// Renders `frames` samples and counts the zero crossings of the left channel.
+pub fn count_zero_crossings(synth: &mut crate::prelude::Synthesizer, frames: usize) -> usize {
+    let mut left = vec![0_f32; frames];
+    let mut right = vec![0_f32; frames];
+    synth.render(&mut left, &mut right);
+    left.windows(2)
+        .filter(|w| (w[0] < 0_f32) != (w[1] < 0_f32))
+        .count()
+}
+
+/// Builds a synthesizer for the tests, playing the synthetic SoundFont by default.
+/// The reverb and chorus are disabled, so that only the voices are heard.
+pub struct SynthesizerBuilder {
+    sound_font: Arc<crate::prelude::SoundFont>,
+    settings: crate::prelude::SynthesizerSettings,
+}
+
+impl SynthesizerBuilder {
+    pub fn new() -> Self {
+        Self {
+            sound_font: synthetic_sound_font(),
+            settings: crate::prelude::SynthesizerSettings {
+                enable_reverb: false,
+                enable_chorus: false,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Plays another SoundFont.
+    pub fn sound_font(mut self, sound_font: Arc<crate::prelude::SoundFont>) -> Self {
+        self.sound_font = sound_font;
+        self
+    }
+
+    /// Sets generators of every region of the first instrument.
+    pub fn instrument_generators(mut self, generators: &[(u16, i16)]) -> Self {
+        let mut sound_font = (*self.sound_font).clone();
+        for region in sound_font.get_instruments_mut()[0].get_regions_mut() {
+            for (generator, value) in generators {
+                region.gs[*generator as usize] = *value;
+            }
+        }
+        self.sound_font = Arc::new(sound_font);
+        self
+    }
+
+    /// Changes the settings.
+    pub fn settings(
+        mut self,
+        configure: impl FnOnce(&mut crate::prelude::SynthesizerSettings),
+    ) -> Self {
+        configure(&mut self.settings);
+        self
+    }
+
+    pub fn build(self) -> crate::prelude::Synthesizer {
+        crate::prelude::Synthesizer::new(self.sound_font, &self.settings)
+            .expect("the test settings should be valid")
+    }
+}
+
+/// Builds a synthesizer of the synthetic SoundFont without the reverb and chorus.
+pub fn synthesizer() -> crate::prelude::Synthesizer {
+    SynthesizerBuilder::new().build()
+}
+
+/// Renders `length` samples and returns the left channel.
+pub fn render_left(synth: &mut crate::prelude::Synthesizer, length: usize) -> Vec<f32> {
+    let mut left = vec![0_f32; length];
+    let mut right = vec![0_f32; length];
+    synth.render(&mut left, &mut right);
+    left
+}
+
+/// Gets the peak absolute value of the samples.
+pub fn peak(buffer: &[f32]) -> f32 {
+    buffer.iter().fold(0_f32, |max, x| max.max(x.abs()))
+}
+
+/// Gets the position of the first non-zero sample.
+pub fn first_sound(buffer: &[f32]) -> Option<usize> {
+    buffer.iter().position(|x| *x != 0_f32)
+}
@@ -0,0 +1,152 @@
+use super::utils::*;
+use crate::prelude::*;
+
+// Builds a synthesizer of 8 voices using the policy, which renders the channels separately.
+fn stealing_synthesizer(policy: VoiceStealingPolicy) -> Synthesizer {
+    SynthesizerBuilder::new()
+        .settings(|settings| {
+            settings.maximum_polyphony = 8;
+            settings.voice_stealing_policy = policy;
+            settings.enable_multi_output = true;
+        })
+        .build()
+}
+
+// Fills the polyphony with one note on each of the channels 0 to 7, in order,
+// rendering a block between the notes so that the voices have different ages.
+fn fill(synth: &mut Synthesizer, velocity: impl Fn(u8) -> u8) {
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    for channel in 0..8 {
+        synth.note_on(channel, 60 + channel, velocity(channel));
+        synth.render(&mut left, &mut right);
+    }
+    assert_eq!(synth.get_active_voice_count(), 8);
+}
+
+// Gets the channels which are still sounding, after the stolen voices have faded out.
+fn sounding_channels(synth: &mut Synthesizer) -> Vec<u8> {
+    let mut buffers = MultiOutputBuffers::new(256, false);
+    synth.render_multi(&mut buffers);
+    synth.render_multi(&mut buffers);
+    buffers
+        .channels
+        .iter()
+        .enumerate()
+        .filter(|(_, [left, right])| left.iter().chain(right.iter()).any(|x| *x != 0_f32))
+        .map(|(channel, _)| channel as u8)
+        .collect()
+}
+
+#[test]
+fn oldest_voice_is_stolen() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::Oldest);
+    fill(&mut synth, |_| 100);
+
+    synth.note_on(8, 72, 100);
+
+    assert_eq!(synth.get_active_voice_count(), 8);
+    assert_eq!(sounding_channels(&mut synth), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn quietest_voice_is_stolen() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::Quietest);
+    fill(&mut synth, |channel| if channel == 3 { 20 } else { 100 });
+
+    synth.note_on(8, 72, 100);
+
+    assert_eq!(sounding_channels(&mut synth), vec![0, 1, 2, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn released_voice_is_stolen_first() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::ReleasedFirst);
+    fill(&mut synth, |_| 100);
+
+    synth.note_off(5, 65);
+    let mut left = vec![0_f32; 64];
+    let mut right = vec![0_f32; 64];
+    synth.render(&mut left, &mut right);
+    assert_eq!(synth.get_active_voice_count(), 8);
+
+    synth.note_on(8, 72, 100);
+
+    assert_eq!(sounding_channels(&mut synth), vec![0, 1, 2, 3, 4, 6, 7, 8]);
+}
+
+#[test]
+fn same_note_is_stolen_first() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::SameNoteFirst);
+    fill(&mut synth, |_| 100);
+
+    // Retrigger the note of channel 4 rather than dropping a note of another channel.
+    synth.note_on(4, 64, 100);
+
+    assert_eq!(synth.get_active_voice_count(), 8);
+    assert_eq!(sounding_channels(&mut synth), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn lowest_channel_priority_is_stolen_first() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::LowestChannelPriority);
+    for channel in 0..16 {
+        synth.set_channel_priority(channel, 100);
+    }
+    synth.set_channel_priority(6, 10);
+    assert_eq!(synth.get_channel_priority(6), 10);
+    assert_eq!(synth.get_channel_priority(0), 100);
+
+    fill(&mut synth, |_| 100);
+    synth.note_on(8, 72, 100);
+
+    assert_eq!(sounding_channels(&mut synth), vec![0, 1, 2, 3, 4, 5, 7, 8]);
+
+    // The priority is not a MIDI state, so it survives a reset.
+    synth.reset();
+    assert_eq!(synth.get_channel_priority(6), 10);
+}
+
+#[test]
+fn stolen_voice_fades_out() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::Oldest);
+    fill(&mut synth, |_| 100);
+
+    synth.note_on(8, 72, 100);
+    assert_eq!(synth.get_active_voice_count(), 8);
+
+    let mut buffers = MultiOutputBuffers::new(512, false);
+    synth.render_multi(&mut buffers);
+    let [left, right] = &buffers.channels[0];
+
+    // The stolen voice keeps sounding through the first block, fading out within a few milliseconds.
+    let level = |range: core::ops::Range<usize>| {
+        left[range.clone()]
+            .iter()
+            .chain(right[range].iter())
+            .fold(0_f32, |max, x| max.max(x.abs()))
+    };
+    assert!(level(0..32) > 0_f32);
+    assert!(level(32..64) < level(0..32));
+    assert_eq!(level(192..512), 0_f32);
+
+    // The new voice starts right away.
+    assert!(buffers.channels[8][0][..64].iter().any(|x| *x != 0_f32));
+}
+
+#[test]
+fn many_steals_keep_the_polyphony() {
+    let mut synth = stealing_synthesizer(VoiceStealingPolicy::Oldest);
+    fill(&mut synth, |_| 100);
+
+    // More steals in a single block than the extra slots for the fading voices.
+    for key in 30..60 {
+        synth.note_on(8, key, 100);
+        assert_eq!(synth.get_active_voice_count(), 8);
+    }
+
+    let mut left = vec![0_f32; 1024];
+    let mut right = vec![0_f32; 1024];
+    synth.render(&mut left, &mut right);
+    assert_eq!(synth.get_active_voice_count(), 8);
+}
@@ -0,0 +1,192 @@
+use super::utils::*;
+use crate::prelude::*;
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn pcm_wav_has_valid_header() {
+    let left = [0_f32, 0.5, -1.0];
+    let right = [0_f32, -0.5, 1.0];
+
+    let mut bytes = Vec::new();
+    write_wav(
+        &mut bytes,
+        48000,
+        &[&left, &right],
+        WavFormat::Pcm16,
+        Dither::None,
+    )
+    .unwrap();
+
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(read_u16(&bytes, 20), 1);
+    assert_eq!(read_u16(&bytes, 22), 2);
+    assert_eq!(read_u32(&bytes, 24), 48000);
+    assert_eq!(read_u32(&bytes, 28), 48000 * 4);
+    assert_eq!(read_u16(&bytes, 32), 4);
+    assert_eq!(read_u16(&bytes, 34), 16);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(read_u32(&bytes, 40), 12);
+
+    let samples: Vec<i16> = bytes[44..]
+        .chunks(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    assert_eq!(samples, [0, 0, 16384, -16384, -32768, 32767]);
+}
+
+#[test]
+fn pcm24_and_float_wav_have_valid_headers() {
+    let mono = [0.25_f32; 3];
+
+    let mut bytes = Vec::new();
+    write_wav(&mut bytes, 44100, &[&mono], WavFormat::Pcm24, Dither::None).unwrap();
+    assert_eq!(read_u16(&bytes, 32), 3);
+    assert_eq!(read_u16(&bytes, 34), 24);
+    assert_eq!(read_u32(&bytes, 40), 9);
+    // The odd-sized data chunk is padded.
+    assert_eq!(bytes.len(), 44 + 10);
+    assert_eq!(&bytes[44..47], &[0x00, 0x00, 0x20]);
+
+    let mut bytes = Vec::new();
+    write_wav(
+        &mut bytes,
+        44100,
+        &[&mono],
+        WavFormat::Float32,
+        Dither::None,
+    )
+    .unwrap();
+    assert_eq!(read_u16(&bytes, 20), 3);
+    assert_eq!(&bytes[36..40], b"fact");
+    assert_eq!(read_u32(&bytes, 44), 3);
+    assert_eq!(&bytes[48..52], b"data");
+    assert_eq!(f32::from_le_bytes(bytes[56..60].try_into().unwrap()), 0.25);
+
+    assert!(
+        write_wav(
+            &mut Vec::new(),
+            44100,
+            &[&mono, &mono[1..]],
+            WavFormat::Pcm16,
+            Dither::None
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn sample_wav_has_loop_metadata() {
+    let sound_font = synthetic_sound_font();
+    let sample = &sound_font.get_sample_headers()[0];
+
+    let mut bytes = Vec::new();
+    sample
+        .write_wav(sound_font.get_wave_data(), &mut bytes)
+        .unwrap();
+
+    assert_eq!(read_u16(&bytes, 22), 1);
+    assert_eq!(read_u32(&bytes, 24), 44100);
+    assert_eq!(&bytes[36..40], b"smpl");
+    assert_eq!(read_u32(&bytes, 40), 60);
+    let smpl = &bytes[44..104];
+    // The unity note, the pitch fraction and the number of loops.
+    assert_eq!(read_u32(smpl, 12), 69);
+    assert_eq!(read_u32(smpl, 16), 0);
+    assert_eq!(read_u32(smpl, 28), 1);
+    // The loop start and the inclusive loop end.
+    assert_eq!(read_u32(smpl, 44), 100);
+    assert_eq!(read_u32(smpl, 48), 4299);
+
+    // A pitch correction of 10 cents means that the sample is 10 cents flat.
+    let mut corrected = sample.clone();
+    corrected.pitch_correction = 10;
+    let mut corrected_bytes = Vec::new();
+    corrected
+        .write_wav(sound_font.get_wave_data(), &mut corrected_bytes)
+        .unwrap();
+    assert_eq!(read_u32(&corrected_bytes, 44 + 12), 68);
+    assert_eq!(read_u32(&corrected_bytes, 44 + 16), 3865470566);
+
+    assert_eq!(&bytes[104..108], b"data");
+    assert_eq!(read_u32(&bytes, 108), 2 * 4400);
+    assert_eq!(
+        i16::from_le_bytes([bytes[112 + 50], bytes[113 + 50]]),
+        sound_font.get_wave_data()[25]
+    );
+}
+
+#[test]
+fn preset_samples_are_exported() {
+    let sound_font = synthetic_sound_font();
+    let preset = &sound_font.get_presets()[0];
+    assert_eq!(sound_font.get_preset_sample_ids(preset), [0]);
+
+    let directory = std::env::temp_dir().join(format!("midix_synth_export_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let paths = sound_font
+        .export_preset_samples(preset, &directory)
+        .unwrap();
+
+    assert_eq!(paths.len(), 1);
+    assert!(paths[0].ends_with("0000_Sine.wav"));
+    let bytes = std::fs::read(&paths[0]).unwrap();
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn noise_shaping_keeps_each_channel_separate() {
+    // Noise shaping feeds the error of each sample back into the next sample of the channel,
+    // which moves the noise away from low frequencies.
+    // The error of another channel would only add more noise.
+    let inputs: Vec<Vec<f32>> = (0..3)
+        .map(|ch| {
+            (0..10000)
+                .map(|t| 0.3_f32 * (t as f32 * 0.01 * (ch + 1) as f32).sin())
+                .collect()
+        })
+        .collect();
+    let channels: Vec<&[f32]> = inputs.iter().map(|input| input.as_slice()).collect();
+
+    let mut bytes = Vec::new();
+    write_wav(
+        &mut bytes,
+        44100,
+        &channels,
+        WavFormat::Pcm16,
+        Dither::NoiseShaped,
+    )
+    .unwrap();
+
+    let samples: Vec<i16> = bytes[44..]
+        .chunks(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    for (ch, input) in inputs.iter().enumerate() {
+        let noise: Vec<f64> = samples
+            .iter()
+            .skip(ch)
+            .step_by(3)
+            .zip(input)
+            .map(|(sample, x)| *sample as f64 - *x as f64 * 32768.0)
+            .collect();
+        // The RMS of the noise averaged over 100 samples, which is a crude low-pass filter.
+        let low = noise
+            .chunks(100)
+            .map(|chunk| (chunk.iter().sum::<f64>() / 100.0).powi(2))
+            .sum::<f64>()
+            / 100.0;
+        assert!(low.sqrt() < 0.03, "{ch} {}", low.sqrt());
+    }
+}
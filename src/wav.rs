@@ -0,0 +1,142 @@
+//! Writing waveforms to WAV files.
+
+use std::io::{self, Write};
+
+use bevy_platform::prelude::*;
+
+use crate::{prelude::*, synthesizer::Quantizer};
+
+/// Specifies the sample format of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 16-bit integer PCM.
+    Pcm16,
+    /// 24-bit integer PCM.
+    Pcm24,
+    /// 32-bit floating point.
+    Float32,
+}
+
+impl WavFormat {
+    fn get_bits_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Pcm24 => 24,
+            WavFormat::Float32 => 32,
+        }
+    }
+}
+
+/// Writes a WAV file.
+///
+/// # Arguments
+///
+/// * `writer` - The destination of the WAV file.
+/// * `sample_rate` - The sample rate of the waveform.
+/// * `channels` - The samples of each channel, which must be the same length.
+/// * `format` - The sample format of the file.
+/// * `dither` - The dithering applied when the samples are converted to integers.
+pub fn write_wav<W: Write + ?Sized>(
+    writer: &mut W,
+    sample_rate: u32,
+    channels: &[&[f32]],
+    format: WavFormat,
+    dither: Dither,
+) -> io::Result<()> {
+    let length = channels.first().map(|channel| channel.len()).unwrap_or(0);
+    if channels.iter().any(|channel| channel.len() != length) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the channels must be the same length",
+        ));
+    }
+
+    let bytes_per_sample = format.get_bits_per_sample() as usize / 8;
+    let mut data = Vec::with_capacity(length * channels.len() * bytes_per_sample);
+    let mut quantizer = Quantizer::new(channels.len());
+    quantizer.set_dither(dither);
+    for t in 0..length {
+        for (ch, channel) in channels.iter().enumerate() {
+            let value = channel[t];
+            match format {
+                WavFormat::Pcm16 => {
+                    let sample = quantizer.quantize(ch, value, 16) as i16;
+                    data.extend(sample.to_le_bytes());
+                }
+                WavFormat::Pcm24 => {
+                    let sample = quantizer.quantize(ch, value, 24);
+                    data.extend(&sample.to_le_bytes()[..3]);
+                }
+                WavFormat::Float32 => data.extend(value.to_le_bytes()),
+            }
+        }
+    }
+
+    let mut chunks = Vec::new();
+    if format == WavFormat::Float32 {
+        // Non-PCM formats require the number of frames.
+        chunks.push((*b"fact", (length as u32).to_le_bytes().to_vec()));
+    }
+
+    write_riff(
+        writer,
+        sample_rate,
+        channels.len() as u16,
+        format,
+        &chunks,
+        &data,
+    )
+}
+
+// Writes a RIFF WAVE file with the extra chunks placed before the data.
+pub(crate) fn write_riff<W: Write + ?Sized>(
+    writer: &mut W,
+    sample_rate: u32,
+    channel_count: u16,
+    format: WavFormat,
+    chunks: &[([u8; 4], Vec<u8>)],
+    data: &[u8],
+) -> io::Result<()> {
+    let bits_per_sample = format.get_bits_per_sample();
+    let block_align = channel_count * bits_per_sample / 8;
+    let format_tag: u16 = match format {
+        WavFormat::Float32 => 3,
+        _ => 1,
+    };
+
+    let mut fmt = Vec::with_capacity(16);
+    fmt.extend(format_tag.to_le_bytes());
+    fmt.extend(channel_count.to_le_bytes());
+    fmt.extend(sample_rate.to_le_bytes());
+    fmt.extend((sample_rate * block_align as u32).to_le_bytes());
+    fmt.extend(block_align.to_le_bytes());
+    fmt.extend(bits_per_sample.to_le_bytes());
+
+    // Chunks are padded to an even size.
+    let chunk_size = |data: &[u8]| 8 + data.len() + data.len() % 2;
+    let riff_size = 4
+        + chunk_size(&fmt)
+        + chunks
+            .iter()
+            .map(|(_, data)| chunk_size(data))
+            .sum::<usize>()
+        + chunk_size(data);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(riff_size as u32).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    for (id, data) in [(b"fmt ", fmt.as_slice())]
+        .into_iter()
+        .chain(chunks.iter().map(|(id, data)| (id, data.as_slice())))
+        .chain([(b"data", data)])
+    {
+        writer.write_all(id)?;
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(data)?;
+        if data.len() % 2 == 1 {
+            writer.write_all(&[0])?;
+        }
+    }
+
+    Ok(())
+}